@@ -0,0 +1,33 @@
+#!/bin/sh
+# Measures operator decoding with `benches/walk.rs` at an older revision and
+# in the working tree, on the same modules.
+#
+#     benches/compare.sh <revision> <module.wasm>...
+#
+# The older revision is checked out into a temporary worktree, with
+# `walk.rs` added as an example, since it may not have the bench target.
+set -e
+
+if [ $# -lt 2 ]; then
+    echo "usage: $0 <revision> <module.wasm>..." >&2
+    exit 1
+fi
+rev=$1
+shift
+
+root=$(git rev-parse --show-toplevel)
+modules=
+for module in "$@"; do
+    modules="$modules $(realpath "$module")"
+done
+
+tmp=$(mktemp -d)
+trap 'git -C "$root" worktree remove --force "$tmp/tree"; rm -rf "$tmp"' EXIT
+git -C "$root" worktree add --detach --quiet "$tmp/tree" "$rev"
+mkdir -p "$tmp/tree/examples"
+cp "$root/benches/walk.rs" "$tmp/tree/examples/walk.rs"
+
+echo "$rev:"
+(cd "$tmp/tree" && cargo run --quiet --release --example walk -- $modules)
+echo "working tree:"
+(cd "$root" && cargo bench --quiet --bench walk -- $modules)
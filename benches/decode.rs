@@ -0,0 +1,220 @@
+//! Decoding throughput.
+//!
+//! Run with `cargo bench --bench decode`. This measures a generated module
+//! with a few thousand functions, and real modules: the paths given after
+//! `--`, or else every `.wasm` file in `benches/corpus`. That directory is
+//! not checked in; the modules the `wasmparser` crate benchmarks with are
+//! a good set, built by rustc and emscripten:
+//!
+//!     mkdir -p benches/corpus
+//!     curl -sL https://static.crates.io/crates/wasmparser/wasmparser-0.89.1.crate |
+//!         tar xz -C benches/corpus --strip-components=2 --wildcards '*/benches/*.wasm'
+//!     cargo bench --bench decode
+//!
+//! Modules that use proposals the reader does not support are skipped.
+//!
+//! The `leb128` cases compare the slice-based reader with the `leb128`
+//! crate reading from an `io::Read`, which is how the reader used to work.
+//! `benches/compare.sh` compares whole modules with an older revision.
+
+extern crate leb128;
+extern crate wasm;
+
+use std::env::args;
+use std::fs::File;
+use std::hint::black_box;
+use std::fs::read_dir;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use wasm::ast;
+use wasm::reader::bytecode::{Op, OpCode, MemoryImmediate, BrTable};
+use wasm::reader::{
+    self, Module, SectionContent, ValueType, InlineSignatureType, Local, OperatorVisitor,
+    read_var_u32
+};
+
+/// Runs `f` at least ten times and for at least two seconds, and returns
+/// the fastest run.
+fn measure<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut best = Duration::from_secs(u64::MAX);
+    let mut runs = 0;
+    while runs < 10 || start.elapsed() < Duration::from_secs(2) {
+        let run = Instant::now();
+        f();
+        best = best.min(run.elapsed());
+        runs += 1;
+    }
+    best
+}
+
+fn report(name: &str, bytes: usize, time: Duration) {
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    let secs = time.as_secs_f64();
+    println!("{:<40} {:>12.1} us {:>10.1} MiB/s", name, secs * 1e6, mb / secs);
+}
+
+/// A module with many functions whose bodies mix the common operators,
+/// with indices and constants on both sides of the single byte LEB128
+/// range.
+fn generate() -> Vec<u8> {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.types.push(ast::FunctionType {
+        params: vec![ValueType::I32, ValueType::I32],
+        return_type: Some(ValueType::I32),
+    });
+    module.memories.push(ast::Memory {
+        limits: reader::ResizableLimits {
+            initial: 1,
+            maximum: None,
+            shared: false,
+        },
+    });
+    let functions = 4000;
+    for index in 0..functions {
+        let mut ops = vec![];
+        for i in 0..100u32 {
+            ops.push(Op::Block(InlineSignatureType::Empty));
+            ops.push(Op::GetLocal(i % 2));
+            ops.push(Op::I32Const((i * 37) as i32 - 1000));
+            ops.push(Op::I32Add);
+            ops.push(Op::I32Load(MemoryImmediate {
+                flags: 2,
+                offset: i * 8,
+            }));
+            ops.push(Op::SetLocal(2 + i % 3));
+            ops.push(Op::GetLocal(2));
+            ops.push(Op::BrIf(0));
+            ops.push(Op::GetLocal(3));
+            ops.push(Op::GetLocal(4));
+            ops.push(Op::Call((index + i) % functions));
+            ops.push(Op::Drop);
+            ops.push(Op::GetLocal(3));
+            ops.push(Op::BrTable(BrTable::new(&[0, 0, 0], 0)));
+            ops.push(Op::End);
+        }
+        ops.push(Op::GetLocal(0));
+        ops.push(Op::End);
+        module.functions.push(ast::Function {
+            ty: 0,
+            locals: vec![Local {
+                count: 3,
+                ty: ValueType::I32,
+            }],
+            body: ops,
+        });
+    }
+    module.encode()
+}
+
+struct CountOps(usize);
+
+impl<'a> OperatorVisitor<'a> for CountOps {
+    type Error = reader::Error;
+
+    fn visit_operator(&mut self, _offset: usize, _code: OpCode) -> Result<(), reader::Error> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+fn bench_module(name: &str, buf: &[u8]) {
+    let module = Module::new(buf).expect("not a wasm module");
+    if let Err(err) = module.validate() {
+        println!("{:<40} skipped: {}", name, err);
+        return
+    }
+    report(&format!("{}: sections", name), buf.len(), measure(|| {
+        for section in module.strict_sections() {
+            black_box(section.unwrap());
+        }
+    }));
+    report(&format!("{}: operators", name), buf.len(), measure(|| {
+        for section in module.sections() {
+            if let Ok(SectionContent::Code(code)) = section.unwrap().content() {
+                for body in code.entries() {
+                    for op in body.unwrap().operators().unwrap() {
+                        black_box(op.unwrap());
+                    }
+                }
+            }
+        }
+    }));
+    report(&format!("{}: visitor", name), buf.len(), measure(|| {
+        let mut count = CountOps(0);
+        for section in module.sections() {
+            if let Ok(SectionContent::Code(code)) = section.unwrap().content() {
+                for body in code.entries() {
+                    body.unwrap().visit_operators(&mut count).unwrap();
+                }
+            }
+        }
+        black_box(count.0);
+    }));
+    report(&format!("{}: ast", name), buf.len(), measure(|| {
+        black_box(module.decode().unwrap());
+    }));
+    report(&format!("{}: validate", name), buf.len(), measure(|| {
+        black_box(module.validate().is_ok());
+    }));
+}
+
+fn bench_leb128() {
+    // Mostly single byte values, like the indices in real code.
+    let mut buf = vec![];
+    let mut count = 0;
+    for i in 0..1_000_000u32 {
+        let value = if i % 8 == 0 { i * 1000 } else { i % 128 };
+        wasm::writer::write_varuint(&mut buf, value as u64);
+        count += 1;
+    }
+    report("leb128: slice", buf.len(), measure(|| {
+        let mut iter = &buf[..];
+        for _ in 0..count {
+            black_box(read_var_u32(&mut iter).unwrap());
+        }
+    }));
+    report("leb128: leb128 crate", buf.len(), measure(|| {
+        let mut reader = &buf[..];
+        for _ in 0..count {
+            black_box(leb128::read::unsigned(&mut reader).unwrap());
+        }
+    }));
+}
+
+/// The paths of the modules to measure.
+fn modules() -> Vec<String> {
+    let paths: Vec<String> = args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    if !paths.is_empty() {
+        return paths
+    }
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/corpus");
+    let mut paths: Vec<String> = match read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .map(|path| path.display().to_string())
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths
+}
+
+fn main() {
+    bench_leb128();
+    bench_module("generated", &generate());
+    for path in modules() {
+        let mut buf = vec![];
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut buf))
+            .unwrap_or_else(|_| panic!("Failed to read {}", path));
+        let name = Path::new(&path).file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+        bench_module(&name, &buf);
+    }
+}
@@ -0,0 +1,85 @@
+//! Operator decoding throughput, comparable across revisions.
+//!
+//! This walks every local and operator of every function body using only
+//! the reader API the crate started with (`Module::new`, `sections`,
+//! `content`, `CodeSection::entries` and `FunctionBody::contents`), so the
+//! same file also builds against older revisions of the crate.
+//! `benches/compare.sh` uses that to measure an older revision and the
+//! working tree on the same modules:
+//!
+//!     cargo bench --bench walk -- path/to/module.wasm...
+//!     benches/compare.sh <revision> path/to/module.wasm...
+//!
+//! Only the standard library and `wasm` may be used here.
+
+extern crate wasm;
+
+use std::env::args;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use wasm::reader::{Module, SectionContent, FunctionPart};
+
+/// Runs `f` at least ten times and for at least two seconds, and returns
+/// the fastest run.
+fn measure<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut best = Duration::from_secs(u64::MAX);
+    let mut runs = 0;
+    while runs < 10 || start.elapsed() < Duration::from_secs(2) {
+        let run = Instant::now();
+        f();
+        best = best.min(run.elapsed());
+        runs += 1;
+    }
+    best
+}
+
+/// Decodes every function body of `buf`, and returns the number of locals
+/// and operators, or a description of the first error.
+fn walk(buf: &[u8]) -> Result<usize, String> {
+    let module = Module::new(buf).map_err(|err| format!("{:?}", err))?;
+    let mut parts = 0;
+    for section in module.sections() {
+        let section = section.map_err(|err| format!("{:?}", err))?;
+        if let Ok(SectionContent::Code(code)) = section.content() {
+            for body in code.entries() {
+                let body = body.map_err(|err| format!("{:?}", err))?;
+                for part in body.contents() {
+                    match part.map_err(|err| format!("{:?}", err))? {
+                        FunctionPart::Local(local) => { black_box(local); },
+                        FunctionPart::Op(op) => { black_box(op); },
+                    }
+                    parts += 1;
+                }
+            }
+        }
+    }
+    Ok(parts)
+}
+
+fn main() {
+    for path in args().skip(1).filter(|arg| !arg.starts_with("--")) {
+        let mut buf = vec![];
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut buf))
+            .unwrap_or_else(|_| panic!("Failed to read {}", path));
+        let name = Path::new(&path).file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+        let parts = match walk(&buf) {
+            Ok(parts) => parts,
+            Err(err) => {
+                println!("{:<24} skipped: {}", name, err);
+                continue
+            },
+        };
+        let time = measure(|| {
+            black_box(walk(&buf).unwrap());
+        });
+        let mb = buf.len() as f64 / (1024.0 * 1024.0);
+        let secs = time.as_secs_f64();
+        println!("{:<24} {:>12.1} us {:>10.1} MiB/s {:>10} parts",
+            name, secs * 1e6, mb / secs, parts);
+    }
+}
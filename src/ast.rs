@@ -0,0 +1,459 @@
+//! An owned, fully decoded representation of a module.
+//!
+//! Unlike the types in `reader`, everything here is owned and can be
+//! indexed and mutated freely. Build one with `reader::Module::decode`.
+//!
+//! # Serialization
+//!
+//! With the `serde` feature, every type here implements `Serialize` and
+//! `Deserialize`, and the `wasm-json` tool (feature `json`) converts
+//! between binaries and JSON. The format follows the definitions in this
+//! module and is kept stable:
+//!
+//! - Structs are objects whose keys are the field names, in declaration
+//!   order. `Option`s are `null` when absent.
+//! - Enums without data, such as `ValueType`, `ExternalKind`,
+//!   `SectionType` and `InlineSignatureType`, are their variant names:
+//!   `"I32"`, `"Function"`, `"Empty"`.
+//! - Enums with data are objects with a single key naming the variant:
+//!   `{"Function": 3}` for an `ImportKind`.
+//! - Ops follow the same rules: `"I32Add"`, `{"GetLocal": 0}`,
+//!   `{"CallIndirect": [1, false]}` and
+//!   `{"I32Load": {"flags": 2, "offset": 0}}`. `BrTable` is
+//!   `{"BrTable": {"arms": [0, 1], "default": 2}}`, and float constants
+//!   are bit patterns in hexadecimal, `{"F32Const": "0x3f800000"}`.
+//! - Byte strings, such as data segments and custom section payloads, are
+//!   arrays of numbers.
+//! - `encoding`, which keeps the sections of the original binary that
+//!   `encode` would not reproduce, is left out.
+//!
+//! A module decoded from JSON encodes to the same binary as the module the
+//! JSON was produced from with its `encoding` cleared: every integer in its
+//! shortest form.
+
+use reader;
+use reader::{
+    Error, SectionType, SectionContent, ValueType, ExternalKind, ResizableLimits,
+    Local, TypeEntry, ImportEntryContents, GlobalEntryEither, ElementEntry, DataEntry,
+    FunctionPart
+};
+use reader::bytecode::Op;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Module {
+    pub version: u32,
+    pub types: Vec<FunctionType>,
+    pub imports: Vec<Import>,
+    /// Functions defined by the module, combining the function and code
+    /// sections. Their indices start after the imported functions.
+    pub functions: Vec<Function>,
+    pub tables: Vec<Table>,
+    pub memories: Vec<Memory>,
+    /// The type index of each tag, for the exception handling proposal.
+    pub tags: Vec<u32>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub start: Option<u32>,
+    pub elements: Vec<Element>,
+    /// The number of data segments, from the DataCount section of the
+    /// bulk memory proposal.
+    pub data_count: Option<u32>,
+    pub data: Vec<Data>,
+    /// Custom sections, including the name section, in the order they
+    /// appeared.
+    pub custom_sections: Vec<CustomSection>,
+    /// The sections of the binary the module was decoded from, in order,
+    /// for `encode` to reproduce. Empty for a module built from scratch.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub encoding: Vec<SectionEncoding>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionType {
+    pub params: Vec<ValueType>,
+    pub return_type: Option<ValueType>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Import {
+    pub module: String,
+    pub field: String,
+    pub kind: ImportKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ImportKind {
+    /// A function with the given type index.
+    Function(u32),
+    Table(Table),
+    Memory(Memory),
+    Global(GlobalType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Function {
+    /// Index into the type section.
+    pub ty: u32,
+    pub locals: Vec<Local>,
+    /// The function's instructions, including the final `End`.
+    pub body: Vec<Op<'static>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Table {
+    pub element_type: u8,
+    pub limits: ResizableLimits,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Memory {
+    pub limits: ResizableLimits,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GlobalType {
+    pub ty: ValueType,
+    pub mutable: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Global {
+    pub ty: GlobalType,
+    /// The initializer expression, including the final `End`.
+    pub init: Vec<Op<'static>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Export {
+    pub field: String,
+    pub kind: ExternalKind,
+    pub index: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Element {
+    /// The table being initialized.
+    pub index: u32,
+    /// The offset expression, including the final `End`.
+    pub offset: Vec<Op<'static>>,
+    /// Function indices to place in the table.
+    pub elems: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Data {
+    /// The memory being initialized.
+    pub index: u32,
+    /// The offset expression, including the final `End`.
+    pub offset: Vec<Op<'static>>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CustomSection {
+    pub name: String,
+    pub payload: Vec<u8>,
+    /// The last non-custom section that came before this one, or `None` if
+    /// it came before all of them.
+    pub after: Option<SectionType>,
+}
+
+/// A section of the binary a module was decoded from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SectionEncoding {
+    pub id: SectionType,
+    /// For a section `encode` would write differently, for instance with
+    /// shorter LEB128 integers, the section as it was.
+    pub original: Option<OriginalSection>,
+}
+
+/// A section `encode` would not reproduce from the decoded contents.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OriginalSection {
+    /// The length and a hash of the section as `encode` writes it from the
+    /// decoded contents, which tell whether the module has been modified.
+    pub canonical_len: usize,
+    pub canonical_hash: u64,
+    /// The section as it was, id and size included.
+    pub bytes: Vec<u8>,
+}
+
+impl SectionEncoding {
+    /// Records section `id`, which was `original` in the binary and which
+    /// `encode` writes as `canonical`.
+    pub fn new(id: SectionType, canonical: &[u8], original: &[u8]) -> SectionEncoding {
+        let original = if canonical == original {
+            None
+        } else {
+            Some(OriginalSection {
+                canonical_len: canonical.len(),
+                canonical_hash: fnv1a(canonical),
+                bytes: original.to_owned(),
+            })
+        };
+        SectionEncoding {
+            id,
+            original,
+        }
+    }
+
+    /// The section as it was, if `written`, the section as `encode` writes
+    /// it now, is what it wrote for the decoded contents.
+    pub fn original_of(&self, written: &[u8]) -> Option<&[u8]> {
+        match self.original {
+            Some(ref original) if original.canonical_len == written.len() && original.canonical_hash == fnv1a(written) => {
+                Some(&original.bytes)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+impl Module {
+    pub fn decode(module: &reader::Module) -> Result<Module, Error> {
+        let mut result = Module {
+            version: module.version,
+            .. Module::default()
+        };
+        let mut function_types = vec![];
+        let mut after = None;
+        // Each section with its bytes in the binary.
+        let mut originals = vec![];
+        let mut sections = module.sections();
+        loop {
+            let start = sections.offset() - 8;
+            let section = match sections.next() {
+                Some(section) => section?,
+                None => break,
+            };
+            let bytes = &module.payload[start..sections.offset() - 8];
+            if section.id == SectionType::Named {
+                result.custom_sections.push(CustomSection {
+                    name: section.name.to_owned(),
+                    payload: section.payload.to_owned(),
+                    after,
+                });
+                originals.push((section.id, bytes));
+                continue
+            }
+            after = Some(section.id);
+            originals.push((section.id, bytes));
+            match section.content()? {
+                SectionContent::Type(types) => {
+                    for entry in types.entries() {
+                        let TypeEntry::Function(func) = entry?;
+                        let mut params = vec![];
+                        for param in func.params() {
+                            params.push(param?);
+                        }
+                        result.types.push(FunctionType {
+                            params,
+                            return_type: func.return_type,
+                        });
+                    }
+                },
+                SectionContent::Import(imports) => {
+                    for entry in imports.entries() {
+                        let entry = entry?;
+                        let kind = match entry.contents {
+                            ImportEntryContents::Function(ty) => ImportKind::Function(ty),
+                            ImportEntryContents::Table { element_type, limits } => ImportKind::Table(Table {
+                                element_type,
+                                limits,
+                            }),
+                            ImportEntryContents::Memory(limits) => ImportKind::Memory(Memory {
+                                limits,
+                            }),
+                            ImportEntryContents::Global { ty, mutable } => ImportKind::Global(GlobalType {
+                                ty,
+                                mutable,
+                            }),
+                        };
+                        result.imports.push(Import {
+                            module: entry.module.to_owned(),
+                            field: entry.field.to_owned(),
+                            kind,
+                        });
+                    }
+                },
+                SectionContent::Function(functions) => {
+                    for ty in functions.types() {
+                        function_types.push(ty?);
+                    }
+                },
+                SectionContent::Table(tables) => {
+                    for entry in tables.entries() {
+                        let entry = entry?;
+                        result.tables.push(Table {
+                            element_type: entry.ty,
+                            limits: entry.limits,
+                        });
+                    }
+                },
+                SectionContent::Memory(memories) => {
+                    for entry in memories.entries() {
+                        result.memories.push(Memory {
+                            limits: entry?.limits,
+                        });
+                    }
+                },
+                SectionContent::Tag(tags) => {
+                    for ty in tags.types() {
+                        result.tags.push(ty?);
+                    }
+                },
+                SectionContent::Global(globals) => {
+                    for entry in globals.entries() {
+                        match entry? {
+                            GlobalEntryEither::Entry(entry) => result.globals.push(Global {
+                                ty: GlobalType {
+                                    ty: entry.ty,
+                                    mutable: entry.mutable,
+                                },
+                                init: vec![],
+                            }),
+                            GlobalEntryEither::Op(op) => {
+                                let global = result.globals.last_mut().expect("op before global entry");
+                                global.init.push(op.into_owned());
+                            },
+                        }
+                    }
+                },
+                SectionContent::Export(exports) => {
+                    for entry in exports.entries() {
+                        let entry = entry?;
+                        result.exports.push(Export {
+                            field: entry.field.to_owned(),
+                            kind: entry.kind,
+                            index: entry.index,
+                        });
+                    }
+                },
+                SectionContent::Start(index) => {
+                    result.start = Some(index);
+                },
+                SectionContent::Elements(elements) => {
+                    for entry in elements.entries() {
+                        match entry? {
+                            ElementEntry::Index(index) => result.elements.push(Element {
+                                index,
+                                offset: vec![],
+                                elems: vec![],
+                            }),
+                            ElementEntry::Op(op) => {
+                                let element = result.elements.last_mut().expect("op before element entry");
+                                element.offset.push(op.into_owned());
+                            },
+                            ElementEntry::Elem(elem) => {
+                                let element = result.elements.last_mut().expect("elem before element entry");
+                                element.elems.push(elem);
+                            },
+                        }
+                    }
+                },
+                SectionContent::Code(code) => {
+                    if code.count as usize != function_types.len() {
+                        return Err(Error::Malformed("function and code section have different lengths"))
+                    }
+                    for (body, &ty) in code.entries().zip(function_types.iter()) {
+                        let mut function = Function {
+                            ty,
+                            locals: vec![],
+                            body: vec![],
+                        };
+                        for part in body?.contents() {
+                            match part? {
+                                FunctionPart::Local(local) => function.locals.push(local),
+                                FunctionPart::Op(op) => function.body.push(op.into_owned()),
+                            }
+                        }
+                        result.functions.push(function);
+                    }
+                },
+                SectionContent::Data(data) => {
+                    for entry in data.entries() {
+                        match entry? {
+                            DataEntry::Index(index) => result.data.push(Data {
+                                index,
+                                offset: vec![],
+                                data: vec![],
+                            }),
+                            DataEntry::Op(op) => {
+                                let data = result.data.last_mut().expect("op before data entry");
+                                data.offset.push(op.into_owned());
+                            },
+                            DataEntry::Data(bytes) => {
+                                let data = result.data.last_mut().expect("data before data entry");
+                                data.data.extend_from_slice(bytes);
+                            },
+                        }
+                    }
+                },
+                SectionContent::DataCount(count) => {
+                    result.data_count = Some(count);
+                },
+                SectionContent::Name(_) => unreachable!(),
+            }
+        }
+        if result.functions.len() != function_types.len() {
+            return Err(Error::Malformed("function and code section have different lengths"))
+        }
+        let mut counts = [0; 256];
+        for &(id, _) in &originals {
+            counts[id as usize] += 1;
+        }
+        let mut customs = 0;
+        let mut canonical = vec![];
+        for &(id, bytes) in &originals {
+            // A known section that appeared more than once is written as
+            // one, which none of the originals are.
+            if id != SectionType::Named && counts[id as usize] > 1 {
+                result.encoding.push(SectionEncoding {
+                    id,
+                    original: None,
+                });
+                continue
+            }
+            canonical.clear();
+            if id == SectionType::Named {
+                ::writer::write_custom_section(&mut canonical, &result.custom_sections[customs]);
+                customs += 1;
+            } else {
+                ::writer::write_section(&mut canonical, &result, id);
+            }
+            result.encoding.push(SectionEncoding::new(id, &canonical, bytes));
+        }
+        Ok(result)
+    }
+
+    /// Encodes the module into the binary format. See `writer::encode`.
+    pub fn encode(&self) -> Vec<u8> {
+        ::writer::encode(self)
+    }
+
+    /// Returns the custom section with the given name, if there is one.
+    pub fn custom_section(&self, name: &str) -> Option<&CustomSection> {
+        self.custom_sections.iter().find(|section| section.name == name)
+    }
+}
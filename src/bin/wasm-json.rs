@@ -0,0 +1,59 @@
+extern crate wasm;
+extern crate serde_json;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::env::args;
+use wasm::ast;
+use wasm::reader::Module;
+
+fn read_file(name: &str) -> Vec<u8> {
+    let mut buf = vec![];
+    File::open(name)
+        .unwrap_or_else(|_| panic!("Failed to open {}", name))
+        .read_to_end(&mut buf)
+        .unwrap_or_else(|_| panic!("Failed to read {}", name));
+    buf
+}
+
+fn to_json(input: &str) {
+    let buf = read_file(input);
+    let module = match Module::new(&buf).and_then(|module| module.decode()) {
+        Err(e) => {
+            println!("{}", e);
+            return
+        },
+        Ok(v) => v
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer_pretty(&mut stdout, &module).expect("Failed to write JSON");
+    writeln!(stdout).expect("Failed to write JSON");
+}
+
+fn to_wasm(input: &str, output: &str) {
+    let buf = read_file(input);
+    let module: ast::Module = match serde_json::from_slice(&buf) {
+        Err(e) => {
+            println!("{}", e);
+            return
+        },
+        Ok(v) => v
+    };
+    File::create(output)
+        .unwrap_or_else(|_| panic!("Failed to create {}", output))
+        .write_all(&module.encode())
+        .unwrap_or_else(|_| panic!("Failed to write {}", output));
+}
+
+fn main() {
+    let args = args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(|arg| &arg[..]).collect::<Vec<_>>()[..] {
+        [input] => to_json(input),
+        ["--to-wasm", input, output] => to_wasm(input, output),
+        _ => {
+            println!("usage: wasm-json <input binary>");
+            println!("       wasm-json --to-wasm <input json> <output binary>");
+        },
+    }
+}
@@ -3,21 +3,31 @@ extern crate wasm;
 use std::fs::File;
 use std::io::Read;
 use wasm::reader::*;
+use wasm::wat::Style;
 use std::env::args;
 
 fn main() {
     let mut buf = vec![];
-    let name = match args().skip(1).next() {
+    let mut style = None;
+    let mut name = None;
+    for arg in args().skip(1) {
+        match &arg[..] {
+            "--wat" => style = Some(Style::Flat),
+            "--wat-folded" => style = Some(Style::Folded),
+            _ => name = Some(arg),
+        }
+    }
+    let name = match name {
         None => {
-            println!("usage: wasm-print <input binary>");
+            println!("usage: wasm-print [--wat | --wat-folded] <input binary>");
             return
         },
         Some(x) => x
     };
     File::open(&name)
-        .expect(&format!("Failed to open {}", name))
+        .unwrap_or_else(|_| panic!("Failed to open {}", name))
         .read_to_end(&mut buf)
-        .expect(&format!("Failed to read {}", name));
+        .unwrap_or_else(|_| panic!("Failed to read {}", name));
     let module = match Module::new(&buf) {
         Err(e) => {
             println!("{}", e);
@@ -25,6 +35,13 @@ fn main() {
         },
         Ok(v) => v
     };
+    if let Some(style) = style {
+        match module.decode() {
+            Err(e) => println!("{}", e),
+            Ok(module) => print!("{}", module.to_wat(style)),
+        }
+        return
+    }
     for section in module.sections() {
         let section = match section {
             Err(e) => {
@@ -51,9 +68,7 @@ fn main() {
                         },
                         Ok(v) => v
                     };
-                    let func = match ty {
-                        TypeEntry::Function(func) => func,
-                    };
+                    let TypeEntry::Function(func) = ty;
                     print!("Function returning {:?}, accepting:", func.return_type);
                     for param in func.params() {
                         let param = match param {
@@ -65,7 +80,7 @@ fn main() {
                         };
                         print!(" {:?}", param);
                     }
-                    println!("");
+                    println!();
                 }
             },
             SectionContent::Import(imports) => {
@@ -167,7 +182,7 @@ fn main() {
                     match entry {
                         ElementEntry::Index(index) => {
                             if state != State::Default {
-                                println!("");
+                                println!();
                                 state = State::Default;
                             }
                             println!("element index={}", index);
@@ -185,7 +200,7 @@ fn main() {
                     }
                 }
                 if state != State::Default {
-                    println!("");
+                    println!();
                 }
             },
             SectionContent::Code(code) => {
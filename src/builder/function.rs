@@ -0,0 +1,264 @@
+use super::*;
+use reader::InlineSignatureType;
+use reader::bytecode::BrTable;
+
+// Like the handles of a `ModuleBuilder`, labels and locals carry the id of
+// the builder that made them first.
+
+/// A block, loop or if that branches can target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Label(u32, u32);
+
+/// A parameter or local of the function being built.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LocalHandle(u32, u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ScopeKind {
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+struct Scope {
+    label: Label,
+    kind: ScopeKind,
+}
+
+/// An instruction whose local index may not be known yet.
+enum Item {
+    Instr(Instr),
+    GetLocal(LocalHandle),
+    SetLocal(LocalHandle),
+    TeeLocal(LocalHandle),
+}
+
+/// Builds a function body with structured control flow.
+///
+/// Branches target `Label`s rather than relative depths, and locals are
+/// referred to by `LocalHandle`s. Locals are grouped by type when the body
+/// is finished, so that they are declared in as few runs as possible.
+/// Local indices in raw `GetLocal`, `SetLocal` and `TeeLocal` instructions
+/// count the parameters and then the locals in the order they were
+/// allocated, and are remapped the same way.
+///
+/// Errors, such as branching to a label whose scope has been closed, are
+/// reported by `finish`.
+pub struct FunctionBuilder {
+    id: u32,
+    params: Vec<ValueType>,
+    locals: Vec<ValueType>,
+    body: Vec<Item>,
+    scopes: Vec<Scope>,
+    labels: u32,
+    error: Option<BuildError>,
+}
+
+impl FunctionBuilder {
+    pub fn new(params: &[ValueType]) -> FunctionBuilder {
+        FunctionBuilder {
+            id: NEXT_BUILDER.fetch_add(1, Ordering::Relaxed),
+            params: params.to_vec(),
+            locals: vec![],
+            body: vec![],
+            scopes: vec![],
+            labels: 0,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, error: BuildError) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    pub fn param(&self, index: u32) -> Result<LocalHandle, BuildError> {
+        if index as usize >= self.params.len() {
+            return Err(BuildError::UnknownParam(index))
+        }
+        Ok(LocalHandle(self.id, index))
+    }
+
+    /// Allocates a new local of the given type.
+    pub fn local(&mut self, ty: ValueType) -> LocalHandle {
+        self.locals.push(ty);
+        LocalHandle(self.id, (self.params.len() + self.locals.len() - 1) as u32)
+    }
+
+    /// Appends an instruction. `Block`, `Loop`, `If`, `Else` and `End`
+    /// open and close scopes just like the corresponding methods do, but
+    /// the labels of scopes opened this way cannot be branched to by
+    /// handle.
+    pub fn instr<I: Into<Instr>>(&mut self, instr: I) {
+        match instr.into() {
+            Instr::Op(Op::Block(sig)) => {
+                self.block(sig);
+            },
+            Instr::Op(Op::Loop(sig)) => {
+                self.loop_(sig);
+            },
+            Instr::Op(Op::If(sig)) => {
+                self.if_(sig);
+            },
+            Instr::Op(Op::Else) => self.else_(),
+            Instr::Op(Op::End) => self.end(),
+            Instr::Op(Op::GetLocal(index)) => self.body.push(Item::GetLocal(LocalHandle(self.id, index))),
+            Instr::Op(Op::SetLocal(index)) => self.body.push(Item::SetLocal(LocalHandle(self.id, index))),
+            Instr::Op(Op::TeeLocal(index)) => self.body.push(Item::TeeLocal(LocalHandle(self.id, index))),
+            instr => self.body.push(Item::Instr(instr)),
+        }
+    }
+
+    pub fn call(&mut self, function: FuncHandle) {
+        self.body.push(Item::Instr(Instr::Call(function)));
+    }
+
+    pub fn call_indirect(&mut self, ty: TypeHandle) {
+        self.body.push(Item::Instr(Instr::CallIndirect(ty)));
+    }
+
+    pub fn get_global(&mut self, global: GlobalHandle) {
+        self.body.push(Item::Instr(Instr::GetGlobal(global)));
+    }
+
+    pub fn set_global(&mut self, global: GlobalHandle) {
+        self.body.push(Item::Instr(Instr::SetGlobal(global)));
+    }
+
+    pub fn get_local(&mut self, local: LocalHandle) {
+        self.body.push(Item::GetLocal(local));
+    }
+
+    pub fn set_local(&mut self, local: LocalHandle) {
+        self.body.push(Item::SetLocal(local));
+    }
+
+    pub fn tee_local(&mut self, local: LocalHandle) {
+        self.body.push(Item::TeeLocal(local));
+    }
+
+    fn open(&mut self, kind: ScopeKind, op: Op<'static>) -> Label {
+        let label = Label(self.id, self.labels);
+        self.labels += 1;
+        self.scopes.push(Scope {
+            label,
+            kind,
+        });
+        self.body.push(Item::Instr(Instr::Op(op)));
+        label
+    }
+
+    /// Opens a block. Branching to its label jumps to its end.
+    pub fn block(&mut self, sig: InlineSignatureType) -> Label {
+        self.open(ScopeKind::Block, Op::Block(sig))
+    }
+
+    /// Opens a loop. Branching to its label jumps back to its start.
+    pub fn loop_(&mut self, sig: InlineSignatureType) -> Label {
+        self.open(ScopeKind::Loop, Op::Loop(sig))
+    }
+
+    /// Opens an if, which may be followed by `else_`. Branching to its
+    /// label jumps to its end.
+    pub fn if_(&mut self, sig: InlineSignatureType) -> Label {
+        self.open(ScopeKind::If, Op::If(sig))
+    }
+
+    /// Starts the else arm of the innermost scope, which has to be an if.
+    pub fn else_(&mut self) {
+        match self.scopes.last_mut() {
+            Some(ref mut scope) if scope.kind == ScopeKind::If => scope.kind = ScopeKind::Else,
+            _ => return self.fail(BuildError::UnexpectedElse),
+        }
+        self.body.push(Item::Instr(Instr::Op(Op::Else)));
+    }
+
+    /// Closes the innermost scope.
+    pub fn end(&mut self) {
+        if self.scopes.pop().is_none() {
+            return self.fail(BuildError::UnexpectedEnd)
+        }
+        self.body.push(Item::Instr(Instr::Op(Op::End)));
+    }
+
+    /// Returns the relative depth of `label` from the current position.
+    fn depth(&mut self, label: Label) -> u32 {
+        match self.scopes.iter().rev().position(|scope| scope.label == label) {
+            Some(depth) => depth as u32,
+            None => {
+                self.fail(BuildError::LabelOutOfScope(label));
+                0
+            },
+        }
+    }
+
+    pub fn br(&mut self, label: Label) {
+        let depth = self.depth(label);
+        self.body.push(Item::Instr(Instr::Op(Op::Br(depth))));
+    }
+
+    pub fn br_if(&mut self, label: Label) {
+        let depth = self.depth(label);
+        self.body.push(Item::Instr(Instr::Op(Op::BrIf(depth))));
+    }
+
+    pub fn br_table(&mut self, labels: &[Label], default: Label) {
+        let arms = labels.iter().map(|&label| self.depth(label)).collect::<Vec<_>>();
+        let default = self.depth(default);
+        self.body.push(Item::Instr(Instr::Op(Op::BrTable(BrTable::new(&arms, default)))));
+    }
+
+    /// Checks that every scope has been closed, appends the `End` of the
+    /// function body, and assigns final local indices.
+    pub fn finish(self) -> Result<(Vec<Local>, Vec<Instr>), BuildError> {
+        if let Some(error) = self.error {
+            return Err(error)
+        }
+        if !self.scopes.is_empty() {
+            return Err(BuildError::UnclosedScope(self.scopes.len()))
+        }
+
+        // Group the locals by type, in the order each type first appears.
+        let mut runs: Vec<Local> = vec![];
+        for &ty in &self.locals {
+            match runs.iter_mut().find(|run| run.ty == ty) {
+                Some(run) => run.count += 1,
+                None => runs.push(Local {
+                    count: 1,
+                    ty,
+                }),
+            }
+        }
+        let mut next = vec![];
+        let mut start = self.params.len() as u32;
+        for run in &runs {
+            next.push(start);
+            start += run.count;
+        }
+        let mut indices = (0..self.params.len() as u32).collect::<Vec<_>>();
+        for &ty in &self.locals {
+            let run = runs.iter().position(|run| run.ty == ty).unwrap();
+            indices.push(next[run]);
+            next[run] += 1;
+        }
+
+        let id = self.id;
+        let index = |local: LocalHandle| match local {
+            LocalHandle(builder, index) if builder == id => indices.get(index as usize).cloned().ok_or(BuildError::UnknownLocal(local)),
+            _ => Err(BuildError::UnknownLocal(local)),
+        };
+        let mut body = vec![];
+        for item in self.body {
+            body.push(match item {
+                Item::Instr(instr) => instr,
+                Item::GetLocal(local) => Instr::Op(Op::GetLocal(index(local)?)),
+                Item::SetLocal(local) => Instr::Op(Op::SetLocal(index(local)?)),
+                Item::TeeLocal(local) => Instr::Op(Op::TeeLocal(index(local)?)),
+            });
+        }
+        body.push(Instr::Op(Op::End));
+        Ok((runs, body))
+    }
+}
@@ -0,0 +1,602 @@
+//! Builds modules without having to keep track of index spaces by hand.
+//!
+//! Functions, globals, memories and tables are referred to by handles,
+//! whether they are imported or defined. Imports always come first in
+//! their index space, so final indices are only known once everything has
+//! been added; `ModuleBuilder::finish` computes them and produces an
+//! `ast::Module`, and `finish_with_indices` also returns them. Handles
+//! remember the builder that made them, and using one with another
+//! builder is an error.
+
+use ast;
+use reader::{ValueType, ExternalKind, ResizableLimits, Local};
+use reader::bytecode::Op;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub mod function;
+pub use self::function::{
+    FunctionBuilder, Label, LocalHandle
+};
+
+quick_error! {
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum BuildError {
+        UndefinedFunction(handle: FuncHandle) {
+            description("Function was declared but never defined")
+            display("Function {:?} was declared but never defined", handle)
+        }
+        AlreadyDefined(handle: FuncHandle) {
+            description("Function was defined twice")
+            display("Function {:?} was defined twice", handle)
+        }
+        Imported(handle: FuncHandle) {
+            description("Imported function cannot be defined")
+            display("Function {:?} is imported and cannot be defined", handle)
+        }
+        UnknownHandle(handle: ExternHandle) {
+            description("Handle does not belong to this builder")
+            display("{:?} does not belong to this builder", handle)
+        }
+        UnknownType(handle: TypeHandle) {
+            description("Type handle does not belong to this builder")
+            display("{:?} does not belong to this builder", handle)
+        }
+        InvalidStart(handle: FuncHandle) {
+            description("Start function must have type [] -> []")
+            display("Start function {:?} must have type [] -> []", handle)
+        }
+        DuplicateExport(name: String) {
+            description("Duplicate export name")
+            display("Export name {:?} is used more than once", name)
+        }
+        UnknownParam(index: u32) {
+            description("Parameter index out of range")
+            display("Function has no parameter {}", index)
+        }
+        UnknownLocal(handle: LocalHandle) {
+            description("Local does not belong to this function")
+            display("{:?} does not belong to this function", handle)
+        }
+        LabelOutOfScope(label: Label) {
+            description("Branch to a label whose scope is not open")
+            display("Branch to {:?}, whose scope is not open", label)
+        }
+        UnexpectedElse {
+            description("Else outside of an if")
+        }
+        UnexpectedEnd {
+            description("End without an open scope")
+        }
+        UnclosedScope(count: usize) {
+            description("Scope was never closed")
+            display("{} scopes were never closed", count)
+        }
+    }
+}
+
+/// The id of the next `ModuleBuilder` or `FunctionBuilder`, which its
+/// handles carry.
+static NEXT_BUILDER: AtomicU32 = AtomicU32::new(0);
+
+/// Where an item lives in its index space before final indices are known.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Slot {
+    Imported(u32),
+    Defined(u32),
+}
+
+// The first field of each handle is the id of the builder that made it.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeHandle(u32, u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FuncHandle(u32, Slot);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GlobalHandle(u32, Slot);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryHandle(u32, Slot);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableHandle(u32, Slot);
+
+/// Anything that can be exported.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ExternHandle {
+    Function(FuncHandle),
+    Table(TableHandle),
+    Memory(MemoryHandle),
+    Global(GlobalHandle),
+}
+
+impl From<FuncHandle> for ExternHandle {
+    fn from(handle: FuncHandle) -> ExternHandle {
+        ExternHandle::Function(handle)
+    }
+}
+
+impl From<TableHandle> for ExternHandle {
+    fn from(handle: TableHandle) -> ExternHandle {
+        ExternHandle::Table(handle)
+    }
+}
+
+impl From<MemoryHandle> for ExternHandle {
+    fn from(handle: MemoryHandle) -> ExternHandle {
+        ExternHandle::Memory(handle)
+    }
+}
+
+impl From<GlobalHandle> for ExternHandle {
+    fn from(handle: GlobalHandle) -> ExternHandle {
+        ExternHandle::Global(handle)
+    }
+}
+
+impl TypeHandle {
+    /// The index into the type section of the builder that made the
+    /// handle. Types are never reordered, so this is final as soon as the
+    /// handle exists.
+    pub fn index(self) -> u32 {
+        self.1
+    }
+}
+
+/// An instruction whose indices may not be known yet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instr {
+    Op(Op<'static>),
+    Call(FuncHandle),
+    CallIndirect(TypeHandle),
+    GetGlobal(GlobalHandle),
+    SetGlobal(GlobalHandle),
+}
+
+impl From<Op<'static>> for Instr {
+    fn from(op: Op<'static>) -> Instr {
+        Instr::Op(op)
+    }
+}
+
+struct FunctionDef {
+    ty: TypeHandle,
+    code: Option<(Vec<Local>, Vec<Instr>)>,
+}
+
+struct GlobalDef {
+    ty: ast::GlobalType,
+    init: Vec<Instr>,
+}
+
+struct ElementDef {
+    table: TableHandle,
+    offset: Vec<Instr>,
+    elems: Vec<FuncHandle>,
+}
+
+struct DataDef {
+    memory: MemoryHandle,
+    offset: Vec<Instr>,
+    data: Vec<u8>,
+}
+
+pub struct ModuleBuilder {
+    id: u32,
+    version: u32,
+    types: Vec<ast::FunctionType>,
+    type_indices: HashMap<ast::FunctionType, TypeHandle>,
+    imports: Vec<ast::Import>,
+    /// The type of each imported function.
+    imported_functions: Vec<TypeHandle>,
+    imported_tables: u32,
+    imported_memories: u32,
+    imported_globals: u32,
+    functions: Vec<FunctionDef>,
+    tables: Vec<ast::Table>,
+    memories: Vec<ast::Memory>,
+    globals: Vec<GlobalDef>,
+    exports: Vec<(String, ExternHandle)>,
+    start: Option<FuncHandle>,
+    elements: Vec<ElementDef>,
+    data: Vec<DataDef>,
+    custom_sections: Vec<ast::CustomSection>,
+}
+
+/// The final indices of the handles of a module, returned by
+/// `ModuleBuilder::finish_with_indices`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Indices {
+    builder: u32,
+    types: u32,
+    /// The number of imported and defined items of each kind.
+    functions: (u32, u32),
+    tables: (u32, u32),
+    memories: (u32, u32),
+    globals: (u32, u32),
+}
+
+impl Indices {
+    fn slot(&self, counts: (u32, u32), builder: u32, slot: Slot) -> Option<u32> {
+        if builder != self.builder {
+            return None
+        }
+        match slot {
+            Slot::Imported(index) if index < counts.0 => Some(index),
+            Slot::Defined(index) if index < counts.1 => Some(counts.0 + index),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of a type, or `None` if the handle is from a
+    /// different builder.
+    pub fn ty(&self, handle: TypeHandle) -> Option<u32> {
+        match handle {
+            TypeHandle(builder, index) if builder == self.builder && index < self.types => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of a function, or `None` if the handle is from a
+    /// different builder.
+    pub fn function(&self, handle: FuncHandle) -> Option<u32> {
+        self.slot(self.functions, handle.0, handle.1)
+    }
+
+    pub fn table(&self, handle: TableHandle) -> Option<u32> {
+        self.slot(self.tables, handle.0, handle.1)
+    }
+
+    pub fn memory(&self, handle: MemoryHandle) -> Option<u32> {
+        self.slot(self.memories, handle.0, handle.1)
+    }
+
+    pub fn global(&self, handle: GlobalHandle) -> Option<u32> {
+        self.slot(self.globals, handle.0, handle.1)
+    }
+
+    fn resolve_type(&self, handle: TypeHandle) -> Result<u32, BuildError> {
+        self.ty(handle).ok_or(BuildError::UnknownType(handle))
+    }
+
+    fn resolve<H: Into<ExternHandle>>(index: Option<u32>, handle: H) -> Result<u32, BuildError> {
+        index.ok_or_else(|| BuildError::UnknownHandle(handle.into()))
+    }
+
+    fn instr(&self, instr: Instr) -> Result<Op<'static>, BuildError> {
+        Ok(match instr {
+            Instr::Op(op) => op,
+            Instr::Call(handle) => Op::Call(Indices::resolve(self.function(handle), handle)?),
+            Instr::CallIndirect(ty) => Op::CallIndirect(self.resolve_type(ty)?, false),
+            Instr::GetGlobal(handle) => Op::GetGlobal(Indices::resolve(self.global(handle), handle)?),
+            Instr::SetGlobal(handle) => Op::SetGlobal(Indices::resolve(self.global(handle), handle)?),
+        })
+    }
+
+    fn expr(&self, instrs: Vec<Instr>) -> Result<Vec<Op<'static>>, BuildError> {
+        instrs.into_iter().map(|instr| self.instr(instr)).collect()
+    }
+}
+
+impl Default for ModuleBuilder {
+    fn default() -> ModuleBuilder {
+        ModuleBuilder::new()
+    }
+}
+
+impl ModuleBuilder {
+    pub fn new() -> ModuleBuilder {
+        ModuleBuilder {
+            id: NEXT_BUILDER.fetch_add(1, Ordering::Relaxed),
+            version: 1,
+            types: vec![],
+            type_indices: HashMap::new(),
+            imports: vec![],
+            imported_functions: vec![],
+            imported_tables: 0,
+            imported_memories: 0,
+            imported_globals: 0,
+            functions: vec![],
+            tables: vec![],
+            memories: vec![],
+            globals: vec![],
+            exports: vec![],
+            start: None,
+            elements: vec![],
+            data: vec![],
+            custom_sections: vec![],
+        }
+    }
+
+    /// Sets the version written in the module header. Defaults to 1.
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// Returns the type with the given signature, adding it to the type
+    /// section if it is not there yet.
+    pub fn func_type(&mut self, params: &[ValueType], return_type: Option<ValueType>) -> TypeHandle {
+        let ty = ast::FunctionType {
+            params: params.to_vec(),
+            return_type,
+        };
+        if let Some(&handle) = self.type_indices.get(&ty) {
+            return handle
+        }
+        let handle = TypeHandle(self.id, self.types.len() as u32);
+        self.types.push(ty.clone());
+        self.type_indices.insert(ty, handle);
+        handle
+    }
+
+    fn import(&mut self, module: &str, field: &str, kind: ast::ImportKind) {
+        self.imports.push(ast::Import {
+            module: module.to_owned(),
+            field: field.to_owned(),
+            kind,
+        });
+    }
+
+    pub fn import_function(&mut self, module: &str, field: &str, ty: TypeHandle) -> FuncHandle {
+        self.import(module, field, ast::ImportKind::Function(ty.index()));
+        self.imported_functions.push(ty);
+        FuncHandle(self.id, Slot::Imported(self.imported_functions.len() as u32 - 1))
+    }
+
+    pub fn import_table(&mut self, module: &str, field: &str, table: ast::Table) -> TableHandle {
+        self.import(module, field, ast::ImportKind::Table(table));
+        self.imported_tables += 1;
+        TableHandle(self.id, Slot::Imported(self.imported_tables - 1))
+    }
+
+    pub fn import_memory(&mut self, module: &str, field: &str, limits: ResizableLimits) -> MemoryHandle {
+        self.import(module, field, ast::ImportKind::Memory(ast::Memory {
+            limits,
+        }));
+        self.imported_memories += 1;
+        MemoryHandle(self.id, Slot::Imported(self.imported_memories - 1))
+    }
+
+    pub fn import_global(&mut self, module: &str, field: &str, ty: ast::GlobalType) -> GlobalHandle {
+        self.import(module, field, ast::ImportKind::Global(ty));
+        self.imported_globals += 1;
+        GlobalHandle(self.id, Slot::Imported(self.imported_globals - 1))
+    }
+
+    /// Adds a function whose body is provided later with
+    /// `define_function`, so that it can be referred to before then, such
+    /// as by itself.
+    pub fn declare_function(&mut self, ty: TypeHandle) -> FuncHandle {
+        self.functions.push(FunctionDef {
+            ty,
+            code: None,
+        });
+        FuncHandle(self.id, Slot::Defined(self.functions.len() as u32 - 1))
+    }
+
+    /// Provides the body of a function added with `declare_function`. The
+    /// body has to end with `End`.
+    pub fn define_function(&mut self, handle: FuncHandle, locals: Vec<Local>, body: Vec<Instr>) -> Result<(), BuildError> {
+        let function = match handle {
+            FuncHandle(builder, _) if builder != self.id => return Err(BuildError::UnknownHandle(handle.into())),
+            FuncHandle(_, Slot::Defined(index)) => match self.functions.get_mut(index as usize) {
+                Some(function) => function,
+                None => return Err(BuildError::UnknownHandle(handle.into())),
+            },
+            FuncHandle(_, Slot::Imported(_)) => return Err(BuildError::Imported(handle)),
+        };
+        if function.code.is_some() {
+            return Err(BuildError::AlreadyDefined(handle))
+        }
+        function.code = Some((locals, body));
+        Ok(())
+    }
+
+    /// Provides the body of a function added with `declare_function`,
+    /// from a `FunctionBuilder`.
+    pub fn define_body(&mut self, handle: FuncHandle, body: FunctionBuilder) -> Result<(), BuildError> {
+        let (locals, body) = body.finish()?;
+        self.define_function(handle, locals, body)
+    }
+
+    /// Adds a function with the given body, which has to end with `End`.
+    pub fn function(&mut self, ty: TypeHandle, locals: Vec<Local>, body: Vec<Instr>) -> FuncHandle {
+        let handle = self.declare_function(ty);
+        self.functions.last_mut().unwrap().code = Some((locals, body));
+        handle
+    }
+
+    pub fn table(&mut self, table: ast::Table) -> TableHandle {
+        self.tables.push(table);
+        TableHandle(self.id, Slot::Defined(self.tables.len() as u32 - 1))
+    }
+
+    pub fn memory(&mut self, limits: ResizableLimits) -> MemoryHandle {
+        self.memories.push(ast::Memory {
+            limits,
+        });
+        MemoryHandle(self.id, Slot::Defined(self.memories.len() as u32 - 1))
+    }
+
+    /// Adds a global with the given initializer expression, which has to
+    /// end with `End`.
+    pub fn global(&mut self, ty: ast::GlobalType, init: Vec<Instr>) -> GlobalHandle {
+        self.globals.push(GlobalDef {
+            ty,
+            init,
+        });
+        GlobalHandle(self.id, Slot::Defined(self.globals.len() as u32 - 1))
+    }
+
+    pub fn export<H: Into<ExternHandle>>(&mut self, name: &str, item: H) {
+        self.exports.push((name.to_owned(), item.into()));
+    }
+
+    /// Makes `function` the start function. Its type has to be
+    /// `[] -> []`, which `finish` checks.
+    pub fn start(&mut self, function: FuncHandle) {
+        self.start = Some(function);
+    }
+
+    /// Places `elems` in `table`, starting at the result of the `offset`
+    /// expression.
+    pub fn element(&mut self, table: TableHandle, offset: Vec<Instr>, elems: Vec<FuncHandle>) {
+        self.elements.push(ElementDef {
+            table,
+            offset,
+            elems,
+        });
+    }
+
+    /// Places `data` in `memory`, starting at the result of the `offset`
+    /// expression.
+    pub fn data(&mut self, memory: MemoryHandle, offset: Vec<Instr>, data: Vec<u8>) {
+        self.data.push(DataDef {
+            memory,
+            offset,
+            data,
+        });
+    }
+
+    /// Adds a custom section, written after all other sections.
+    pub fn custom_section(&mut self, name: &str, payload: Vec<u8>) {
+        self.custom_sections.push(ast::CustomSection {
+            name: name.to_owned(),
+            payload,
+            after: Some(::reader::SectionType::Data),
+        });
+    }
+
+    /// The type index of a function, if the handle belongs to this
+    /// builder.
+    fn function_type(&self, handle: FuncHandle) -> Option<u32> {
+        match handle {
+            FuncHandle(builder, _) if builder != self.id => None,
+            FuncHandle(_, Slot::Imported(index)) => self.imported_functions.get(index as usize).map(|ty| ty.index()),
+            FuncHandle(_, Slot::Defined(index)) => self.functions.get(index as usize).map(|function| function.ty.index()),
+        }
+    }
+
+    /// Assigns final indices and produces the module.
+    pub fn finish(self) -> Result<ast::Module, BuildError> {
+        self.finish_with_indices().map(|(module, _)| module)
+    }
+
+    /// Like `finish`, but also returns the final index of every handle.
+    /// Indices are only known here, since imports come first in their
+    /// index space and can be added until the end.
+    pub fn finish_with_indices(self) -> Result<(ast::Module, Indices), BuildError> {
+        let indices = Indices {
+            builder: self.id,
+            types: self.types.len() as u32,
+            functions: (self.imported_functions.len() as u32, self.functions.len() as u32),
+            tables: (self.imported_tables, self.tables.len() as u32),
+            memories: (self.imported_memories, self.memories.len() as u32),
+            globals: (self.imported_globals, self.globals.len() as u32),
+        };
+
+        for &ty in &self.imported_functions {
+            indices.resolve_type(ty)?;
+        }
+        for function in &self.functions {
+            indices.resolve_type(function.ty)?;
+        }
+
+        if let Some(handle) = self.start {
+            let ty = Indices::resolve(self.function_type(handle), handle)?;
+            match self.types.get(ty as usize) {
+                Some(ty) if ty.params.is_empty() && ty.return_type.is_none() => (),
+                _ => return Err(BuildError::InvalidStart(handle)),
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut exports = vec![];
+        for (name, item) in self.exports {
+            let (kind, index) = match item {
+                ExternHandle::Function(handle) => (ExternalKind::Function, indices.function(handle)),
+                ExternHandle::Table(handle) => (ExternalKind::Table, indices.table(handle)),
+                ExternHandle::Memory(handle) => (ExternalKind::Memory, indices.memory(handle)),
+                ExternHandle::Global(handle) => (ExternalKind::Global, indices.global(handle)),
+            };
+            let index = Indices::resolve(index, item)?;
+            if !names.insert(name.clone()) {
+                return Err(BuildError::DuplicateExport(name))
+            }
+            exports.push(ast::Export {
+                field: name,
+                kind,
+                index,
+            });
+        }
+
+        let mut functions = vec![];
+        for (index, function) in self.functions.into_iter().enumerate() {
+            let (locals, body) = match function.code {
+                Some(code) => code,
+                None => return Err(BuildError::UndefinedFunction(FuncHandle(indices.builder, Slot::Defined(index as u32)))),
+            };
+            functions.push(ast::Function {
+                ty: function.ty.index(),
+                locals,
+                body: indices.expr(body)?,
+            });
+        }
+
+        let mut globals = vec![];
+        for global in self.globals {
+            globals.push(ast::Global {
+                ty: global.ty,
+                init: indices.expr(global.init)?,
+            });
+        }
+
+        let mut elements = vec![];
+        for element in self.elements {
+            let mut elems = vec![];
+            for handle in element.elems {
+                elems.push(Indices::resolve(indices.function(handle), handle)?);
+            }
+            elements.push(ast::Element {
+                index: Indices::resolve(indices.table(element.table), element.table)?,
+                offset: indices.expr(element.offset)?,
+                elems,
+            });
+        }
+
+        let mut data = vec![];
+        for segment in self.data {
+            data.push(ast::Data {
+                index: Indices::resolve(indices.memory(segment.memory), segment.memory)?,
+                offset: indices.expr(segment.offset)?,
+                data: segment.data,
+            });
+        }
+
+        let start = match self.start {
+            Some(handle) => indices.function(handle),
+            None => None,
+        };
+
+        let module = ast::Module {
+            version: self.version,
+            types: self.types,
+            imports: self.imports,
+            functions,
+            tables: self.tables,
+            memories: self.memories,
+            tags: vec![],
+            globals,
+            exports,
+            start,
+            data_count: None,
+            elements,
+            data,
+            custom_sections: self.custom_sections,
+            encoding: vec![],
+        };
+        Ok((module, indices))
+    }
+}
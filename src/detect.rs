@@ -0,0 +1,548 @@
+//! Detection of the proposals a module uses.
+//!
+//! `detect` finds the smallest `WasmFeatures` set a module can be read
+//! with, the offset of one place that needs each proposal, and the oldest
+//! release of the spec that includes them all. It does not validate the
+//! module.
+//!
+//! Sections are scanned entry by entry, with the encodings proposals add
+//! to them skipped over, so an entry that needs a proposal does not hide
+//! the rest of its section. In function bodies and constant expressions,
+//! the operators of every proposal the reader knows of are skipped over as
+//! well. Only a prefixed operator whose immediates are not known, such as
+//! one added to a proposal later, ends the scan of its function body, or
+//! of the rest of its section outside of the code section.
+//!
+//! The multi-memory and memory64 proposals are detected too, although the
+//! reader does not support them and `WasmFeatures` has no flag for them.
+
+use reader::{
+    Error, Module, ParserConfig, WasmFeatures, SectionsIterator, SectionType, SectionContent,
+    FunctionBody, ValueType, ResizableLimits, read_u8, read_bytes, read_var_u32, read_var_s33, read_varuint
+};
+use reader::bytecode::OpIterator;
+use alloc::vec::Vec;
+
+/// A proposal a module uses, and where.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FeatureUse {
+    /// The name of the proposal, as in `Error::Disabled`.
+    pub feature: &'static str,
+    /// The offset from the start of the module of the operator, entry or
+    /// section that uses the proposal.
+    pub offset: usize,
+}
+
+/// The result of `detect`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeatureReport {
+    /// The proposals the module uses, each once, in the order they were
+    /// found.
+    pub uses: Vec<FeatureUse>,
+    /// The same proposals as a set, but for those `WasmFeatures` has no
+    /// flag for.
+    pub features: WasmFeatures,
+}
+
+impl FeatureReport {
+    /// The oldest release of the spec that can run the module. See
+    /// `WasmFeatures::release`. A proposal `WasmFeatures` has no flag for
+    /// gives `None`.
+    pub fn release(&self) -> Option<&'static str> {
+        if self.uses.iter().any(|used| !WasmFeatures::wasm1().set(used.feature, true)) {
+            return None
+        }
+        self.features.release()
+    }
+
+    fn record(&mut self, feature: &'static str, offset: usize) {
+        if self.uses.iter().any(|used| used.feature == feature) {
+            return
+        }
+        self.features.set(feature, true);
+        self.uses.push(FeatureUse {
+            feature,
+            offset,
+        });
+    }
+
+    /// Records the proposal `err` names, if it is `Error::Disabled`.
+    /// `base` is the offset of the input of the reader that returned it.
+    fn record_error(&mut self, err: Error, base: usize) -> Result<(), Error> {
+        match err {
+            Error::Disabled(feature, offset) => {
+                self.record(feature, base + offset);
+                Ok(())
+            },
+            err => Err(err),
+        }
+    }
+}
+
+/// Finds the proposals a module uses. See the module documentation.
+pub fn detect(module: &Module) -> Result<FeatureReport, Error> {
+    let mvp = WasmFeatures::wasm1();
+    // Sections are split with every proposal enabled, but operators are
+    // read with none, so that those that need a proposal produce
+    // `Error::Disabled`.
+    let mut sections = SectionsIterator::new(module.payload, ParserConfig {
+        features: WasmFeatures::all(),
+        .. module.config
+    });
+    let mut detector = Detector {
+        report: FeatureReport::default(),
+        module,
+        config: ParserConfig {
+            features: mvp,
+            .. module.config
+        },
+        globals: vec![],
+        tables: 0,
+        memories: 0,
+    };
+    loop {
+        let offset = sections.offset();
+        let mut section = match sections.next() {
+            Some(section) => section?,
+            None => break,
+        };
+        if let Err(err) = mvp.check_section(section.id) {
+            detector.report.record_error(err, offset)?;
+        }
+        let payload = section.payload;
+        match section.id {
+            SectionType::Type => detector.types(payload)?,
+            SectionType::Import => detector.imports(payload)?,
+            SectionType::Table => detector.tables(payload)?,
+            SectionType::Memory => detector.memories(payload)?,
+            SectionType::Global => detector.globals(payload)?,
+            SectionType::Export => detector.exports(payload)?,
+            SectionType::Element => detector.elements(payload)?,
+            SectionType::Data => detector.data(payload)?,
+            SectionType::Code => {
+                section.config = detector.config;
+                if let SectionContent::Code(code) = section.content()? {
+                    for body in code.entries() {
+                        detector.body(&body?)?;
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(detector.report)
+}
+
+/// The state of `detect` between sections.
+struct Detector<'m, 'a: 'm> {
+    report: FeatureReport,
+    module: &'m Module<'a>,
+    /// The configuration of the module, without any proposals.
+    config: ParserConfig,
+    /// Whether each global is mutable, imports first.
+    globals: Vec<bool>,
+    tables: u32,
+    memories: u32,
+}
+
+impl<'m, 'a> Detector<'m, 'a> {
+    /// The offset of `iter`, a part of the module, from its start.
+    fn at(&self, iter: &[u8]) -> usize {
+        iter.as_ptr() as usize - self.module.payload.as_ptr() as usize + 8
+    }
+
+    /// Checks the parameter and result types of every function type.
+    fn types(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            let offset = self.at(iter);
+            // The form.
+            read_u8(&mut iter)?;
+            for &results in &[false, true] {
+                let len = read_var_u32(&mut iter)?;
+                if results && len > 1 {
+                    self.report.record("multi-value", offset);
+                }
+                for _ in 0..len {
+                    self.value_type(&mut iter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn imports(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            for _ in 0..2 {
+                let len = read_var_u32(&mut iter)?;
+                read_bytes(&mut iter, len as usize)?;
+            }
+            let offset = self.at(iter);
+            match read_u8(&mut iter)? {
+                0 => {
+                    read_var_u32(&mut iter)?;
+                },
+                1 => self.table(&mut iter)?,
+                2 => self.memory(&mut iter)?,
+                3 => {
+                    self.value_type(&mut iter)?;
+                    let mutable = read_u8(&mut iter)? != 0;
+                    if mutable {
+                        self.report.record("mutable-global", offset);
+                    }
+                    self.globals.push(mutable);
+                },
+                4 => {
+                    self.report.record("exceptions", offset);
+                    self.tag(&mut iter)?;
+                },
+                _ => return Err(Error::UnknownVariant("external kind")),
+            }
+        }
+        Ok(())
+    }
+
+    fn tables(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            self.table(&mut iter)?;
+        }
+        Ok(())
+    }
+
+    fn memories(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            self.memory(&mut iter)?;
+        }
+        Ok(())
+    }
+
+    fn globals(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            self.value_type(&mut iter)?;
+            self.globals.push(read_u8(&mut iter)? != 0);
+            iter = match self.ops(iter)? {
+                Some(rest) => rest,
+                None => return Ok(()),
+            };
+        }
+        Ok(())
+    }
+
+    fn exports(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            let len = read_var_u32(&mut iter)?;
+            read_bytes(&mut iter, len as usize)?;
+            let offset = self.at(iter);
+            let kind = read_u8(&mut iter)?;
+            let index = read_var_u32(&mut iter)?;
+            match kind {
+                0..=2 => (),
+                3 => {
+                    if self.globals.get(index as usize) == Some(&true) {
+                        self.report.record("mutable-global", offset);
+                    }
+                },
+                4 => self.report.record("exceptions", offset),
+                _ => return Err(Error::UnknownVariant("external kind")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the element segments. Any but the original kind, active with
+    /// function indices in table 0, needs the bulk memory proposal.
+    fn elements(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            let offset = self.at(iter);
+            // Bit 0 makes the segment passive, or declarative with bit 1.
+            // Otherwise bit 1 gives it a table index. Bit 2 replaces the
+            // function indices with expressions.
+            let flags = read_var_u32(&mut iter)?;
+            if flags > 7 {
+                return Err(Error::UnknownVariant("element segment flags"))
+            }
+            if flags != 0 {
+                self.report.record("bulk-memory", offset);
+            }
+            if flags & 3 == 3 {
+                // Declarative segments came with reference types.
+                self.report.record("reference-types", offset);
+            }
+            if flags & 1 == 0 {
+                if flags & 2 != 0 {
+                    read_var_u32(&mut iter)?;
+                }
+                iter = match self.ops(iter)? {
+                    Some(rest) => rest,
+                    None => return Ok(()),
+                };
+            }
+            if flags & 3 != 0 {
+                // The element kind, or with expressions their type.
+                let offset = self.at(iter);
+                let ty = read_u8(&mut iter)?;
+                if flags & 4 != 0 && ty != 0x70 {
+                    self.value_type_at(ty, offset)?;
+                }
+            }
+            let elems = read_var_u32(&mut iter)?;
+            for _ in 0..elems {
+                if flags & 4 == 0 {
+                    read_var_u32(&mut iter)?;
+                    continue
+                }
+                iter = match self.ops(iter)? {
+                    Some(rest) => rest,
+                    None => return Ok(()),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the data segments. Passive segments, and those with a memory
+    /// index, need the bulk memory proposal.
+    fn data(&mut self, mut iter: &[u8]) -> Result<(), Error> {
+        let count = read_var_u32(&mut iter)?;
+        for _ in 0..count {
+            let offset = self.at(iter);
+            let flags = read_var_u32(&mut iter)?;
+            if flags > 2 {
+                return Err(Error::UnknownVariant("data segment flags"))
+            }
+            if flags != 0 {
+                self.report.record("bulk-memory", offset);
+            }
+            if flags == 2 {
+                read_var_u32(&mut iter)?;
+            }
+            if flags != 1 {
+                iter = match self.ops(iter)? {
+                    Some(rest) => rest,
+                    None => return Ok(()),
+                };
+            }
+            let len = read_var_u32(&mut iter)?;
+            read_bytes(&mut iter, len as usize)?;
+        }
+        Ok(())
+    }
+
+    fn body(&mut self, body: &FunctionBody) -> Result<(), Error> {
+        let mut iter = body.body;
+        for _ in 0..body.local_count {
+            read_var_u32(&mut iter)?;
+            self.value_type(&mut iter)?;
+        }
+        self.ops(iter)?;
+        Ok(())
+    }
+
+    /// Checks the element type of a table and its limits, and counts it:
+    /// more than one table needs the reference types proposal.
+    fn table(&mut self, iter: &mut &[u8]) -> Result<(), Error> {
+        let offset = self.at(iter);
+        let element_type = read_u8(iter)?;
+        // Not a value type in 1.0, but the only element type.
+        if element_type != 0x70 {
+            self.value_type_at(element_type, offset)?;
+        }
+        self.tables += 1;
+        if self.tables > 1 {
+            self.report.record("reference-types", offset);
+        }
+        self.limits(iter)
+    }
+
+    /// Checks the limits of a memory, and counts it: more than one memory
+    /// needs the multi-memory proposal.
+    fn memory(&mut self, iter: &mut &[u8]) -> Result<(), Error> {
+        let offset = self.at(iter);
+        self.memories += 1;
+        if self.memories > 1 {
+            self.report.record("multi-memory", offset);
+        }
+        self.limits(iter)
+    }
+
+    /// Checks the limits of a table or memory: shared memories need the
+    /// threads proposal, and 64-bit ones, which set the third flag, the
+    /// memory64 proposal.
+    fn limits(&mut self, iter: &mut &[u8]) -> Result<(), Error> {
+        let offset = self.at(iter);
+        let flags = read_var_u32(&mut { *iter })?;
+        if flags & 0x4 == 0 {
+            if ResizableLimits::parse(iter, &WasmFeatures::all())?.shared {
+                self.report.record("threads", offset);
+            }
+            return Ok(())
+        }
+        if flags > 7 {
+            return Err(Error::UnknownVariant("limits flags"))
+        }
+        self.report.record("memory64", offset);
+        if flags & 0x2 != 0 {
+            self.report.record("threads", offset);
+        }
+        read_var_u32(iter)?;
+        // The initial size, and the maximum if the first flag is set.
+        for _ in 0..1 + (flags & 0x1) {
+            read_varuint(iter)?;
+        }
+        Ok(())
+    }
+
+    /// Skips a tag: its attribute and type index.
+    fn tag(&mut self, iter: &mut &[u8]) -> Result<(), Error> {
+        read_u8(iter)?;
+        read_var_u32(iter)?;
+        Ok(())
+    }
+
+    fn value_type(&mut self, iter: &mut &[u8]) -> Result<(), Error> {
+        let offset = self.at(iter);
+        let code = read_u8(iter)?;
+        self.value_type_at(code, offset)
+    }
+
+    fn value_type_at(&mut self, code: u8, offset: usize) -> Result<(), Error> {
+        if ValueType::from_int(code).is_some() {
+            return Ok(())
+        }
+        self.report.record_error(WasmFeatures::wasm1().value_type_error(code, "value type"), offset)
+    }
+
+    /// Checks the operators of an expression or function body, up to the
+    /// `end` that closes it, and returns what follows. Returns `None` if it
+    /// comes across an operator it cannot skip.
+    fn ops<'b>(&mut self, mut iter: &'b [u8]) -> Result<Option<&'b [u8]>, Error> {
+        // The `try` blocks skipped over. The reader takes the `end` of each
+        // for that of an outer block, and so stops early once per block.
+        let mut tries = 0usize;
+        loop {
+            let base = self.at(iter);
+            let mut ops = OpIterator::with_config(iter, &self.config);
+            loop {
+                let start = ops.iter;
+                let err = match ops.next() {
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => err,
+                    None => break,
+                };
+                self.report.record_error(err, base)?;
+                if !skip_immediates(start[0], &mut ops.iter)? {
+                    return Ok(None)
+                }
+                match start[0] {
+                    0x06 => tries += 1,
+                    // `delegate` closes a `try` without an `end`.
+                    0x18 => tries = tries.saturating_sub(1),
+                    _ => (),
+                }
+            }
+            if tries == 0 {
+                return Ok(Some(ops.iter))
+            }
+            tries -= 1;
+            iter = ops.iter;
+        }
+    }
+}
+
+/// Skips the rest of an operator `OpIterator` reported as disabled, given
+/// its first byte. Returns `false` if the operator cannot be skipped.
+fn skip_immediates(code: u8, iter: &mut &[u8]) -> Result<bool, Error> {
+    match code {
+        // Block types are read before they are checked.
+        0x02..=0x04 => (),
+        // Sign extension, `ref.is_null` and `catch_all`.
+        0xc0..=0xc4 | 0xd1 | 0x19 => (),
+        // `try`.
+        0x06 => {
+            read_var_s33(iter)?;
+        },
+        // `ref.null`.
+        0xd0 => {
+            read_u8(iter)?;
+        },
+        // `ref.func`, `table.get`, `table.set`, `catch`, `throw`,
+        // `rethrow` and `delegate`.
+        0xd2 | 0x25 | 0x26 | 0x07..=0x09 | 0x18 => {
+            read_var_u32(iter)?;
+        },
+        // Typed `select`.
+        0x1c => {
+            let count = read_var_u32(iter)?;
+            for _ in 0..count {
+                read_u8(iter)?;
+            }
+        },
+        0xfc => {
+            let (indices, bytes) = match read_var_u32(iter)? {
+                // Saturating conversions.
+                0..=7 => (0, 0),
+                // `memory.init`.
+                8 => (1, 1),
+                // `data.drop`, `elem.drop` and the table operators.
+                9 | 13 | 15..=17 => (1, 0),
+                // `memory.copy`.
+                10 => (0, 2),
+                // `memory.fill`.
+                11 => (0, 1),
+                // `table.init` and `table.copy`.
+                12 | 14 => (2, 0),
+                _ => return Ok(false),
+            };
+            for _ in 0..indices {
+                read_var_u32(iter)?;
+            }
+            for _ in 0..bytes {
+                read_u8(iter)?;
+            }
+        },
+        // SIMD.
+        0xfd => {
+            let (memory, bytes) = match read_var_u32(iter)? {
+                // Loads and stores, and those of zero-extended scalars.
+                0x00..=0x0b | 0x5c | 0x5d => (true, 0),
+                // `v128.const` and `i8x16.shuffle`.
+                0x0c | 0x0d => (false, 16),
+                // Lane extractions and replacements.
+                0x15..=0x22 => (false, 1),
+                // Loads and stores of a single lane.
+                0x54..=0x5b => (true, 1),
+                // The rest have no immediates.
+                0x0e..=0x14 | 0x23..=0x53 | 0x5e..=0x113 => (false, 0),
+                _ => return Ok(false),
+            };
+            if memory {
+                read_var_u32(iter)?;
+                read_var_u32(iter)?;
+            }
+            for _ in 0..bytes {
+                read_u8(iter)?;
+            }
+        },
+        // Threads.
+        0xfe => match read_var_u32(iter)? {
+            // `atomic.fence`.
+            0x03 => {
+                read_u8(iter)?;
+            },
+            // The other operators all access memory.
+            0x00..=0x02 | 0x10..=0x4e => {
+                read_var_u32(iter)?;
+                read_var_u32(iter)?;
+            },
+            _ => return Ok(false),
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
@@ -1,6 +1,24 @@
-extern crate leb128;
-extern crate byteorder;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate core;
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate quick_error;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 
+#[macro_use]
 pub mod reader;
+pub mod ast;
+pub mod writer;
+pub mod validate;
+pub mod detect;
+pub mod wat;
+#[cfg(feature = "std")]
+pub mod builder;
@@ -1,26 +1,37 @@
 use super::*;
-use std::io::Read;
 use super::bytecode::{Op, OpIterator};
+use alloc::vec::Vec;
 
 pub struct CodeSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct CodeIterator<'a> {
     count: u32,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    config: ParserConfig,
 }
 
 pub struct FunctionBody<'a> {
+    /// The number of `Local` runs, not of locals. See `locals`.
     pub local_count: usize,
     pub body: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct FunctionIterator<'a> {
     local_count: usize,
+    /// Sum of the `Local::count` runs seen so far.
+    locals: usize,
     opiter: Option<OpIterator<'a>>,
     iter: &'a [u8],
+    /// The length of the body, and the offset of the operators once the
+    /// locals are read, for the offsets in errors.
+    len: usize,
+    ops: usize,
+    config: ParserConfig,
 }
 
 pub enum FunctionPart<'a> {
@@ -28,18 +39,45 @@ pub enum FunctionPart<'a> {
     Op(Op<'a>),
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Local {
     pub count: u32,
     pub ty: ValueType,
 }
 
+/// The types of a function's parameters and locals, in the index space
+/// used by `GetLocal`, `SetLocal` and `TeeLocal`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locals {
+    /// Runs of locals with the same type, as the index one past the end of
+    /// the run, in increasing order.
+    runs: Vec<(u32, ValueType)>,
+    params: u32,
+}
+
 impl<'a> CodeSection<'a> {
     pub fn entries(&self) -> CodeIterator<'a> {
         CodeIterator {
             count: self.count,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            config: self.config,
         }
     }
+
+    /// Splits the section into its function bodies. This only reads the
+    /// size prefixes, so it is cheap, and the bodies can then be decoded
+    /// independently and in any order.
+    pub fn bodies(&self) -> Result<Vec<FunctionBody<'a>>, Error> {
+        // Every body takes at least a byte, so a count larger than the
+        // section cannot be right and should not decide the allocation.
+        let capacity = (self.count as usize).min(self.entries_raw.len());
+        let mut bodies = Vec::with_capacity(capacity);
+        for body in self.entries() {
+            bodies.push(body?);
+        }
+        Ok(bodies)
+    }
 }
 
 impl<'a> Iterator for CodeIterator<'a> {
@@ -50,16 +88,21 @@ impl<'a> Iterator for CodeIterator<'a> {
             return None
         }
         self.count -= 1;
-        let body_size = try_opt!(read_varuint(&mut self.iter)) as usize;
+        let body_size = try_opt!(read_var_u32(&mut self.iter)) as usize;
+        try_opt!(ParserConfig::check("function body size", body_size, self.config.max_body_size));
+        if body_size > self.iter.len() {
+            return Some(Err(Error::UnexpectedEof))
+        }
         let mut body = {
             let res = &self.iter[..body_size];
             self.iter = &self.iter[body_size..];
             res
         };
-        let local_count = try_opt!(read_varuint(&mut body)) as usize;
+        let local_count = try_opt!(read_var_u32(&mut body)) as usize;
         Some(Ok(FunctionBody {
-            local_count: local_count,
-            body: body,
+            local_count,
+            body,
+            config: self.config,
         }))
     }
 }
@@ -68,30 +111,127 @@ impl<'a> FunctionBody<'a> {
     pub fn contents(&self) -> FunctionIterator<'a> {
         FunctionIterator {
             local_count: self.local_count,
+            locals: 0,
             opiter: None,
-            iter: self.body
+            iter: self.body,
+            len: self.body.len(),
+            ops: 0,
+            config: self.config,
         }
     }
+
+    /// Decodes the local declarations, passing each run to `f`, and
+    /// returns the encoded operators that follow them.
+    fn read_locals<F: FnMut(Local)>(&self, mut f: F) -> Result<&'a [u8], Error> {
+        let mut contents = self.contents();
+        for _ in 0..self.local_count {
+            match contents.next() {
+                Some(Ok(FunctionPart::Local(local))) => f(local),
+                Some(Err(e)) => return Err(e),
+                _ => unreachable!(),
+            }
+        }
+        Ok(contents.iter)
+    }
+
+    /// Returns the encoded operators, skipping the local declarations.
+    pub fn skip_locals(&self) -> Result<&'a [u8], Error> {
+        self.read_locals(|_| ())
+    }
+
+    /// Returns an iterator over the operators, skipping the local
+    /// declarations.
+    pub fn operators(&self) -> Result<OpIterator<'a>, Error> {
+        let ops = self.skip_locals()?;
+        Ok(OpIterator::with_config(ops, &self.config))
+    }
+
+    /// Combines the parameters of `ty`, which has to be the function's
+    /// type, with the local declarations. Returns the table together with
+    /// an iterator over the operators that follow the declarations.
+    pub fn locals(&self, ty: &FunctionType) -> Result<(Locals, OpIterator<'a>), Error> {
+        let mut locals = Locals {
+            runs: vec![],
+            params: 0,
+        };
+        for param in ty.params() {
+            locals.push(1, param?)?;
+        }
+        locals.params = locals.len();
+        let mut result = Ok(());
+        let ops = self.read_locals(|local| {
+            if result.is_ok() {
+                result = locals.push(local.count, local.ty);
+            }
+        })?;
+        result?;
+        Ok((locals, OpIterator::with_config(ops, &self.config)))
+    }
+}
+
+impl Locals {
+    fn push(&mut self, count: u32, ty: ValueType) -> Result<(), Error> {
+        if count == 0 {
+            return Ok(())
+        }
+        let end = match self.len().checked_add(count) {
+            Some(end) => end,
+            None => return Err(Error::Malformed("number of locals does not fit in 32 bits")),
+        };
+        match self.runs.last_mut() {
+            Some(run) if run.1 == ty => run.0 = end,
+            _ => self.runs.push((end, ty)),
+        }
+        Ok(())
+    }
+
+    /// The type of the parameter or local with the given index, found by
+    /// binary search over the declared runs.
+    pub fn get(&self, index: u32) -> Option<ValueType> {
+        let run = self.runs.partition_point(|&(end, _)| end <= index);
+        self.runs.get(run).map(|&(_, ty)| ty)
+    }
+
+    /// The number of parameters and locals.
+    pub fn len(&self) -> u32 {
+        self.runs.last().map_or(0, |&(end, _)| end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// The number of parameters, which come before the declared locals.
+    pub fn param_count(&self) -> u32 {
+        self.params
+    }
 }
 
 impl<'a> Iterator for FunctionIterator<'a> {
     type Item = Result<FunctionPart<'a>, Error>;
 
+    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         if self.local_count == 0 && self.opiter.is_none() {
-            self.opiter = Some(OpIterator::new(self.iter))
+            self.ops = self.len - self.iter.len();
+            self.opiter = Some(OpIterator::with_config(self.iter, &self.config))
         }
         if let Some(ref mut iter) = self.opiter {
-            return iter.next().map(|x| x.map(FunctionPart::Op))
+            let ops = self.ops;
+            return iter.next().map(|x| x.map(FunctionPart::Op).map_err(|err| err.offset_by(ops)))
         }
         self.local_count -= 1;
-        let count = try_opt!(read_varuint(&mut self.iter)) as u32;
-        let mut ty = [0; 1];
-        try_opt!((&mut self.iter).read_exact(&mut ty));
-        let ty = try_opt!(ValueType::from_int(ty[0]).ok_or(Error::UnknownVariant("value type")));
+        let start = self.len - self.iter.len();
+        let count = try_opt!(read_var_u32(&mut self.iter));
+        self.locals = match self.locals.checked_add(count as usize) {
+            Some(locals) => locals,
+            None => return Some(Err(Error::LimitExceeded("number of locals", self.config.max_locals))),
+        };
+        try_opt!(ParserConfig::check("number of locals", self.locals, self.config.max_locals));
+        let ty = try_opt!(read_value_type(&mut self.iter, &self.config.features).map_err(|err| err.offset_by(start)));
         Some(Ok(FunctionPart::Local(Local {
-            count: count,
-            ty: ty,
+            count,
+            ty,
         })))
     }
 }
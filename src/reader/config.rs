@@ -0,0 +1,60 @@
+use super::*;
+
+/// Resource limits applied while reading a module, and the proposals it
+/// may use.
+///
+/// The default limits are generous enough for any module produced by a
+/// real toolchain, but keep a hostile module from making the reader (or
+/// whatever consumes it) do an unbounded amount of work. Exceeding any of
+/// them produces `Error::LimitExceeded`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParserConfig {
+    /// Number of sections in the module, including custom sections.
+    pub max_sections: usize,
+    /// Number of entries in the type section.
+    pub max_types: usize,
+    /// Number of entries in the import section.
+    pub max_imports: usize,
+    /// Number of entries in the function and code sections.
+    pub max_functions: usize,
+    /// Sum of all `Local::count` runs declared by a single function body.
+    pub max_locals: usize,
+    /// How deeply `block`, `loop` and `if` may be nested in a single
+    /// expression. The implicit block around a function body counts.
+    pub max_nesting: usize,
+    /// Size in bytes of a single function body.
+    pub max_body_size: usize,
+    /// Length in bytes of any name: section names, import and export
+    /// names, and entries of the name section.
+    pub max_name_len: usize,
+    /// The proposals the module may use, checked by the reader and by
+    /// validation.
+    pub features: WasmFeatures,
+}
+
+impl Default for ParserConfig {
+    fn default() -> ParserConfig {
+        ParserConfig {
+            max_sections: 10_000,
+            max_types: 1_000_000,
+            max_imports: 100_000,
+            max_functions: 1_000_000,
+            max_locals: 50_000,
+            max_nesting: 10_000,
+            max_body_size: 7_654_321,
+            max_name_len: 100_000,
+            features: WasmFeatures::default(),
+        }
+    }
+}
+
+impl ParserConfig {
+    /// Returns `Error::LimitExceeded` if `value` is larger than `limit`.
+    pub fn check(what: &'static str, value: usize, limit: usize) -> Result<(), Error> {
+        if value > limit {
+            Err(Error::LimitExceeded(what, limit))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Structured views of the control flow in a function body.
+//!
+//! `ScopedOps` annotates each operator with the frame it is in, and
+//! `ControlTree` nests the operators inside the blocks, loops and ifs
+//! that contain them, with branch depths resolved to the block they
+//! target.
+
+use super::*;
+use super::bytecode::Op;
+use super::codes::FunctionIterator;
+use alloc::vec::Vec;
+use core::mem;
+
+/// The kind of a control frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// The function body itself.
+    Function,
+    Block,
+    Loop,
+    /// The then arm of an if.
+    If,
+    /// The else arm of an if.
+    Else,
+}
+
+/// An operator together with the frame that contains it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopedOp<'a> {
+    /// The number of blocks, loops and ifs around the operator. Operators
+    /// directly in the function body are at depth 0.
+    pub depth: usize,
+    /// The innermost frame around the operator.
+    pub frame: FrameKind,
+    pub op: Op<'a>,
+}
+
+/// Adapts an iterator over operators to yield `ScopedOp`s.
+///
+/// `Block`, `Loop` and `If` belong to the frame they appear in. `Else` and
+/// `End` belong to the frame they close, so the `End` of a block at depth
+/// 1 is reported at depth 1.
+pub struct ScopedOps<I> {
+    iter: I,
+    frames: Vec<FrameKind>,
+}
+
+impl<'a, I: Iterator<Item = Result<Op<'a>, Error>>> ScopedOps<I> {
+    pub fn new(iter: I) -> ScopedOps<I> {
+        ScopedOps {
+            iter,
+            frames: vec![FrameKind::Function],
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Result<Op<'a>, Error>>> Iterator for ScopedOps<I> {
+    type Item = Result<ScopedOp<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let op = match self.iter.next() {
+            Some(op) => try_opt!(op),
+            None => return None,
+        };
+        let depth = self.frames.len() - 1;
+        let frame = match self.frames.last() {
+            Some(&frame) => frame,
+            None => return Some(Err(Error::Malformed("operator after the end of the function"))),
+        };
+        match op {
+            Op::Block(_) => self.frames.push(FrameKind::Block),
+            Op::Loop(_) => self.frames.push(FrameKind::Loop),
+            Op::If(_) => self.frames.push(FrameKind::If),
+            Op::Else => {
+                if frame != FrameKind::If {
+                    return Some(Err(Error::Malformed("else without a matching if")))
+                }
+                *self.frames.last_mut().unwrap() = FrameKind::Else;
+            },
+            Op::End => {
+                self.frames.pop();
+            },
+            _ => (),
+        }
+        Some(Ok(ScopedOp {
+            depth,
+            frame,
+            op,
+        }))
+    }
+}
+
+/// A node in a `ControlTree`.
+///
+/// Blocks are numbered in the order they start, with the function body
+/// itself as block 0, and `ControlTree::frames` gives the kind of each.
+/// `Else` and `End` are implied by the structure and do not appear.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node<'a> {
+    Op(Op<'a>),
+    Block {
+        id: usize,
+        ty: InlineSignatureType,
+        body: Vec<Node<'a>>,
+    },
+    Loop {
+        id: usize,
+        ty: InlineSignatureType,
+        body: Vec<Node<'a>>,
+    },
+    If {
+        id: usize,
+        ty: InlineSignatureType,
+        then: Vec<Node<'a>>,
+        else_: Option<Vec<Node<'a>>>,
+    },
+    /// A `Br`, `BrIf` or `BrTable`, with the ids of the blocks it can
+    /// branch to. For `BrTable` the default target comes last.
+    Branch {
+        op: Op<'a>,
+        targets: Vec<usize>,
+    },
+}
+
+/// A function body as a tree of nested blocks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlTree<'a> {
+    pub body: Vec<Node<'a>>,
+    /// The kind of each block, indexed by id. An if that has an else arm
+    /// is still recorded as `FrameKind::If`.
+    pub frames: Vec<FrameKind>,
+}
+
+struct Open<'a> {
+    id: usize,
+    kind: FrameKind,
+    ty: InlineSignatureType,
+    body: Vec<Node<'a>>,
+    /// The then arm, once the else arm has started.
+    then: Option<Vec<Node<'a>>>,
+}
+
+impl<'a> ControlTree<'a> {
+    /// Builds the tree from the operators of a function body, up to and
+    /// including its final `End`.
+    pub fn build<I>(ops: I) -> Result<ControlTree<'a>, Error>
+        where I: IntoIterator<Item = Result<Op<'a>, Error>>
+    {
+        let mut frames = vec![FrameKind::Function];
+        let mut stack = vec![Open {
+            id: 0,
+            kind: FrameKind::Function,
+            ty: InlineSignatureType::Empty,
+            body: vec![],
+            then: None,
+        }];
+        for op in ops {
+            let op = op?;
+            let kind = match op {
+                Op::Block(ty) => Some((FrameKind::Block, ty)),
+                Op::Loop(ty) => Some((FrameKind::Loop, ty)),
+                Op::If(ty) => Some((FrameKind::If, ty)),
+                _ => None,
+            };
+            if let Some((kind, ty)) = kind {
+                stack.push(Open {
+                    id: frames.len(),
+                    kind,
+                    ty,
+                    body: vec![],
+                    then: None,
+                });
+                frames.push(kind);
+                continue
+            }
+            let node = match op {
+                Op::Else => {
+                    let top = stack.last_mut().unwrap();
+                    if top.kind != FrameKind::If {
+                        return Err(Error::Malformed("else without a matching if"))
+                    }
+                    top.kind = FrameKind::Else;
+                    top.then = Some(mem::take(&mut top.body));
+                    continue
+                },
+                Op::End => {
+                    let open = stack.pop().unwrap();
+                    match open.kind {
+                        FrameKind::Function => return Ok(ControlTree {
+                            body: open.body,
+                            frames,
+                        }),
+                        FrameKind::Block => Node::Block {
+                            id: open.id,
+                            ty: open.ty,
+                            body: open.body,
+                        },
+                        FrameKind::Loop => Node::Loop {
+                            id: open.id,
+                            ty: open.ty,
+                            body: open.body,
+                        },
+                        FrameKind::If => Node::If {
+                            id: open.id,
+                            ty: open.ty,
+                            then: open.body,
+                            else_: None,
+                        },
+                        FrameKind::Else => Node::If {
+                            id: open.id,
+                            ty: open.ty,
+                            then: open.then.unwrap_or_default(),
+                            else_: Some(open.body),
+                        },
+                    }
+                },
+                Op::Br(depth) | Op::BrIf(depth) => {
+                    let target = resolve(&stack, depth)?;
+                    Node::Branch {
+                        op,
+                        targets: vec![target],
+                    }
+                },
+                Op::BrTable(ref table) => {
+                    let mut targets = vec![];
+                    for arm in table.arms() {
+                        targets.push(resolve(&stack, arm?)?);
+                    }
+                    targets.push(resolve(&stack, table.default)?);
+                    Node::Branch {
+                        op: op.clone(),
+                        targets,
+                    }
+                },
+                op => Node::Op(op),
+            };
+            stack.last_mut().unwrap().body.push(node);
+        }
+        Err(Error::UnexpectedEof)
+    }
+}
+
+/// Returns the id of the block `depth` levels out from the innermost one.
+fn resolve(stack: &[Open], depth: u32) -> Result<usize, Error> {
+    if depth as usize >= stack.len() {
+        return Err(Error::Malformed("branch depth exceeds the control stack"))
+    }
+    Ok(stack[stack.len() - 1 - depth as usize].id)
+}
+
+impl<'a> FunctionBody<'a> {
+    /// Returns the operators of the body, each with the frame around it.
+    pub fn scoped_ops(&self) -> ScopedOps<BodyOps<'a>> {
+        ScopedOps::new(BodyOps(self.contents()))
+    }
+
+    /// Builds the control tree of the body.
+    pub fn control_tree(&self) -> Result<ControlTree<'a>, Error> {
+        ControlTree::build(BodyOps(self.contents()))
+    }
+}
+
+/// The operators of a function body, without its local declarations.
+pub struct BodyOps<'a>(FunctionIterator<'a>);
+
+impl<'a> Iterator for BodyOps<'a> {
+    type Item = Result<Op<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.next() {
+                Some(Ok(FunctionPart::Local(_))) => continue,
+                Some(Ok(FunctionPart::Op(op))) => return Some(Ok(op)),
+                Some(Err(e)) => return Some(Err(e)),
+                None => return None,
+            }
+        }
+    }
+}
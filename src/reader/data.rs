@@ -4,12 +4,18 @@ use super::bytecode::{Op, OpIterator};
 pub struct DataSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct DataEntryIterator<'a> {
     count: u32,
     opiter: Option<OpIterator<'a>>,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    /// The length of all entries, and the offset of the offset expression
+    /// being read, for the offsets in errors.
+    len: usize,
+    expr: usize,
+    config: ParserConfig,
 }
 
 pub enum DataEntry<'a> {
@@ -23,7 +29,10 @@ impl<'a> DataSection<'a> {
         DataEntryIterator {
             count: self.count,
             opiter: None,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            len: self.entries_raw.len(),
+            expr: 0,
+            config: self.config,
         }
     }
 }
@@ -36,9 +45,13 @@ impl<'a> Iterator for DataEntryIterator<'a> {
             if let Some(op) = iter.next() {
                 self.iter = iter.iter;
                 self.opiter = Some(iter);
-                return Some(op.map(DataEntry::Op))
+                let expr = self.expr;
+                return Some(op.map(DataEntry::Op).map_err(|err| err.offset_by(expr)))
+            }
+            let size = try_opt!(read_var_u32(&mut self.iter)) as usize;
+            if size > self.iter.len() {
+                return Some(Err(Error::UnexpectedEof))
             }
-            let size = try_opt!(read_varuint(&mut self.iter)) as usize;
             let res = &self.iter[..size];
             self.iter = &self.iter[size..];
             return Some(Ok(DataEntry::Data(res)))
@@ -47,8 +60,9 @@ impl<'a> Iterator for DataEntryIterator<'a> {
             return None
         }
         self.count -= 1;
-        let index = try_opt!(read_varuint(&mut self.iter)) as u32;
-        self.opiter = Some(OpIterator::new(self.iter));
+        let index = try_opt!(read_var_u32(&mut self.iter));
+        self.expr = self.len - self.iter.len();
+        self.opiter = Some(OpIterator::with_config(self.iter, &self.config));
         Some(Ok(DataEntry::Index(index)))
     }
 }
@@ -4,13 +4,19 @@ use super::bytecode::{Op, OpIterator};
 pub struct ElementSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct ElementEntryIterator<'a> {
     count: u32,
     opiter: Option<OpIterator<'a>>,
     elems: usize,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    /// The length of all entries, and the offset of the offset expression
+    /// being read, for the offsets in errors.
+    len: usize,
+    expr: usize,
+    config: ParserConfig,
 }
 
 pub enum ElementEntry<'a> {
@@ -25,7 +31,10 @@ impl<'a> ElementSection<'a> {
             count: self.count,
             opiter: None,
             elems: 0,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            len: self.entries_raw.len(),
+            expr: 0,
+            config: self.config,
         }
     }
 }
@@ -38,22 +47,24 @@ impl<'a> Iterator for ElementEntryIterator<'a> {
             if let Some(op) = iter.next() {
                 self.iter = iter.iter;
                 self.opiter = Some(iter);
-                return Some(op.map(ElementEntry::Op))
+                let expr = self.expr;
+                return Some(op.map(ElementEntry::Op).map_err(|err| err.offset_by(expr)))
             }
-            self.elems = try_opt!(read_varuint(&mut self.iter)) as usize;
+            self.elems = try_opt!(read_var_u32(&mut self.iter)) as usize;
         }
         if self.elems > 0 {
             self.elems -= 1;
             return Some(Ok(ElementEntry::Elem(try_opt!(
-                read_varuint(&mut self.iter)
-            ) as u32)));
+                read_var_u32(&mut self.iter)
+            ))));
         }
         if self.count == 0 {
             return None
         }
         self.count -= 1;
-        let index = try_opt!(read_varuint(&mut self.iter)) as u32;
-        self.opiter = Some(OpIterator::new(self.iter));
+        let index = try_opt!(read_var_u32(&mut self.iter));
+        self.expr = self.len - self.iter.len();
+        self.opiter = Some(OpIterator::with_config(self.iter, &self.config));
         Some(Ok(ElementEntry::Index(index)))
     }
 }
@@ -1,4 +1,5 @@
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum SectionType {
     Named = 0,
@@ -12,7 +13,13 @@ pub enum SectionType {
     Start = 8,
     Element = 9,
     Code = 10,
-    Data = 11
+    Data = 11,
+    /// The number of data segments, from the bulk memory proposal. Comes
+    /// between the element and code sections.
+    DataCount = 12,
+    /// Exception tags, from the exception handling proposal. Comes between
+    /// the memory and global sections.
+    Tag = 13,
 }
 
 impl SectionType {
@@ -30,12 +37,15 @@ impl SectionType {
             9 => SectionType::Element,
             10 => SectionType::Code,
             11 => SectionType::Data,
+            12 => SectionType::DataCount,
+            13 => SectionType::Tag,
             _ => return None,
         })
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum ValueType {
     I32 = 0x7f,
@@ -57,6 +67,7 @@ impl ValueType {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum ExternalKind {
     Function = 0,
@@ -78,6 +89,7 @@ impl ExternalKind {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum InlineSignatureType {
     I32 = 0x7f,
@@ -101,6 +113,7 @@ impl InlineSignatureType {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum LanguageType {
     I32 = 0x7f,
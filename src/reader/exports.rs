@@ -1,15 +1,15 @@
 use super::*;
-use std::io::Read;
-use std::str::from_utf8;
 
 pub struct ExportSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct ExportEntryIterator<'a> {
     count: u32,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    config: ParserConfig,
 }
 
 pub struct ExportEntry<'a> {
@@ -22,7 +22,8 @@ impl<'a> ExportSection<'a> {
     pub fn entries(&self) -> ExportEntryIterator<'a> {
         ExportEntryIterator {
             count: self.count,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            config: self.config,
         }
     }
 }
@@ -35,20 +36,14 @@ impl<'a> Iterator for ExportEntryIterator<'a> {
             return None
         }
         self.count -= 1;
-        let flen = try_opt!(read_varuint(&mut self.iter)) as usize;
-        let field = {
-            let res = &self.iter[..flen];
-            self.iter = &self.iter[flen..];
-            try_opt!(from_utf8(res))
-        };
-        let mut kind = [0; 1];
-        try_opt!((&mut self.iter).read_exact(&mut kind));
-        let kind = try_opt!(ExternalKind::from_int(kind[0]).ok_or(Error::UnknownVariant("external kind")));
-        let index = try_opt!(read_varuint(&mut self.iter)) as u32;
+        let field = try_opt!(read_name(&mut self.iter, &self.config));
+        let kind = try_opt!(read_u8(&mut self.iter));
+        let kind = try_opt!(ExternalKind::from_int(kind).ok_or(Error::UnknownVariant("external kind")));
+        let index = try_opt!(read_var_u32(&mut self.iter));
         Some(Ok(ExportEntry {
-            field: field,
-            kind: kind,
-            index: index,
+            field,
+            kind,
+            index,
         }))
     }
 }
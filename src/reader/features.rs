@@ -0,0 +1,192 @@
+use super::*;
+
+/// The WebAssembly proposals a module is allowed to use.
+///
+/// The reader recognizes the encodings each proposal adds and rejects them
+/// with `Error::Disabled`, naming the proposal, unless it is enabled here.
+/// Enabling a proposal does not make the reader decode it: its sections
+/// are read, but operators and types it introduces that this crate does
+/// not implement are still `Error::UnknownVariant`. The reader implements
+/// nothing of multi-value and SIMD, so their flags only decide which of the
+/// two errors it returns.
+///
+/// The default is the 1.0 release of the spec.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WasmFeatures {
+    /// Importing and exporting mutable globals.
+    pub mutable_global: bool,
+    /// The `i32.extend8_s` family of operators.
+    pub sign_extension: bool,
+    /// The non-trapping `trunc_sat` conversions.
+    pub saturating_float_to_int: bool,
+    /// Block types that refer to the type section, and functions with more
+    /// than one result. Only names the proposal in errors.
+    pub multi_value: bool,
+    /// The data count section and the `memory.copy` family of operators.
+    pub bulk_memory: bool,
+    /// Reference types, typed `select` and the table operators.
+    pub reference_types: bool,
+    /// The `v128` type and its operators. Only names the proposal in
+    /// errors.
+    pub simd: bool,
+    /// Shared memories and atomic operators.
+    pub threads: bool,
+    /// The tag section and the `try`, `catch` and `throw` operators.
+    pub exceptions: bool,
+}
+
+impl Default for WasmFeatures {
+    fn default() -> WasmFeatures {
+        WasmFeatures::wasm1()
+    }
+}
+
+impl WasmFeatures {
+    /// The 1.0 release of the spec, without any proposals.
+    pub fn wasm1() -> WasmFeatures {
+        WasmFeatures {
+            mutable_global: false,
+            sign_extension: false,
+            saturating_float_to_int: false,
+            multi_value: false,
+            bulk_memory: false,
+            reference_types: false,
+            simd: false,
+            threads: false,
+            exceptions: false,
+        }
+    }
+
+    /// The proposals of the 2.0 release of the spec that the reader
+    /// implements: all of them but multi-value and SIMD.
+    pub fn wasm2() -> WasmFeatures {
+        WasmFeatures {
+            mutable_global: true,
+            sign_extension: true,
+            saturating_float_to_int: true,
+            bulk_memory: true,
+            reference_types: true,
+            .. WasmFeatures::wasm1()
+        }
+    }
+
+    /// Every proposal known to the reader.
+    pub fn all() -> WasmFeatures {
+        WasmFeatures {
+            multi_value: true,
+            simd: true,
+            threads: true,
+            exceptions: true,
+            .. WasmFeatures::wasm2()
+        }
+    }
+
+    /// Enables or disables a proposal by the name used in
+    /// `Error::Disabled`. Returns `false` if the name is not known.
+    pub fn set(&mut self, feature: &str, enabled: bool) -> bool {
+        let flag = match feature {
+            "mutable-global" => &mut self.mutable_global,
+            "sign-extension" => &mut self.sign_extension,
+            "saturating-float-to-int" => &mut self.saturating_float_to_int,
+            "multi-value" => &mut self.multi_value,
+            "bulk-memory" => &mut self.bulk_memory,
+            "reference-types" => &mut self.reference_types,
+            "simd" => &mut self.simd,
+            "threads" => &mut self.threads,
+            "exceptions" => &mut self.exceptions,
+            _ => return false,
+        };
+        *flag = enabled;
+        true
+    }
+
+    /// Whether every proposal enabled in `other` is enabled here.
+    pub fn contains(&self, other: &WasmFeatures) -> bool {
+        (self.mutable_global || !other.mutable_global) &&
+        (self.sign_extension || !other.sign_extension) &&
+        (self.saturating_float_to_int || !other.saturating_float_to_int) &&
+        (self.multi_value || !other.multi_value) &&
+        (self.bulk_memory || !other.bulk_memory) &&
+        (self.reference_types || !other.reference_types) &&
+        (self.simd || !other.simd) &&
+        (self.threads || !other.threads) &&
+        (self.exceptions || !other.exceptions)
+    }
+
+    /// The oldest release of the spec that includes these proposals,
+    /// `"1.0"` or `"2.0"`. Threads are not part of a release, and the
+    /// exception handling operators the reader knows are those of the
+    /// legacy proposal, so either gives `None`.
+    pub fn release(&self) -> Option<&'static str> {
+        let wasm2 = WasmFeatures {
+            multi_value: true,
+            simd: true,
+            .. WasmFeatures::wasm2()
+        };
+        if WasmFeatures::wasm1().contains(self) {
+            Some("1.0")
+        } else if wasm2.contains(self) {
+            Some("2.0")
+        } else {
+            None
+        }
+    }
+
+    /// Returns `Error::Disabled` naming `feature` unless it is `enabled`.
+    /// The offset is 0, for the caller to fill in.
+    pub fn check(feature: &'static str, enabled: bool) -> Result<(), Error> {
+        if enabled {
+            Ok(())
+        } else {
+            Err(Error::Disabled(feature, 0))
+        }
+    }
+
+    /// Checks that a section does not belong to a disabled proposal.
+    pub fn check_section(&self, id: SectionType) -> Result<(), Error> {
+        match id {
+            SectionType::DataCount => WasmFeatures::check("bulk-memory", self.bulk_memory),
+            SectionType::Tag => WasmFeatures::check("exceptions", self.exceptions),
+            _ => Ok(()),
+        }
+    }
+
+    /// The error for an opcode the reader does not know: `Error::Disabled`
+    /// if a disabled proposal defines it, and `Error::UnknownVariant`
+    /// otherwise. `rest` follows the opcode, and holds the second part of
+    /// prefixed opcodes.
+    pub fn opcode_error(&self, code: u8, rest: &[u8]) -> Error {
+        let feature = match code {
+            0x06..=0x09 | 0x18 | 0x19 => Some(("exceptions", self.exceptions)),
+            0x1c | 0x25 | 0x26 | 0xd0..=0xd2 => Some(("reference-types", self.reference_types)),
+            0xc0..=0xc4 => Some(("sign-extension", self.sign_extension)),
+            0xfc => match read_var_u32(&mut { rest }) {
+                Ok(0..=7) => Some(("saturating-float-to-int", self.saturating_float_to_int)),
+                Ok(8..=14) => Some(("bulk-memory", self.bulk_memory)),
+                Ok(15..=17) => Some(("reference-types", self.reference_types)),
+                _ => None,
+            },
+            0xfd => Some(("simd", self.simd)),
+            0xfe => Some(("threads", self.threads)),
+            _ => None,
+        };
+        match feature {
+            Some((feature, false)) => Error::Disabled(feature, 0),
+            _ => Error::UnknownVariant("opcode"),
+        }
+    }
+
+    /// Like `opcode_error`, for a value type. `what` describes where the
+    /// type appears, for `Error::UnknownVariant`.
+    pub fn value_type_error(&self, code: u8, what: &'static str) -> Error {
+        let feature = match code {
+            0x7b => Some(("simd", self.simd)),
+            0x70 | 0x6f => Some(("reference-types", self.reference_types)),
+            _ => None,
+        };
+        match feature {
+            Some((feature, false)) => Error::Disabled(feature, 0),
+            _ => Error::UnknownVariant(what),
+        }
+    }
+}
@@ -18,6 +18,6 @@ impl<'a> Iterator for FunctionEntryIterator<'a> {
             return None
         }
         self.1 -= 1;
-        Some(read_varuint(&mut self.0).map(|x| x as u32).map_err(|x| x.into()))
+        Some(read_var_u32(&mut self.0))
     }
 }
@@ -1,16 +1,21 @@
 use super::*;
-use std::io::Read;
 use super::bytecode::{Op, OpIterator};
 
 pub struct GlobalSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct GlobalEntryIterator<'a> {
     count: u32,
     opiter: Option<OpIterator<'a>>,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    /// The length of all entries, and the offset of the initializer being
+    /// read, for the offsets in errors.
+    len: usize,
+    expr: usize,
+    config: ParserConfig,
 }
 
 pub struct GlobalEntry {
@@ -28,7 +33,10 @@ impl<'a> GlobalSection<'a> {
         GlobalEntryIterator {
             count: self.count,
             opiter: None,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            len: self.entries_raw.len(),
+            expr: 0,
+            config: self.config,
         }
     }
 }
@@ -41,20 +49,22 @@ impl<'a> Iterator for GlobalEntryIterator<'a> {
             if let Some(op) = iter.next() {
                 self.iter = iter.iter;
                 self.opiter = Some(iter);
-                return Some(op.map(GlobalEntryEither::Op))
+                let expr = self.expr;
+                return Some(op.map(GlobalEntryEither::Op).map_err(|err| err.offset_by(expr)))
             }
         }
         if self.count == 0 {
             return None
         }
         self.count -= 1;
-        let mut ty = [0; 1];
-        try_opt!((&mut self.iter).read_exact(&mut ty));
-        let mutable = try_opt!(read_varuint(&mut self.iter)) != 0;
-        self.opiter = Some(OpIterator::new(self.iter));
+        let start = self.len - self.iter.len();
+        let ty = try_opt!(read_value_type(&mut self.iter, &self.config.features).map_err(|err| err.offset_by(start)));
+        let mutable = try_opt!(read_var_u32(&mut self.iter)) != 0;
+        self.expr = self.len - self.iter.len();
+        self.opiter = Some(OpIterator::with_config(self.iter, &self.config));
         Some(Ok(GlobalEntryEither::Entry(GlobalEntry {
-            ty: try_opt!(ValueType::from_int(ty[0]).ok_or(Error::UnknownVariant("value type"))),
-            mutable: mutable,
+            ty,
+            mutable,
         })))
     }
 }
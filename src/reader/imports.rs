@@ -1,10 +1,10 @@
 use super::*;
-use std::str::from_utf8;
-use std::io::Read;
 
-pub struct ImportSection<'a>(pub &'a [u8], pub usize);
+pub struct ImportSection<'a>(pub &'a [u8], pub usize, pub ParserConfig);
 
-pub struct ImportEntryIterator<'a>(&'a [u8], usize);
+/// The entries left, their number, the configuration, and the length of
+/// all entries, for the offsets in errors.
+pub struct ImportEntryIterator<'a>(&'a [u8], usize, ParserConfig, usize);
 
 pub struct ImportEntry<'a> {
     pub module: &'a str,
@@ -25,14 +25,19 @@ pub enum ImportEntryContents {
     },
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ResizableLimits {
     pub initial: u32,
     pub maximum: Option<u32>,
+    /// Whether the memory is shared between threads.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shared: bool,
 }
 
 impl<'a> ImportSection<'a> {
     pub fn entries(&self) -> ImportEntryIterator<'a> {
-        ImportEntryIterator(self.0, self.1)
+        ImportEntryIterator(self.0, self.1, self.2, self.0.len())
     }
 }
 
@@ -40,69 +45,65 @@ impl<'a> Iterator for ImportEntryIterator<'a> {
     type Item = Result<ImportEntry<'a>, Error>;
 
     fn next(&mut self) -> Option<Result<ImportEntry<'a>, Error>> {
+        let start = self.3 - self.0.len();
+        self.read().map(|entry| entry.map_err(|err| err.offset_by(start)))
+    }
+}
+
+impl<'a> ImportEntryIterator<'a> {
+    fn read(&mut self) -> Option<Result<ImportEntry<'a>, Error>> {
         if self.1 == 0 {
             return None
         }
         self.1 -= 1;
-        let mlen = try_opt!(read_varuint(&mut self.0));
-        let module = {
-            let res = &self.0[..mlen as usize];
-            self.0 = &self.0[mlen as usize..];
-            try_opt!(from_utf8(res))
-        };
-        let flen = try_opt!(read_varuint(&mut self.0));
-        let field = {
-            let res = &self.0[..flen as usize];
-            self.0 = &self.0[flen as usize..];
-            try_opt!(from_utf8(res))
-        };
-        let mut kind = [0; 1];
-        try_opt!((&mut self.0).read_exact(&mut kind));
-        let kind = try_opt!(ExternalKind::from_int(kind[0]).ok_or(Error::UnknownVariant("external kind")));
+        let module = try_opt!(read_name(&mut self.0, &self.2));
+        let field = try_opt!(read_name(&mut self.0, &self.2));
+        let kind = try_opt!(read_u8(&mut self.0));
+        let kind = try_opt!(ExternalKind::from_int(kind).ok_or(Error::UnknownVariant("external kind")));
         let contents = match kind {
             ExternalKind::Function => ImportEntryContents::Function(try_opt!(
-                read_varuint(&mut self.0)) as u32
+                read_var_u32(&mut self.0))
             ),
             ExternalKind::Table => ImportEntryContents::Table {
-                element_type: {
-                    let mut ty = [0; 1];
-                    try_opt!((&mut self.0).read_exact(&mut ty));
-                    ty[0]
-                },
-                limits: try_opt!(ResizableLimits::parse(&mut self.0)),
+                element_type: try_opt!(read_u8(&mut self.0)),
+                limits: try_opt!(ResizableLimits::parse(&mut self.0, &self.2.features)),
             },
             ExternalKind::Memory => ImportEntryContents::Memory(
-                try_opt!(ResizableLimits::parse(&mut self.0))
+                try_opt!(ResizableLimits::parse(&mut self.0, &self.2.features))
             ),
             ExternalKind::Global => ImportEntryContents::Global {
-                ty: {
-                    let mut ty = [0; 1];
-                    try_opt!((&mut self.0).read_exact(&mut ty));
-                    try_opt!(ValueType::from_int(ty[0]).ok_or(Error::UnknownVariant("value type")))
-                },
-                mutable: try_opt!(read_varuint(&mut self.0)) != 0,
+                ty: try_opt!(read_value_type(&mut self.0, &self.2.features)),
+                mutable: try_opt!(read_var_u32(&mut self.0)) != 0,
             },
         };
         Some(Ok(ImportEntry {
-            module: module,
-            field: field,
-            contents: contents,
+            module,
+            field,
+            contents,
         }))
     }
 }
 
 impl ResizableLimits {
-    pub fn parse(mut iter: &mut &[u8]) -> Result<ResizableLimits, Error> {
-        let flags = try!(read_varuint(iter));
-        let initial = try!(read_varuint(iter));
+    /// Reads the limits of a table or memory. Shared memories, which set
+    /// the second flag, need the threads proposal.
+    pub fn parse(iter: &mut &[u8], features: &WasmFeatures) -> Result<ResizableLimits, Error> {
+        let flags = read_var_u32(iter)?;
+        match flags {
+            0 | 1 => (),
+            2 | 3 => WasmFeatures::check("threads", features.threads)?,
+            _ => return Err(Error::UnknownVariant("limits flags")),
+        }
+        let initial = read_var_u32(iter)?;
         let maximum = if flags & 0x1 != 0 {
-            Some(try!(read_varuint(iter)))
+            Some(read_var_u32(iter)?)
         } else {
             None
         };
         Ok(ResizableLimits {
-            initial: initial as u32,
-            maximum: maximum.map(|x| x as u32),
+            initial,
+            maximum,
+            shared: flags & 0x2 != 0,
         })
     }
 }
@@ -0,0 +1,467 @@
+use super::*;
+use super::bytecode::Op;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap as NameMap;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as NameMap;
+
+/// Random access to the contents of a module.
+///
+/// Building the index walks the module once, recording where each section
+/// and function body starts and decoding the small per-item tables (types,
+/// imports, function signatures, globals, exports and names). Lookups are
+/// then constant time, and everything still borrows from the input.
+/// Without the `std` feature there is no hash map, and lookups of exports
+/// and function names are logarithmic instead.
+pub struct ModuleIndex<'a> {
+    pub version: u32,
+    pub sections: Vec<IndexedSection<'a>>,
+    types: Vec<FunctionType<'a>>,
+    imports: Vec<ImportEntry<'a>>,
+    /// Index into `imports` of each imported function.
+    imported_functions: Vec<usize>,
+    imported_tables: Vec<usize>,
+    imported_memories: Vec<usize>,
+    imported_globals: Vec<usize>,
+    /// Type index of each defined function.
+    function_types: Vec<u32>,
+    bodies: Vec<IndexedBody<'a>>,
+    tables: Vec<TableEntry>,
+    memories: Vec<MemoryEntry>,
+    globals: Vec<(GlobalEntry, Vec<Op<'a>>)>,
+    exports: Vec<ExportEntry<'a>>,
+    export_names: NameMap<&'a str, usize>,
+    function_names: NameMap<u32, &'a str>,
+    pub start: Option<u32>,
+}
+
+pub struct IndexedSection<'a> {
+    /// Offset of the section payload from the start of the module.
+    pub offset: usize,
+    pub section: Section<'a>,
+}
+
+pub struct IndexedBody<'a> {
+    /// Offset of the body, after its size, from the start of the module.
+    pub offset: usize,
+    pub body: FunctionBody<'a>,
+}
+
+/// What the index knows about a function.
+pub struct FunctionInfo<'i, 'a: 'i> {
+    pub type_index: u32,
+    pub ty: &'i FunctionType<'a>,
+    /// The body, or `None` for imported functions.
+    pub body: Option<&'i IndexedBody<'a>>,
+    /// The name from the name section, if there is one.
+    pub name: Option<&'a str>,
+}
+
+/// What the index knows about a global.
+pub struct GlobalInfo<'i, 'a: 'i> {
+    pub ty: ValueType,
+    pub mutable: bool,
+    /// The initializer expression, or `None` for imported globals.
+    pub init: Option<&'i [Op<'a>]>,
+}
+
+/// A function, resolved to the import or definition it refers to.
+pub enum FunctionRef<'i, 'a: 'i> {
+    Imported {
+        module: &'a str,
+        field: &'a str,
+        type_index: u32,
+        ty: &'i FunctionType<'a>,
+    },
+    Defined {
+        /// Index among the functions defined by the module, which is also
+        /// the index of its body in the code section.
+        local_index: u32,
+        type_index: u32,
+        ty: &'i FunctionType<'a>,
+        body: &'i IndexedBody<'a>,
+    },
+}
+
+/// A global, resolved to the import or definition it refers to.
+pub enum GlobalRef<'i, 'a: 'i> {
+    Imported {
+        module: &'a str,
+        field: &'a str,
+        ty: ValueType,
+        mutable: bool,
+    },
+    Defined {
+        local_index: u32,
+        ty: ValueType,
+        mutable: bool,
+        /// The initializer expression, including the final `End`.
+        init: &'i [Op<'a>],
+    },
+}
+
+/// A table, resolved to the import or definition it refers to.
+pub enum TableRef<'i, 'a> {
+    Imported {
+        module: &'a str,
+        field: &'a str,
+        element_type: u8,
+        limits: &'i ResizableLimits,
+    },
+    Defined {
+        local_index: u32,
+        element_type: u8,
+        limits: &'i ResizableLimits,
+    },
+}
+
+/// A memory, resolved to the import or definition it refers to.
+pub enum MemoryRef<'i, 'a> {
+    Imported {
+        module: &'a str,
+        field: &'a str,
+        limits: &'i ResizableLimits,
+    },
+    Defined {
+        local_index: u32,
+        limits: &'i ResizableLimits,
+    },
+}
+
+/// The target of an export, resolved according to its kind.
+pub enum ExternRef<'i, 'a: 'i> {
+    Function(FunctionRef<'i, 'a>),
+    Table(TableRef<'i, 'a>),
+    Memory(MemoryRef<'i, 'a>),
+    Global(GlobalRef<'i, 'a>),
+}
+
+impl<'i, 'a> FunctionRef<'i, 'a> {
+    pub fn type_index(&self) -> u32 {
+        match *self {
+            FunctionRef::Imported { type_index, .. } | FunctionRef::Defined { type_index, .. } => type_index,
+        }
+    }
+
+    pub fn ty(&self) -> &'i FunctionType<'a> {
+        match *self {
+            FunctionRef::Imported { ty, .. } | FunctionRef::Defined { ty, .. } => ty,
+        }
+    }
+
+    /// The body, or `None` for imported functions.
+    pub fn body(&self) -> Option<&'i IndexedBody<'a>> {
+        match *self {
+            FunctionRef::Imported { .. } => None,
+            FunctionRef::Defined { body, .. } => Some(body),
+        }
+    }
+}
+
+/// Splits an index into an index space that counts imports first.
+enum Slot {
+    Imported(usize),
+    Defined(usize),
+}
+
+fn slot(imported: &[usize], index: u32) -> Slot {
+    match imported.get(index as usize) {
+        Some(&import) => Slot::Imported(import),
+        None => Slot::Defined(index as usize - imported.len()),
+    }
+}
+
+fn offset_in(base: &[u8], slice: &[u8]) -> usize {
+    slice.as_ptr() as usize - base.as_ptr() as usize + 8
+}
+
+impl<'a> ModuleIndex<'a> {
+    pub fn new(module: &Module<'a>) -> Result<ModuleIndex<'a>, Error> {
+        let base = module.payload;
+        let mut index = ModuleIndex {
+            version: module.version,
+            sections: vec![],
+            types: vec![],
+            imports: vec![],
+            imported_functions: vec![],
+            imported_tables: vec![],
+            imported_memories: vec![],
+            imported_globals: vec![],
+            function_types: vec![],
+            bodies: vec![],
+            tables: vec![],
+            memories: vec![],
+            globals: vec![],
+            exports: vec![],
+            export_names: NameMap::new(),
+            function_names: NameMap::new(),
+            start: None,
+        };
+        for section in SectionsIterator::new(base, module.config) {
+            let section = section?;
+            let content = match section.id {
+                SectionType::Named if section.name != "name" => None,
+                SectionType::Tag => None,
+                _ => Some(section.content()?),
+            };
+            match content {
+                Some(SectionContent::Type(types)) => {
+                    for entry in types.entries() {
+                        let TypeEntry::Function(ty) = entry?;
+                        index.types.push(ty);
+                    }
+                },
+                Some(SectionContent::Import(imports)) => {
+                    for entry in imports.entries() {
+                        let entry = entry?;
+                        match entry.contents {
+                            ImportEntryContents::Function(_) => index.imported_functions.push(index.imports.len()),
+                            ImportEntryContents::Table { .. } => index.imported_tables.push(index.imports.len()),
+                            ImportEntryContents::Memory(_) => index.imported_memories.push(index.imports.len()),
+                            ImportEntryContents::Global { .. } => index.imported_globals.push(index.imports.len()),
+                        }
+                        index.imports.push(entry);
+                    }
+                },
+                Some(SectionContent::Function(functions)) => {
+                    for ty in functions.types() {
+                        index.function_types.push(ty?);
+                    }
+                },
+                Some(SectionContent::Table(tables)) => {
+                    for entry in tables.entries() {
+                        index.tables.push(entry?);
+                    }
+                },
+                Some(SectionContent::Memory(memories)) => {
+                    for entry in memories.entries() {
+                        index.memories.push(entry?);
+                    }
+                },
+                Some(SectionContent::Global(globals)) => {
+                    for entry in globals.entries() {
+                        match entry? {
+                            GlobalEntryEither::Entry(entry) => index.globals.push((entry, vec![])),
+                            GlobalEntryEither::Op(op) => {
+                                if let Some(global) = index.globals.last_mut() {
+                                    global.1.push(op);
+                                }
+                            },
+                        }
+                    }
+                },
+                Some(SectionContent::Export(exports)) => {
+                    for entry in exports.entries() {
+                        let entry = entry?;
+                        index.export_names.insert(entry.field, index.exports.len());
+                        index.exports.push(entry);
+                    }
+                },
+                Some(SectionContent::Start(start)) => index.start = Some(start),
+                Some(SectionContent::Code(code)) => {
+                    for body in code.entries() {
+                        let body = body?;
+                        index.bodies.push(IndexedBody {
+                            offset: offset_in(base, body.body),
+                            body,
+                        });
+                    }
+                },
+                Some(SectionContent::Name(names)) => {
+                    for entry in names.function_names() {
+                        let (function, name) = entry?;
+                        index.function_names.insert(function, name);
+                    }
+                },
+                _ => (),
+            }
+            index.sections.push(IndexedSection {
+                offset: offset_in(base, section.payload),
+                section,
+            });
+        }
+        if index.bodies.len() != index.function_types.len() {
+            return Err(Error::Malformed("function and code section have different lengths"))
+        }
+        Ok(index)
+    }
+
+    /// Returns the first section with the given id.
+    pub fn section(&self, id: SectionType) -> Option<&IndexedSection<'a>> {
+        self.sections.iter().find(|section| section.section.id == id)
+    }
+
+    pub fn func_type(&self, index: u32) -> Option<&FunctionType<'a>> {
+        self.types.get(index as usize)
+    }
+
+    /// The number of functions, imported and defined.
+    pub fn function_count(&self) -> u32 {
+        (self.imported_functions.len() + self.function_types.len()) as u32
+    }
+
+    /// Looks up a function by its index in the function index space, which
+    /// counts imported functions first.
+    pub fn function(&self, index: u32) -> Option<FunctionInfo<'_, 'a>> {
+        let function = self.resolve_function(index)?;
+        Some(FunctionInfo {
+            type_index: function.type_index(),
+            ty: function.ty(),
+            body: function.body(),
+            name: self.function_names.get(&index).cloned(),
+        })
+    }
+
+    /// Resolves an index in the function index space, as used by `Call`,
+    /// exports, elements and the start function.
+    pub fn resolve_function(&self, index: u32) -> Option<FunctionRef<'_, 'a>> {
+        match slot(&self.imported_functions, index) {
+            Slot::Imported(import) => {
+                let import = &self.imports[import];
+                match import.contents {
+                    ImportEntryContents::Function(type_index) => Some(FunctionRef::Imported {
+                        module: import.module,
+                        field: import.field,
+                        type_index,
+                        ty: self.types.get(type_index as usize)?,
+                    }),
+                    _ => unreachable!(),
+                }
+            },
+            Slot::Defined(defined) => {
+                let type_index = *self.function_types.get(defined)?;
+                Some(FunctionRef::Defined {
+                    local_index: defined as u32,
+                    type_index,
+                    ty: self.types.get(type_index as usize)?,
+                    body: &self.bodies[defined],
+                })
+            },
+        }
+    }
+
+    /// The number of tables, imported and defined.
+    pub fn table_count(&self) -> u32 {
+        (self.imported_tables.len() + self.tables.len()) as u32
+    }
+
+    /// Resolves an index in the table index space.
+    pub fn resolve_table(&self, index: u32) -> Option<TableRef<'_, 'a>> {
+        match slot(&self.imported_tables, index) {
+            Slot::Imported(import) => {
+                let import = &self.imports[import];
+                match import.contents {
+                    ImportEntryContents::Table { element_type, ref limits } => Some(TableRef::Imported {
+                        module: import.module,
+                        field: import.field,
+                        element_type,
+                        limits,
+                    }),
+                    _ => unreachable!(),
+                }
+            },
+            Slot::Defined(defined) => self.tables.get(defined).map(|entry| TableRef::Defined {
+                local_index: defined as u32,
+                element_type: entry.ty,
+                limits: &entry.limits,
+            }),
+        }
+    }
+
+    /// The number of memories, imported and defined.
+    pub fn memory_count(&self) -> u32 {
+        (self.imported_memories.len() + self.memories.len()) as u32
+    }
+
+    /// Resolves an index in the memory index space.
+    pub fn resolve_memory(&self, index: u32) -> Option<MemoryRef<'_, 'a>> {
+        match slot(&self.imported_memories, index) {
+            Slot::Imported(import) => {
+                let import = &self.imports[import];
+                match import.contents {
+                    ImportEntryContents::Memory(ref limits) => Some(MemoryRef::Imported {
+                        module: import.module,
+                        field: import.field,
+                        limits,
+                    }),
+                    _ => unreachable!(),
+                }
+            },
+            Slot::Defined(defined) => self.memories.get(defined).map(|entry| MemoryRef::Defined {
+                local_index: defined as u32,
+                limits: &entry.limits,
+            }),
+        }
+    }
+
+    /// The number of globals, imported and defined.
+    pub fn global_count(&self) -> u32 {
+        (self.imported_globals.len() + self.globals.len()) as u32
+    }
+
+    /// Looks up a global by its index in the global index space, which
+    /// counts imported globals first.
+    pub fn global(&self, index: u32) -> Option<GlobalInfo<'_, 'a>> {
+        self.resolve_global(index).map(|global| match global {
+            GlobalRef::Imported { ty, mutable, .. } => GlobalInfo {
+                ty,
+                mutable,
+                init: None,
+            },
+            GlobalRef::Defined { ty, mutable, init, .. } => GlobalInfo {
+                ty,
+                mutable,
+                init: Some(init),
+            },
+        })
+    }
+
+    /// Resolves an index in the global index space, as used by `GetGlobal`
+    /// and `SetGlobal`.
+    pub fn resolve_global(&self, index: u32) -> Option<GlobalRef<'_, 'a>> {
+        match slot(&self.imported_globals, index) {
+            Slot::Imported(import) => {
+                let import = &self.imports[import];
+                match import.contents {
+                    ImportEntryContents::Global { ty, mutable } => Some(GlobalRef::Imported {
+                        module: import.module,
+                        field: import.field,
+                        ty,
+                        mutable,
+                    }),
+                    _ => unreachable!(),
+                }
+            },
+            Slot::Defined(defined) => self.globals.get(defined).map(|(entry, init)| GlobalRef::Defined {
+                local_index: defined as u32,
+                ty: entry.ty,
+                mutable: entry.mutable,
+                init: &init[..],
+            }),
+        }
+    }
+
+    /// Resolves what an export refers to.
+    pub fn resolve_export(&self, export: &ExportEntry<'a>) -> Option<ExternRef<'_, 'a>> {
+        match export.kind {
+            ExternalKind::Function => self.resolve_function(export.index).map(ExternRef::Function),
+            ExternalKind::Table => self.resolve_table(export.index).map(ExternRef::Table),
+            ExternalKind::Memory => self.resolve_memory(export.index).map(ExternRef::Memory),
+            ExternalKind::Global => self.resolve_global(export.index).map(ExternRef::Global),
+        }
+    }
+
+    pub fn imports(&self) -> &[ImportEntry<'a>] {
+        &self.imports
+    }
+
+    pub fn exports(&self) -> &[ExportEntry<'a>] {
+        &self.exports
+    }
+
+    /// Looks up an export by name.
+    pub fn export(&self, name: &str) -> Option<&ExportEntry<'a>> {
+        self.export_names.get(name).map(|&index| &self.exports[index])
+    }
+}
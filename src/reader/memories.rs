@@ -3,11 +3,15 @@ use super::*;
 pub struct MemorySection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct MemoryEntryIterator<'a> {
     count: u32,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    /// The length of all entries, for the offsets in errors.
+    len: usize,
+    config: ParserConfig,
 }
 
 pub struct MemoryEntry {
@@ -18,7 +22,9 @@ impl<'a> MemorySection<'a> {
     pub fn entries(&self) -> MemoryEntryIterator<'a> {
         MemoryEntryIterator {
             count: self.count,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            len: self.entries_raw.len(),
+            config: self.config,
         }
     }
 }
@@ -31,9 +37,10 @@ impl<'a> Iterator for MemoryEntryIterator<'a> {
             return None
         }
         self.count -= 1;
-        let limits = try_opt!(ResizableLimits::parse(&mut self.iter));
+        let start = self.len - self.iter.len();
+        let limits = try_opt!(ResizableLimits::parse(&mut self.iter, &self.config.features).map_err(|err| err.offset_by(start)));
         Some(Ok(MemoryEntry {
-            limits: limits,
+            limits,
         }))
     }
 }
@@ -1,4 +1,7 @@
-use std::io;
+use core::fmt;
+use core::str::{from_utf8, Utf8Error};
+#[cfg(feature = "std")]
+use std::{error, io};
 
 macro_rules! try_opt {
     ($ex:expr) => {
@@ -9,41 +12,134 @@ macro_rules! try_opt {
     }
 }
 
-quick_error! {
-    #[derive(Debug)]
-    pub enum Error {
-        NotWasm(err: io::Error) {
-            cause(err)
-            description("Not a valid WebAssembly binary")
-            display("Not a valid WebAssembly binary: {}", err)
-        }
-        Io(err: io::Error) {
-            description("IO error")
-            cause(err)
-            display("{}", err)
-            from()
+/// Errors produced while reading a module.
+#[derive(Debug)]
+pub enum Error {
+    /// The input does not start with the WebAssembly magic number.
+    NotWasm,
+    /// The input ended in the middle of an item.
+    UnexpectedEof,
+    /// The input is structurally invalid.
+    Malformed(&'static str),
+    Leb128(&'static str),
+    UnknownVariant(&'static str),
+    Utf8(Utf8Error),
+    LimitExceeded(&'static str, usize),
+    /// The module uses a proposal that is not enabled in
+    /// `ParserConfig::features`, at the given offset.
+    ///
+    /// The offset is that of the section, entry or operator that uses the
+    /// proposal, counted from the start of the input of the reader that
+    /// found it: the module for `SectionsIterator`, the entries after the
+    /// count for the iterators over a section, the body after the local
+    /// count for `FunctionBody::contents`, and the operators for
+    /// `OpIterator`. Functions that read a single item, such as
+    /// `ResizableLimits::parse`, report 0.
+    Disabled(&'static str, usize),
+    /// Reading from the underlying stream failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NotWasm => write!(f, "Not a valid WebAssembly binary"),
+            Error::UnexpectedEof => write!(f, "Unexpected end of input"),
+            Error::Malformed(what) => write!(f, "Malformed module: {}", what),
+            Error::Leb128(what) => write!(f, "Malformed LEB128 integer: {}", what),
+            Error::UnknownVariant(of) => write!(f, "Unknown enum variant for {}", of),
+            Error::Utf8(ref err) => write!(f, "UTF-8 error: {}", err),
+            Error::LimitExceeded(what, limit) => write!(f, "{} exceeds the configured limit of {}", what, limit),
+            Error::Disabled(feature, offset) => write!(f, "The {} proposal is not enabled (at offset {:#x})", feature, offset),
+            #[cfg(feature = "std")]
+            Error::Io(ref err) => write!(f, "{}", err),
         }
-        Leb128(err: ::leb128::read::Error) {
-            description("Malformed LEB128 integer")
-            cause(err)
-            display("Malformed LEB128 integer: {}", err)
-            from()
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Utf8(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            _ => None,
         }
-        UnknownVariant(of: &'static str) {
-            description("Unknown enum variant")
-            display("Unknown enum variant for {}", of)
+    }
+}
+
+impl Error {
+    /// Moves the offset of an `Error::Disabled` forward by `by`, for a
+    /// reader that found it in a part of its input starting there.
+    fn offset_by(self, by: usize) -> Error {
+        match self {
+            Error::Disabled(feature, offset) => Error::Disabled(feature, offset + by),
+            err => err,
         }
-        Utf8(err: ::std::str::Utf8Error) {
-            description("UTF-8 error")
-            cause(err)
-            display("UTF-8 error: {}", err)
-            from()
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(err: Utf8Error) -> Error {
+        Error::Utf8(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            Error::UnexpectedEof
+        } else {
+            Error::Io(err)
         }
     }
 }
 
-pub use leb128::read::unsigned as read_varuint;
-pub use leb128::read::signed as read_varint;
+/// Reads a length-prefixed UTF-8 string, such as an import or export name.
+fn read_name<'a>(iter: &mut &'a [u8], config: &ParserConfig) -> Result<&'a str, Error> {
+    let len = read_var_u32(iter)?;
+    ParserConfig::check("name length", len as usize, config.max_name_len)?;
+    let res = read_bytes(iter, len as usize)?;
+    Ok(from_utf8(res)?)
+}
+
+/// Reads a block type. It is encoded as a signed 33-bit integer, with the
+/// negative values standing for the inline signatures. Non-negative values
+/// are type indices, which the multi-value proposal introduced and which
+/// are not supported.
+fn read_block_type(iter: &mut &[u8], features: &WasmFeatures) -> Result<InlineSignatureType, Error> {
+    let value = read_var_s33(iter)?;
+    if value >= 0 {
+        WasmFeatures::check("multi-value", features.multi_value)?;
+    }
+    if !(-0x40..0).contains(&value) {
+        return Err(Error::UnknownVariant("inline signature type"))
+    }
+    let code = (value & 0x7f) as u8;
+    InlineSignatureType::from_int(code).ok_or_else(|| features.value_type_error(code, "inline signature type"))
+}
+
+/// Reads a value type, naming the proposal it belongs to if that is
+/// disabled.
+fn read_value_type(iter: &mut &[u8], features: &WasmFeatures) -> Result<ValueType, Error> {
+    let code = read_u8(iter)?;
+    ValueType::from_int(code).ok_or_else(|| features.value_type_error(code, "value type"))
+}
+
+pub mod primitives;
+pub use self::primitives::{
+    read_u8, read_bytes, read_u32, read_u64, read_f32, read_f64,
+    read_varuint, read_varint, read_leb128, read_var_u32, read_var_s32, read_var_s33,
+    read_var_s64, Leb128, Leb128Kind
+};
+
+pub mod config;
+pub use self::config::ParserConfig;
+
+pub mod features;
+pub use self::features::WasmFeatures;
 
 pub mod enums;
 pub use self::enums::{ValueType, SectionType, ExternalKind, InlineSignatureType, LanguageType};
@@ -80,6 +176,11 @@ pub use self::memories::{
     MemorySection, MemoryEntryIterator, MemoryEntry
 };
 
+pub mod tags;
+pub use self::tags::{
+    TagSection, TagEntryIterator
+};
+
 pub mod globals;
 pub use self::globals::{
     GlobalSection, GlobalEntryIterator, GlobalEntry, GlobalEntryEither
@@ -97,7 +198,7 @@ pub use self::elements::{
 
 pub mod codes;
 pub use self::codes::{
-    CodeSection, CodeIterator, FunctionBody, FunctionPart, Local
+    CodeSection, CodeIterator, FunctionBody, FunctionPart, Local, Locals
 };
 
 pub mod data;
@@ -107,7 +208,46 @@ pub use self::data::{
 
 pub mod names;
 pub use self::names::{
-    NameSection, NameEntryIterator, NameEntry
+    NameSection, NameEntryIterator, NameEntry, NameSubsection, NameSubsectionIterator,
+    NameMapIterator, FunctionNameIterator, IndirectNameMapIterator, LocalNameIterator
 };
 
+#[macro_use]
+mod operators;
 pub mod bytecode;
+
+pub mod opinfo;
+pub use self::opinfo::{
+    OpInfo, OpCategory, ImmediateKind, StackEffect
+};
+
+pub mod visitor;
+pub use self::visitor::{
+    OperatorVisitor, visit_operators
+};
+
+pub mod control;
+pub use self::control::{
+    FrameKind, ScopedOp, ScopedOps, Node, ControlTree, BodyOps
+};
+
+pub mod streaming;
+pub use self::streaming::{
+    StreamParser, Payload
+};
+
+#[cfg(feature = "std")]
+pub mod seekable;
+#[cfg(feature = "std")]
+pub use self::seekable::{
+    SeekModule, SectionInfo, CodeReader
+};
+
+pub mod index;
+pub use self::index::{
+    ModuleIndex, IndexedSection, IndexedBody, FunctionInfo, GlobalInfo,
+    FunctionRef, GlobalRef, TableRef, MemoryRef, ExternRef
+};
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
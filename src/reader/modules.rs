@@ -1,20 +1,31 @@
 use super::*;
-use std::io;
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::str::from_utf8;
-use std::io::Read;
+use core::str::from_utf8;
 
 pub struct Module<'a> {
     pub version: u32,
     pub payload: &'a [u8],
+    pub config: ParserConfig,
 }
 
-pub struct SectionsIterator<'a>(&'a [u8]);
+pub struct SectionsIterator<'a> {
+    iter: &'a [u8],
+    /// The length of the whole payload, for `offset`.
+    len: usize,
+    count: usize,
+    config: ParserConfig,
+    strict: bool,
+    /// The last non-custom section, in strict mode.
+    last: Option<SectionType>,
+    /// The entry counts of the function and code sections, in strict mode.
+    functions: u32,
+    codes: u32,
+}
 
 pub struct Section<'a> {
     pub id: SectionType,
     pub name: &'a str,
     pub payload: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub enum SectionContent<'a> {
@@ -23,73 +34,236 @@ pub enum SectionContent<'a> {
     Function(FunctionSection<'a>),
     Table(TableSection<'a>),
     Memory(MemorySection<'a>),
+    Tag(TagSection<'a>),
     Global(GlobalSection<'a>),
     Export(ExportSection<'a>),
     Start(u32),
     Elements(ElementSection<'a>),
     Code(CodeSection<'a>),
     Data(DataSection<'a>),
+    /// The number of data segments.
+    DataCount(u32),
     Name(NameSection<'a>),
 }
 
 impl<'a> Module<'a> {
-    pub fn new(mut stream: &'a [u8]) -> Result<Module<'a>, Error> {
-        let mut magic = [0; 4];
-        try!((&mut stream).read_exact(&mut magic).map_err(Error::NotWasm));
-        if &magic != b"\0asm" {
-            return Err(Error::NotWasm(io::Error::new(
-                io::ErrorKind::InvalidData, "Magic number did not match"
-            )))
+    pub fn new(stream: &'a [u8]) -> Result<Module<'a>, Error> {
+        Module::with_config(stream, ParserConfig::default())
+    }
+
+    pub fn with_config(mut stream: &'a [u8], config: ParserConfig) -> Result<Module<'a>, Error> {
+        match read_bytes(&mut stream, 4) {
+            Ok(magic) if magic == b"\0asm" => (),
+            _ => return Err(Error::NotWasm),
         }
-        let version = try!((&mut stream).read_u32::<LittleEndian>());
+        let version = read_u32(&mut stream)?;
         Ok(Module {
-            version: version,
-            payload: stream
+            version,
+            payload: stream,
+            config,
         })
     }
 
     pub fn sections(&'a self) -> SectionsIterator<'a> {
-        SectionsIterator(self.payload)
+        SectionsIterator::new(self.payload, self.config)
+    }
+
+    /// Like `sections`, but checks the section layout. See
+    /// `SectionsIterator::strict`.
+    pub fn strict_sections(&'a self) -> SectionsIterator<'a> {
+        self.sections().strict()
+    }
+
+    /// Decodes the whole module into an owned `ast::Module`.
+    pub fn decode(&self) -> Result<::ast::Module, Error> {
+        ::ast::Module::decode(self)
+    }
+
+    /// Checks the module against the validation rules of the spec. See
+    /// `validate::validate`.
+    pub fn validate(&self) -> Result<(), ::validate::ValidationError> {
+        ::validate::validate(self)
+    }
+
+    /// Finds the proposals the module uses. See `detect::detect`.
+    pub fn detect_features(&self) -> Result<::detect::FeatureReport, Error> {
+        ::detect::detect(self)
     }
 }
 
-impl<'a> Iterator for SectionsIterator<'a> {
-    type Item = Result<Section<'a>, Error>;
+impl<'a> SectionsIterator<'a> {
+    /// Iterates over the sections in `payload`, which starts right after
+    /// the module header.
+    pub fn new(payload: &'a [u8], config: ParserConfig) -> SectionsIterator<'a> {
+        SectionsIterator {
+            iter: payload,
+            len: payload.len(),
+            count: 0,
+            config,
+            strict: false,
+            last: None,
+            functions: 0,
+            codes: 0,
+        }
+    }
 
-    fn next(&mut self) -> Option<Result<Section<'a>, Error>> {
-        if self.0.len() == 0 {
-            return None
+    /// Switches to strict mode, which follows the layout rules of the
+    /// spec:
+    ///
+    /// - Known sections appear at most once, in the order type, import,
+    ///   function, table, memory, tag, global, export, start, element,
+    ///   data count, code, data. Custom sections can go anywhere.
+    /// - A section that extends past the end of the module, or a name
+    ///   that extends past the end of its section, is an error rather than
+    ///   the end of the iteration. So are bytes after the last section
+    ///   that do not form a complete section.
+    /// - The start and data count sections contain nothing but their
+    ///   index.
+    /// - The function and code sections have the same number of entries,
+    ///   checked once all sections have been read.
+    ///
+    /// The iterator stops after the first error.
+    pub fn strict(mut self) -> SectionsIterator<'a> {
+        self.strict = true;
+        self
+    }
+
+    /// The offset of the next section from the start of the module.
+    pub fn offset(&self) -> usize {
+        8 + self.len - self.iter.len()
+    }
+
+    /// Returns `None` in lenient mode, which stops at a section that does
+    /// not fit, and the error in strict mode.
+    fn overrun(&self, message: &'static str) -> Option<Result<Section<'a>, Error>> {
+        if self.strict {
+            Some(Err(Error::Malformed(message)))
+        } else {
+            None
         }
-        let id = try_opt!(read_varuint(&mut self.0));
-        let plen = try_opt!(read_varuint(&mut self.0));
-        let start = self.0.len() as u64;
+    }
+
+    fn read_section(&mut self) -> Option<Result<Section<'a>, Error>> {
+        let offset = self.offset();
+        self.count += 1;
+        try_opt!(ParserConfig::check("number of sections", self.count, self.config.max_sections));
+        let (id, plen) = match (read_var_u32(&mut self.iter), read_var_u32(&mut self.iter)) {
+            (Ok(id), Ok(plen)) => (id, plen as u64),
+            (Err(Error::UnexpectedEof), _) | (_, Err(Error::UnexpectedEof)) if self.strict => {
+                return Some(Err(Error::Malformed("trailing bytes after the last section")))
+            },
+            (Err(e), _) | (_, Err(e)) => return Some(Err(e)),
+        };
+        let start = self.iter.len() as u64;
         let nlen = if id == 0 {
-            try_opt!(read_varuint(&mut self.0))
+            try_opt!(read_var_u32(&mut self.iter)) as u64
         } else {
             0
         };
-        let name = if nlen > self.0.len() as u64 {
-            return None
+        let name = if nlen > self.iter.len() as u64 {
+            return self.overrun("section name extends past the end of the module")
         } else {
-            let res = &self.0[0..nlen as usize];
-            self.0 = &self.0[nlen as usize..];
+            try_opt!(ParserConfig::check("section name length", nlen as usize, self.config.max_name_len));
+            let res = &self.iter[0..nlen as usize];
+            self.iter = &self.iter[nlen as usize..];
             res
         };
-        let plen = plen - (start - self.0.len() as u64);
-        let payload = if plen > self.0.len() as u64 {
-            return None
+        let plen = match plen.checked_sub(start - self.iter.len() as u64) {
+            Some(plen) => plen,
+            None => return self.overrun("section name extends past the end of the section"),
+        };
+        let payload = if plen > self.iter.len() as u64 {
+            return self.overrun("section extends past the end of the module")
         } else {
-            let res = &self.0[0..plen as usize];
-            self.0 = &self.0[plen as usize..];
+            let res = &self.iter[0..plen as usize];
+            self.iter = &self.iter[plen as usize..];
             res
         };
         let id = try_opt!(SectionType::from_int(id as u8).ok_or(Error::UnknownVariant("section type")));
+        try_opt!(self.config.features.check_section(id).map_err(|err| err.offset_by(offset)));
+        if self.strict {
+            try_opt!(self.check_layout(id, payload));
+        }
         Some(Ok(Section {
-            id: id,
+            id,
             name: try_opt!(from_utf8(name)),
-            payload: payload,
+            payload,
+            config: self.config,
         }))
     }
+
+    fn check_layout(&mut self, id: SectionType, payload: &[u8]) -> Result<(), Error> {
+        if id == SectionType::Named {
+            return Ok(())
+        }
+        if let Some(last) = self.last {
+            if last == id {
+                return Err(Error::Malformed("duplicate section"))
+            }
+            if section_order(last) > section_order(id) {
+                return Err(Error::Malformed("section out of order"))
+            }
+        }
+        self.last = Some(id);
+        let mut iter = payload;
+        match id {
+            SectionType::Function => self.functions = read_var_u32(&mut iter)?,
+            SectionType::Code => self.codes = read_var_u32(&mut iter)?,
+            SectionType::Start | SectionType::DataCount => {
+                read_var_u32(&mut iter)?;
+                if !iter.is_empty() {
+                    return Err(Error::Malformed("trailing bytes in section"))
+                }
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// The position of a known section in the required order.
+fn section_order(id: SectionType) -> u8 {
+    match id {
+        SectionType::Named => 0,
+        SectionType::Type => 1,
+        SectionType::Import => 2,
+        SectionType::Function => 3,
+        SectionType::Table => 4,
+        SectionType::Memory => 5,
+        SectionType::Tag => 6,
+        SectionType::Global => 7,
+        SectionType::Export => 8,
+        SectionType::Start => 9,
+        SectionType::Element => 10,
+        SectionType::DataCount => 11,
+        SectionType::Code => 12,
+        SectionType::Data => 13,
+    }
+}
+
+impl<'a> Iterator for SectionsIterator<'a> {
+    type Item = Result<Section<'a>, Error>;
+
+    fn next(&mut self) -> Option<Result<Section<'a>, Error>> {
+        if self.iter.is_empty() {
+            if self.strict && self.functions != self.codes {
+                self.functions = self.codes;
+                return Some(Err(Error::Malformed("function and code section have different lengths")))
+            }
+            return None
+        }
+        let section = self.read_section();
+        if self.strict {
+            match section {
+                Some(Ok(_)) => (),
+                _ => {
+                    self.iter = &[];
+                    self.functions = self.codes;
+                },
+            }
+        }
+        section
+    }
 }
 
 impl<'a> Section<'a> {
@@ -97,86 +271,105 @@ impl<'a> Section<'a> {
         match self.id {
             SectionType::Type => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as usize;
-                Ok(SectionContent::Type(TypeSection(iter, count)))
+                let count = read_var_u32(&mut iter)? as usize;
+                ParserConfig::check("number of types", count, self.config.max_types)?;
+                Ok(SectionContent::Type(TypeSection(iter, count, self.config)))
             },
             SectionType::Import => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as usize;
-                Ok(SectionContent::Import(ImportSection(iter, count)))
+                let count = read_var_u32(&mut iter)? as usize;
+                ParserConfig::check("number of imports", count, self.config.max_imports)?;
+                Ok(SectionContent::Import(ImportSection(iter, count, self.config)))
             },
             SectionType::Function => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as usize;
+                let count = read_var_u32(&mut iter)? as usize;
+                ParserConfig::check("number of functions", count, self.config.max_functions)?;
                 Ok(SectionContent::Function(FunctionSection(iter, count)))
             },
             SectionType::Table => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Table(TableSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
             SectionType::Memory => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Memory(MemorySection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
+            SectionType::Tag => {
+                let mut iter = self.payload;
+                let count = read_var_u32(&mut iter)? as usize;
+                Ok(SectionContent::Tag(TagSection(iter, count)))
+            },
             SectionType::Global => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Global(GlobalSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
             SectionType::Export => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Export(ExportSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
             SectionType::Start => {
                 let mut r = self.payload;
-                let index = try!(read_varuint(&mut r));
-                Ok(SectionContent::Start(index as u32))
+                let index = read_var_u32(&mut r)?;
+                Ok(SectionContent::Start(index))
             },
             SectionType::Element => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Elements(ElementSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
             SectionType::Code => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
+                ParserConfig::check("number of functions", count as usize, self.config.max_functions)?;
                 Ok(SectionContent::Code(CodeSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
             SectionType::Data => {
                 let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
+                let count = read_var_u32(&mut iter)?;
                 Ok(SectionContent::Data(DataSection {
-                    count: count,
-                    entries_raw: iter
+                    count,
+                    entries_raw: iter,
+                    config: self.config,
                 }))
             },
+            SectionType::DataCount => {
+                let mut r = self.payload;
+                let count = read_var_u32(&mut r)?;
+                Ok(SectionContent::DataCount(count))
+            },
             SectionType::Named if self.name == "name" => {
-                let mut iter = self.payload;
-                let count = try!(read_varuint(&mut iter)) as u32;
                 Ok(SectionContent::Name(NameSection {
-                    count: count,
-                    entries_raw: iter
+                    payload: self.payload,
+                    config: self.config,
                 }))
             },
             _ => Err(Error::UnknownVariant("section type"))
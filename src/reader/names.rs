@@ -1,15 +1,24 @@
+//! The `name` custom section.
+//!
+//! The spec lays the section out as subsections, each an id and a size,
+//! in increasing order of their ids. Subsection 1 maps function indices to
+//! names, and subsection 2 maps function indices to maps from local
+//! indices to names. Older toolchains wrote the name of each function in
+//! order instead, each followed by the names of its locals, which
+//! `entries` reads. `function_names` and `local_names` read either format.
+
 use super::*;
-use std::str::from_utf8;
 
 pub struct NameSection<'a> {
-    pub count: u32,
-    pub entries_raw: &'a [u8],
+    pub payload: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct NameEntryIterator<'a> {
-    count: u32,
+    count: Option<u32>,
     local_count: u32,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    config: ParserConfig,
 }
 
 pub enum NameEntry<'a> {
@@ -17,12 +26,127 @@ pub enum NameEntry<'a> {
     Local(&'a str),
 }
 
+/// A subsection of a name section in the format of the spec.
+pub struct NameSubsection<'a> {
+    pub id: u8,
+    pub payload: &'a [u8],
+}
+
+pub struct NameSubsectionIterator<'a> {
+    iter: &'a [u8],
+}
+
+/// The entries of a name map, each an index and a name.
+pub struct NameMapIterator<'a> {
+    count: Option<u32>,
+    iter: &'a [u8],
+    config: ParserConfig,
+}
+
+/// The function names of a name section in either format, by function
+/// index.
+pub enum FunctionNameIterator<'a> {
+    Map(NameMapIterator<'a>),
+    Legacy(u32, NameEntryIterator<'a>),
+}
+
+/// The entries of a map from function indices to name maps, each a
+/// function index, a local index and a name.
+pub struct IndirectNameMapIterator<'a> {
+    count: Option<u32>,
+    function: u32,
+    locals: u32,
+    iter: &'a [u8],
+    config: ParserConfig,
+}
+
+/// The local names of a name section in either format, by function index
+/// and local index.
+pub enum LocalNameIterator<'a> {
+    Map(IndirectNameMapIterator<'a>),
+    /// The index of the last function named and of its next local.
+    Legacy(Option<u32>, u32, NameEntryIterator<'a>),
+}
+
 impl<'a> NameSection<'a> {
+    /// The entries of a name section in the older format.
     pub fn entries(&self) -> NameEntryIterator<'a> {
         NameEntryIterator {
-            count: self.count,
+            count: None,
             local_count: 0,
-            iter: self.entries_raw
+            iter: self.payload,
+            config: self.config,
+        }
+    }
+
+    /// The subsections of a name section in the format of the spec, or
+    /// `None` if the section is not laid out that way: the subsections have
+    /// to fill it exactly, in increasing order of their ids.
+    pub fn subsections(&self) -> Option<NameSubsectionIterator<'a>> {
+        let mut iter = self.payload;
+        let mut last = None;
+        while !iter.is_empty() {
+            let id = read_u8(&mut iter).ok()?;
+            if last.is_some_and(|last| last >= id) {
+                return None
+            }
+            last = Some(id);
+            let size = read_var_u32(&mut iter).ok()?;
+            read_bytes(&mut iter, size as usize).ok()?;
+        }
+        Some(NameSubsectionIterator {
+            iter: self.payload,
+        })
+    }
+
+    /// The contents of subsection `id`, empty if the section has none, or
+    /// `None` if it is in the older format.
+    fn subsection(&self, id: u8) -> Option<&'a [u8]> {
+        let mut subsections = self.subsections()?;
+        Some(subsections.find(|subsection| subsection.id == id).map_or(&[][..], |subsection| subsection.payload))
+    }
+
+    /// The function names, from subsection 1 in the format of the spec or
+    /// by position in the older format.
+    pub fn function_names(&self) -> FunctionNameIterator<'a> {
+        match self.subsection(1) {
+            Some(payload) => FunctionNameIterator::Map(NameMapIterator::new(payload, self.config)),
+            None => FunctionNameIterator::Legacy(0, self.entries()),
+        }
+    }
+
+    /// The local names, parameters first, from subsection 2 in the format
+    /// of the spec or by position in the older format.
+    pub fn local_names(&self) -> LocalNameIterator<'a> {
+        match self.subsection(2) {
+            Some(payload) => LocalNameIterator::Map(IndirectNameMapIterator::new(payload, self.config)),
+            None => LocalNameIterator::Legacy(None, 0, self.entries()),
+        }
+    }
+}
+
+impl<'a> NameMapIterator<'a> {
+    /// Iterates over a name map, such as the contents of subsection 1. The
+    /// count at its start is read by the first call to `next`, and an empty
+    /// `payload` is an empty map.
+    pub fn new(payload: &'a [u8], config: ParserConfig) -> NameMapIterator<'a> {
+        NameMapIterator {
+            count: if payload.is_empty() { Some(0) } else { None },
+            iter: payload,
+            config,
+        }
+    }
+}
+
+impl<'a> IndirectNameMapIterator<'a> {
+    /// Like `NameMapIterator::new`, for the contents of subsection 2.
+    pub fn new(payload: &'a [u8], config: ParserConfig) -> IndirectNameMapIterator<'a> {
+        IndirectNameMapIterator {
+            count: if payload.is_empty() { Some(0) } else { None },
+            function: 0,
+            locals: 0,
+            iter: payload,
+            config,
         }
     }
 }
@@ -31,24 +155,128 @@ impl<'a> Iterator for NameEntryIterator<'a> {
     type Item = Result<NameEntry<'a>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.count == 0 && self.local_count == 0 {
+        let count = match self.count {
+            Some(count) => count,
+            None => try_opt!(read_var_u32(&mut self.iter)),
+        };
+        self.count = Some(count);
+        if count == 0 && self.local_count == 0 {
             return None
         }
         if self.local_count > 0 {
             self.local_count -= 1;
-            let len = try_opt!(read_varuint(&mut self.iter)) as usize;
-            let name = &self.iter[..len];
-            self.iter = &self.iter[len..];
-            let name = try_opt!(from_utf8(name));
+            let name = try_opt!(read_name(&mut self.iter, &self.config));
             return Some(Ok(NameEntry::Local(name)))
         }
-        self.count -= 1;
-        let len = try_opt!(read_varuint(&mut self.iter)) as usize;
-        let name = &self.iter[..len];
-        self.iter = &self.iter[len..];
-        let name = try_opt!(from_utf8(name));
-        let count = try_opt!(read_varuint(&mut self.iter)) as u32;
+        self.count = Some(count - 1);
+        let name = try_opt!(read_name(&mut self.iter, &self.config));
+        let count = try_opt!(read_var_u32(&mut self.iter));
         self.local_count = count;
         Some(Ok(NameEntry::Function(name)))
     }
 }
+
+impl<'a> Iterator for NameSubsectionIterator<'a> {
+    type Item = NameSubsection<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // `NameSection::subsections` has checked the layout.
+        let id = read_u8(&mut self.iter).ok()?;
+        let size = read_var_u32(&mut self.iter).ok()?;
+        let payload = read_bytes(&mut self.iter, size as usize).ok()?;
+        Some(NameSubsection {
+            id,
+            payload,
+        })
+    }
+}
+
+impl<'a> Iterator for NameMapIterator<'a> {
+    type Item = Result<(u32, &'a str), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let count = match self.count {
+            Some(count) => count,
+            None => try_opt!(read_var_u32(&mut self.iter)),
+        };
+        if count == 0 {
+            self.count = Some(0);
+            return None
+        }
+        self.count = Some(count - 1);
+        let index = try_opt!(read_var_u32(&mut self.iter));
+        let name = try_opt!(read_name(&mut self.iter, &self.config));
+        Some(Ok((index, name)))
+    }
+}
+
+impl<'a> Iterator for FunctionNameIterator<'a> {
+    type Item = Result<(u32, &'a str), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self {
+            FunctionNameIterator::Map(ref mut names) => names.next(),
+            FunctionNameIterator::Legacy(ref mut index, ref mut entries) => {
+                loop {
+                    match try_opt!(entries.next()?) {
+                        NameEntry::Function(name) => {
+                            *index += 1;
+                            return Some(Ok((*index - 1, name)))
+                        },
+                        NameEntry::Local(_) => (),
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for IndirectNameMapIterator<'a> {
+    type Item = Result<(u32, u32, &'a str), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.locals == 0 {
+            let count = match self.count {
+                Some(count) => count,
+                None => try_opt!(read_var_u32(&mut self.iter)),
+            };
+            if count == 0 {
+                self.count = Some(0);
+                return None
+            }
+            self.count = Some(count - 1);
+            self.function = try_opt!(read_var_u32(&mut self.iter));
+            self.locals = try_opt!(read_var_u32(&mut self.iter));
+        }
+        self.locals -= 1;
+        let index = try_opt!(read_var_u32(&mut self.iter));
+        let name = try_opt!(read_name(&mut self.iter, &self.config));
+        Some(Ok((self.function, index, name)))
+    }
+}
+
+impl<'a> Iterator for LocalNameIterator<'a> {
+    type Item = Result<(u32, u32, &'a str), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self {
+            LocalNameIterator::Map(ref mut names) => names.next(),
+            LocalNameIterator::Legacy(ref mut function, ref mut local, ref mut entries) => {
+                loop {
+                    match try_opt!(entries.next()?) {
+                        NameEntry::Function(_) => {
+                            *function = Some(function.map_or(0, |function| function + 1));
+                            *local = 0;
+                        },
+                        NameEntry::Local(name) => {
+                            // `NameEntryIterator` only yields locals after
+                            // the function they belong to.
+                            *local += 1;
+                            return Some(Ok((function.unwrap_or(0), *local - 1, name)))
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! The operator table.
+//!
+//! `for_each_operator!(callback)` invokes `callback!` with one entry per
+//! operator, in opcode order:
+//!
+//! ```text
+//! Name(immediate: Kind, ...) = opcode => visit_method, "mnemonic", Category, effect;
+//! ```
+//!
+//! `Kind` is an `ImmediateKind`, `Category` an `OpCategory`, and the effect
+//! is `dynamic` or `([pops] -> [pushes])` in `ValueType`s. Attributes before
+//! an entry apply to its variant of `Op`. `OpCode`, `Op`, the decoder,
+//! `OpCode::info`, `OperatorVisitor` and `OpEncoder` are all generated from
+//! it, so an operator is added by adding its entry here.
+
+macro_rules! for_each_operator {
+    ($callback:ident) => {
+        $callback! {
+            // control flow
+            Unreachable = 0x00 => visit_unreachable, "unreachable", Control, dynamic;
+            Nop = 0x01 => visit_nop, "nop", Control, ([] -> []);
+            Block(ty: BlockType) = 0x02 => visit_block, "block", Control, dynamic;
+            Loop(ty: BlockType) = 0x03 => visit_loop, "loop", Control, dynamic;
+            If(ty: BlockType) = 0x04 => visit_if, "if", Control, dynamic;
+            Else = 0x05 => visit_else, "else", Control, dynamic;
+            End = 0x0b => visit_end, "end", Control, dynamic;
+            Br(relative_depth: LabelIndex) = 0x0c => visit_br, "br", Control, dynamic;
+            BrIf(relative_depth: LabelIndex) = 0x0d => visit_br_if, "br_if", Control, dynamic;
+            BrTable(table: LabelTable) = 0x0e => visit_br_table, "br_table", Control, dynamic;
+            Return = 0x0f => visit_return, "return", Control, dynamic;
+
+            // call operators
+            Call(function_index: FunctionIndex) = 0x10 => visit_call, "call", Control, dynamic;
+            CallIndirect(type_index: TypeIndex, reserved: Reserved) = 0x11 => visit_call_indirect, "call_indirect", Control, dynamic;
+
+            // parametric operators
+            Drop = 0x1a => visit_drop, "drop", Parametric, dynamic;
+            Select = 0x1b => visit_select, "select", Parametric, dynamic;
+
+            // basic operators
+            GetLocal(local_index: LocalIndex) = 0x20 => visit_get_local, "local.get", Variable, dynamic;
+            SetLocal(local_index: LocalIndex) = 0x21 => visit_set_local, "local.set", Variable, dynamic;
+            TeeLocal(local_index: LocalIndex) = 0x22 => visit_tee_local, "local.tee", Variable, dynamic;
+            GetGlobal(global_index: GlobalIndex) = 0x23 => visit_get_global, "global.get", Variable, dynamic;
+            SetGlobal(global_index: GlobalIndex) = 0x24 => visit_set_global, "global.set", Variable, dynamic;
+
+            // memory-related
+            I32Load(memarg: Memory) = 0x28 => visit_i32_load, "i32.load", Memory, ([I32] -> [I32]);
+            I64Load(memarg: Memory) = 0x29 => visit_i64_load, "i64.load", Memory, ([I32] -> [I64]);
+            F32Load(memarg: Memory) = 0x2a => visit_f32_load, "f32.load", Memory, ([I32] -> [F32]);
+            F64Load(memarg: Memory) = 0x2b => visit_f64_load, "f64.load", Memory, ([I32] -> [F64]);
+            I32Load8S(memarg: Memory) = 0x2c => visit_i32_load8_s, "i32.load8_s", Memory, ([I32] -> [I32]);
+            I32Load8U(memarg: Memory) = 0x2d => visit_i32_load8_u, "i32.load8_u", Memory, ([I32] -> [I32]);
+            I32Load16S(memarg: Memory) = 0x2e => visit_i32_load16_s, "i32.load16_s", Memory, ([I32] -> [I32]);
+            I32Load16U(memarg: Memory) = 0x2f => visit_i32_load16_u, "i32.load16_u", Memory, ([I32] -> [I32]);
+            I64Load8S(memarg: Memory) = 0x30 => visit_i64_load8_s, "i64.load8_s", Memory, ([I32] -> [I64]);
+            I64Load8U(memarg: Memory) = 0x31 => visit_i64_load8_u, "i64.load8_u", Memory, ([I32] -> [I64]);
+            I64Load16S(memarg: Memory) = 0x32 => visit_i64_load16_s, "i64.load16_s", Memory, ([I32] -> [I64]);
+            I64Load16U(memarg: Memory) = 0x33 => visit_i64_load16_u, "i64.load16_u", Memory, ([I32] -> [I64]);
+            I64Load32S(memarg: Memory) = 0x34 => visit_i64_load32_s, "i64.load32_s", Memory, ([I32] -> [I64]);
+            I64Load32U(memarg: Memory) = 0x35 => visit_i64_load32_u, "i64.load32_u", Memory, ([I32] -> [I64]);
+            I32Store(memarg: Memory) = 0x36 => visit_i32_store, "i32.store", Memory, ([I32, I32] -> []);
+            I64Store(memarg: Memory) = 0x37 => visit_i64_store, "i64.store", Memory, ([I32, I64] -> []);
+            F32Store(memarg: Memory) = 0x38 => visit_f32_store, "f32.store", Memory, ([I32, F32] -> []);
+            F64Store(memarg: Memory) = 0x39 => visit_f64_store, "f64.store", Memory, ([I32, F64] -> []);
+            I32Store8(memarg: Memory) = 0x3a => visit_i32_store8, "i32.store8", Memory, ([I32, I32] -> []);
+            I32Store16(memarg: Memory) = 0x3b => visit_i32_store16, "i32.store16", Memory, ([I32, I32] -> []);
+            I64Store8(memarg: Memory) = 0x3c => visit_i64_store8, "i64.store8", Memory, ([I32, I64] -> []);
+            I64Store16(memarg: Memory) = 0x3d => visit_i64_store16, "i64.store16", Memory, ([I32, I64] -> []);
+            I64Store32(memarg: Memory) = 0x3e => visit_i64_store32, "i64.store32", Memory, ([I32, I64] -> []);
+            CurrentMemory(reserved: Reserved) = 0x3f => visit_current_memory, "memory.size", Memory, ([] -> [I32]);
+            GrowMemory(reserved: Reserved) = 0x40 => visit_grow_memory, "memory.grow", Memory, ([I32] -> [I32]);
+
+            // constants
+            I32Const(value: I32) = 0x41 => visit_i32_const, "i32.const", Numeric, ([] -> [I32]);
+            I64Const(value: I64) = 0x42 => visit_i64_const, "i64.const", Numeric, ([] -> [I64]);
+            #[cfg_attr(feature = "serde", serde(with = "f32_bits"))]
+            F32Const(value: F32) = 0x43 => visit_f32_const, "f32.const", Numeric, ([] -> [F32]);
+            #[cfg_attr(feature = "serde", serde(with = "f64_bits"))]
+            F64Const(value: F64) = 0x44 => visit_f64_const, "f64.const", Numeric, ([] -> [F64]);
+
+            // comparison operators
+            I32Eqz = 0x45 => visit_i32_eqz, "i32.eqz", Numeric, ([I32] -> [I32]);
+            I32Eq = 0x46 => visit_i32_eq, "i32.eq", Numeric, ([I32, I32] -> [I32]);
+            I32Ne = 0x47 => visit_i32_ne, "i32.ne", Numeric, ([I32, I32] -> [I32]);
+            I32LtS = 0x48 => visit_i32_lt_s, "i32.lt_s", Numeric, ([I32, I32] -> [I32]);
+            I32LtU = 0x49 => visit_i32_lt_u, "i32.lt_u", Numeric, ([I32, I32] -> [I32]);
+            I32GtS = 0x4a => visit_i32_gt_s, "i32.gt_s", Numeric, ([I32, I32] -> [I32]);
+            I32GtU = 0x4b => visit_i32_gt_u, "i32.gt_u", Numeric, ([I32, I32] -> [I32]);
+            I32LeS = 0x4c => visit_i32_le_s, "i32.le_s", Numeric, ([I32, I32] -> [I32]);
+            I32LeU = 0x4d => visit_i32_le_u, "i32.le_u", Numeric, ([I32, I32] -> [I32]);
+            I32GeS = 0x4e => visit_i32_ge_s, "i32.ge_s", Numeric, ([I32, I32] -> [I32]);
+            I32GeU = 0x4f => visit_i32_ge_u, "i32.ge_u", Numeric, ([I32, I32] -> [I32]);
+            I64Eqz = 0x50 => visit_i64_eqz, "i64.eqz", Numeric, ([I64] -> [I32]);
+            I64Eq = 0x51 => visit_i64_eq, "i64.eq", Numeric, ([I64, I64] -> [I32]);
+            I64Ne = 0x52 => visit_i64_ne, "i64.ne", Numeric, ([I64, I64] -> [I32]);
+            I64LtS = 0x53 => visit_i64_lt_s, "i64.lt_s", Numeric, ([I64, I64] -> [I32]);
+            I64LtU = 0x54 => visit_i64_lt_u, "i64.lt_u", Numeric, ([I64, I64] -> [I32]);
+            I64GtS = 0x55 => visit_i64_gt_s, "i64.gt_s", Numeric, ([I64, I64] -> [I32]);
+            I64GtU = 0x56 => visit_i64_gt_u, "i64.gt_u", Numeric, ([I64, I64] -> [I32]);
+            I64LeS = 0x57 => visit_i64_le_s, "i64.le_s", Numeric, ([I64, I64] -> [I32]);
+            I64LeU = 0x58 => visit_i64_le_u, "i64.le_u", Numeric, ([I64, I64] -> [I32]);
+            I64GeS = 0x59 => visit_i64_ge_s, "i64.ge_s", Numeric, ([I64, I64] -> [I32]);
+            I64GeU = 0x5a => visit_i64_ge_u, "i64.ge_u", Numeric, ([I64, I64] -> [I32]);
+            F32Eq = 0x5b => visit_f32_eq, "f32.eq", Numeric, ([F32, F32] -> [I32]);
+            F32Ne = 0x5c => visit_f32_ne, "f32.ne", Numeric, ([F32, F32] -> [I32]);
+            F32Lt = 0x5d => visit_f32_lt, "f32.lt", Numeric, ([F32, F32] -> [I32]);
+            F32Gt = 0x5e => visit_f32_gt, "f32.gt", Numeric, ([F32, F32] -> [I32]);
+            F32Le = 0x5f => visit_f32_le, "f32.le", Numeric, ([F32, F32] -> [I32]);
+            F32Ge = 0x60 => visit_f32_ge, "f32.ge", Numeric, ([F32, F32] -> [I32]);
+            F64Eq = 0x61 => visit_f64_eq, "f64.eq", Numeric, ([F64, F64] -> [I32]);
+            F64Ne = 0x62 => visit_f64_ne, "f64.ne", Numeric, ([F64, F64] -> [I32]);
+            F64Lt = 0x63 => visit_f64_lt, "f64.lt", Numeric, ([F64, F64] -> [I32]);
+            F64Gt = 0x64 => visit_f64_gt, "f64.gt", Numeric, ([F64, F64] -> [I32]);
+            F64Le = 0x65 => visit_f64_le, "f64.le", Numeric, ([F64, F64] -> [I32]);
+            F64Ge = 0x66 => visit_f64_ge, "f64.ge", Numeric, ([F64, F64] -> [I32]);
+
+            // numeric operators
+            I32Clz = 0x67 => visit_i32_clz, "i32.clz", Numeric, ([I32] -> [I32]);
+            I32Ctz = 0x68 => visit_i32_ctz, "i32.ctz", Numeric, ([I32] -> [I32]);
+            I32Popcnt = 0x69 => visit_i32_popcnt, "i32.popcnt", Numeric, ([I32] -> [I32]);
+            I32Add = 0x6a => visit_i32_add, "i32.add", Numeric, ([I32, I32] -> [I32]);
+            I32Sub = 0x6b => visit_i32_sub, "i32.sub", Numeric, ([I32, I32] -> [I32]);
+            I32Mul = 0x6c => visit_i32_mul, "i32.mul", Numeric, ([I32, I32] -> [I32]);
+            I32DivS = 0x6d => visit_i32_div_s, "i32.div_s", Numeric, ([I32, I32] -> [I32]);
+            I32DivU = 0x6e => visit_i32_div_u, "i32.div_u", Numeric, ([I32, I32] -> [I32]);
+            I32RemS = 0x6f => visit_i32_rem_s, "i32.rem_s", Numeric, ([I32, I32] -> [I32]);
+            I32RemU = 0x70 => visit_i32_rem_u, "i32.rem_u", Numeric, ([I32, I32] -> [I32]);
+            I32And = 0x71 => visit_i32_and, "i32.and", Numeric, ([I32, I32] -> [I32]);
+            I32Or = 0x72 => visit_i32_or, "i32.or", Numeric, ([I32, I32] -> [I32]);
+            I32Xor = 0x73 => visit_i32_xor, "i32.xor", Numeric, ([I32, I32] -> [I32]);
+            I32Shl = 0x74 => visit_i32_shl, "i32.shl", Numeric, ([I32, I32] -> [I32]);
+            I32ShrS = 0x75 => visit_i32_shr_s, "i32.shr_s", Numeric, ([I32, I32] -> [I32]);
+            I32ShrU = 0x76 => visit_i32_shr_u, "i32.shr_u", Numeric, ([I32, I32] -> [I32]);
+            I32Rotl = 0x77 => visit_i32_rotl, "i32.rotl", Numeric, ([I32, I32] -> [I32]);
+            I32Rotr = 0x78 => visit_i32_rotr, "i32.rotr", Numeric, ([I32, I32] -> [I32]);
+            I64Clz = 0x79 => visit_i64_clz, "i64.clz", Numeric, ([I64] -> [I64]);
+            I64Ctz = 0x7a => visit_i64_ctz, "i64.ctz", Numeric, ([I64] -> [I64]);
+            I64Popcnt = 0x7b => visit_i64_popcnt, "i64.popcnt", Numeric, ([I64] -> [I64]);
+            I64Add = 0x7c => visit_i64_add, "i64.add", Numeric, ([I64, I64] -> [I64]);
+            I64Sub = 0x7d => visit_i64_sub, "i64.sub", Numeric, ([I64, I64] -> [I64]);
+            I64Mul = 0x7e => visit_i64_mul, "i64.mul", Numeric, ([I64, I64] -> [I64]);
+            I64DivS = 0x7f => visit_i64_div_s, "i64.div_s", Numeric, ([I64, I64] -> [I64]);
+            I64DivU = 0x80 => visit_i64_div_u, "i64.div_u", Numeric, ([I64, I64] -> [I64]);
+            I64RemS = 0x81 => visit_i64_rem_s, "i64.rem_s", Numeric, ([I64, I64] -> [I64]);
+            I64RemU = 0x82 => visit_i64_rem_u, "i64.rem_u", Numeric, ([I64, I64] -> [I64]);
+            I64And = 0x83 => visit_i64_and, "i64.and", Numeric, ([I64, I64] -> [I64]);
+            I64Or = 0x84 => visit_i64_or, "i64.or", Numeric, ([I64, I64] -> [I64]);
+            I64Xor = 0x85 => visit_i64_xor, "i64.xor", Numeric, ([I64, I64] -> [I64]);
+            I64Shl = 0x86 => visit_i64_shl, "i64.shl", Numeric, ([I64, I64] -> [I64]);
+            I64ShrS = 0x87 => visit_i64_shr_s, "i64.shr_s", Numeric, ([I64, I64] -> [I64]);
+            I64ShrU = 0x88 => visit_i64_shr_u, "i64.shr_u", Numeric, ([I64, I64] -> [I64]);
+            I64Rotl = 0x89 => visit_i64_rotl, "i64.rotl", Numeric, ([I64, I64] -> [I64]);
+            I64Rotr = 0x8a => visit_i64_rotr, "i64.rotr", Numeric, ([I64, I64] -> [I64]);
+            F32Abs = 0x8b => visit_f32_abs, "f32.abs", Numeric, ([F32] -> [F32]);
+            F32Neg = 0x8c => visit_f32_neg, "f32.neg", Numeric, ([F32] -> [F32]);
+            F32Ceil = 0x8d => visit_f32_ceil, "f32.ceil", Numeric, ([F32] -> [F32]);
+            F32Floor = 0x8e => visit_f32_floor, "f32.floor", Numeric, ([F32] -> [F32]);
+            F32Trunc = 0x8f => visit_f32_trunc, "f32.trunc", Numeric, ([F32] -> [F32]);
+            F32Nearest = 0x90 => visit_f32_nearest, "f32.nearest", Numeric, ([F32] -> [F32]);
+            F32Sqrt = 0x91 => visit_f32_sqrt, "f32.sqrt", Numeric, ([F32] -> [F32]);
+            F32Add = 0x92 => visit_f32_add, "f32.add", Numeric, ([F32, F32] -> [F32]);
+            F32Sub = 0x93 => visit_f32_sub, "f32.sub", Numeric, ([F32, F32] -> [F32]);
+            F32Mul = 0x94 => visit_f32_mul, "f32.mul", Numeric, ([F32, F32] -> [F32]);
+            F32Div = 0x95 => visit_f32_div, "f32.div", Numeric, ([F32, F32] -> [F32]);
+            F32Min = 0x96 => visit_f32_min, "f32.min", Numeric, ([F32, F32] -> [F32]);
+            F32Max = 0x97 => visit_f32_max, "f32.max", Numeric, ([F32, F32] -> [F32]);
+            F32Copysign = 0x98 => visit_f32_copysign, "f32.copysign", Numeric, ([F32, F32] -> [F32]);
+            F64Abs = 0x99 => visit_f64_abs, "f64.abs", Numeric, ([F64] -> [F64]);
+            F64Neg = 0x9a => visit_f64_neg, "f64.neg", Numeric, ([F64] -> [F64]);
+            F64Ceil = 0x9b => visit_f64_ceil, "f64.ceil", Numeric, ([F64] -> [F64]);
+            F64Floor = 0x9c => visit_f64_floor, "f64.floor", Numeric, ([F64] -> [F64]);
+            F64Trunc = 0x9d => visit_f64_trunc, "f64.trunc", Numeric, ([F64] -> [F64]);
+            F64Nearest = 0x9e => visit_f64_nearest, "f64.nearest", Numeric, ([F64] -> [F64]);
+            F64Sqrt = 0x9f => visit_f64_sqrt, "f64.sqrt", Numeric, ([F64] -> [F64]);
+            F64Add = 0xa0 => visit_f64_add, "f64.add", Numeric, ([F64, F64] -> [F64]);
+            F64Sub = 0xa1 => visit_f64_sub, "f64.sub", Numeric, ([F64, F64] -> [F64]);
+            F64Mul = 0xa2 => visit_f64_mul, "f64.mul", Numeric, ([F64, F64] -> [F64]);
+            F64Div = 0xa3 => visit_f64_div, "f64.div", Numeric, ([F64, F64] -> [F64]);
+            F64Min = 0xa4 => visit_f64_min, "f64.min", Numeric, ([F64, F64] -> [F64]);
+            F64Max = 0xa5 => visit_f64_max, "f64.max", Numeric, ([F64, F64] -> [F64]);
+            F64Copysign = 0xa6 => visit_f64_copysign, "f64.copysign", Numeric, ([F64, F64] -> [F64]);
+
+            // conversions
+            I32WrapI64 = 0xa7 => visit_i32_wrap_i64, "i32.wrap_i64", Conversion, ([I64] -> [I32]);
+            I32TruncSF32 = 0xa8 => visit_i32_trunc_s_f32, "i32.trunc_f32_s", Conversion, ([F32] -> [I32]);
+            I32TruncUF32 = 0xa9 => visit_i32_trunc_u_f32, "i32.trunc_f32_u", Conversion, ([F32] -> [I32]);
+            I32TruncSF64 = 0xaa => visit_i32_trunc_s_f64, "i32.trunc_f64_s", Conversion, ([F64] -> [I32]);
+            I32TruncUF64 = 0xab => visit_i32_trunc_u_f64, "i32.trunc_f64_u", Conversion, ([F64] -> [I32]);
+            I64ExtendSI32 = 0xac => visit_i64_extend_s_i32, "i64.extend_i32_s", Conversion, ([I32] -> [I64]);
+            I64ExtendUI32 = 0xad => visit_i64_extend_u_i32, "i64.extend_i32_u", Conversion, ([I32] -> [I64]);
+            I64TruncSF32 = 0xae => visit_i64_trunc_s_f32, "i64.trunc_f32_s", Conversion, ([F32] -> [I64]);
+            I64TruncUF32 = 0xaf => visit_i64_trunc_u_f32, "i64.trunc_f32_u", Conversion, ([F32] -> [I64]);
+            I64TruncSF64 = 0xb0 => visit_i64_trunc_s_f64, "i64.trunc_f64_s", Conversion, ([F64] -> [I64]);
+            I64TruncUF64 = 0xb1 => visit_i64_trunc_u_f64, "i64.trunc_f64_u", Conversion, ([F64] -> [I64]);
+            F32ConvertSI32 = 0xb2 => visit_f32_convert_s_i32, "f32.convert_i32_s", Conversion, ([I32] -> [F32]);
+            F32ConvertUI32 = 0xb3 => visit_f32_convert_u_i32, "f32.convert_i32_u", Conversion, ([I32] -> [F32]);
+            F32ConvertSI64 = 0xb4 => visit_f32_convert_s_i64, "f32.convert_i64_s", Conversion, ([I64] -> [F32]);
+            F32ConvertUI64 = 0xb5 => visit_f32_convert_u_i64, "f32.convert_i64_u", Conversion, ([I64] -> [F32]);
+            F32DemoteF64 = 0xb6 => visit_f32_demote_f64, "f32.demote_f64", Conversion, ([F64] -> [F32]);
+            F64ConvertSI32 = 0xb7 => visit_f64_convert_s_i32, "f64.convert_i32_s", Conversion, ([I32] -> [F64]);
+            F64ConvertUI32 = 0xb8 => visit_f64_convert_u_i32, "f64.convert_i32_u", Conversion, ([I32] -> [F64]);
+            F64ConvertSI64 = 0xb9 => visit_f64_convert_s_i64, "f64.convert_i64_s", Conversion, ([I64] -> [F64]);
+            F64ConvertUI64 = 0xba => visit_f64_convert_u_i64, "f64.convert_i64_u", Conversion, ([I64] -> [F64]);
+            F64PromoteF32 = 0xbb => visit_f64_promote_f32, "f64.promote_f32", Conversion, ([F32] -> [F64]);
+
+            // reinterpretations
+            I32ReinterpretF32 = 0xbc => visit_i32_reinterpret_f32, "i32.reinterpret_f32", Conversion, ([F32] -> [I32]);
+            I64ReinterpretF64 = 0xbd => visit_i64_reinterpret_f64, "i64.reinterpret_f64", Conversion, ([F64] -> [I64]);
+            F32ReinterpretI32 = 0xbe => visit_f32_reinterpret_i32, "f32.reinterpret_i32", Conversion, ([I32] -> [F32]);
+            F64ReinterpretI64 = 0xbf => visit_f64_reinterpret_i64, "f64.reinterpret_i64", Conversion, ([I64] -> [F64]);
+        }
+    }
+}
+
+/// The type an immediate of kind `$kind` is decoded to, borrowing the input
+/// for `$lt`.
+macro_rules! imm_type {
+    ($lt:tt, BlockType) => { InlineSignatureType };
+    ($lt:tt, LabelIndex) => { u32 };
+    ($lt:tt, LabelTable) => { BrTable<$lt> };
+    ($lt:tt, FunctionIndex) => { u32 };
+    ($lt:tt, TypeIndex) => { u32 };
+    ($lt:tt, LocalIndex) => { u32 };
+    ($lt:tt, GlobalIndex) => { u32 };
+    ($lt:tt, Memory) => { MemoryImmediate };
+    ($lt:tt, Reserved) => { bool };
+    ($lt:tt, I32) => { i32 };
+    ($lt:tt, I64) => { i64 };
+    ($lt:tt, F32) => { f32 };
+    ($lt:tt, F64) => { f64 };
+}
+
+/// Reads an immediate of kind `$kind` from `$iter`, a `&mut &[u8]`, with
+/// the block types `$features` enables.
+macro_rules! imm_read {
+    (BlockType, $iter:expr, $features:expr) => {
+        read_block_type($iter, $features)
+    };
+    (LabelIndex, $iter:expr, $features:expr) => {
+        read_var_u32($iter)
+    };
+    (LabelTable, $iter:expr, $features:expr) => {
+        BrTable::read($iter)
+    };
+    (FunctionIndex, $iter:expr, $features:expr) => {
+        read_var_u32($iter)
+    };
+    (TypeIndex, $iter:expr, $features:expr) => {
+        read_var_u32($iter)
+    };
+    (LocalIndex, $iter:expr, $features:expr) => {
+        read_var_u32($iter)
+    };
+    (GlobalIndex, $iter:expr, $features:expr) => {
+        read_var_u32($iter)
+    };
+    (Memory, $iter:expr, $features:expr) => {
+        MemoryImmediate::read($iter)
+    };
+    // The reserved byte of `call_indirect`, `memory.size` and
+    // `memory.grow`.
+    (Reserved, $iter:expr, $features:expr) => {
+        read_var_u32($iter).map(|reserved| reserved != 0)
+    };
+    (I32, $iter:expr, $features:expr) => {
+        read_var_s32($iter)
+    };
+    (I64, $iter:expr, $features:expr) => {
+        read_var_s64($iter)
+    };
+    (F32, $iter:expr, $features:expr) => {
+        read_f32($iter)
+    };
+    (F64, $iter:expr, $features:expr) => {
+        read_f64($iter)
+    };
+}
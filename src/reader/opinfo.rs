@@ -0,0 +1,135 @@
+//! Static information about each opcode.
+//!
+//! `OpCode::info` returns the mnemonic used by the text format, the kinds
+//! of immediates that follow the opcode, a rough category, and how the
+//! operator changes the operand stack when that is known without looking
+//! at the rest of the module.
+
+use super::ValueType;
+use super::bytecode::{OpCode, Op, MemoryImmediate};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OpCategory {
+    /// Blocks, branches, calls and returns.
+    Control,
+    /// `drop` and `select`.
+    Parametric,
+    /// Local and global accesses.
+    Variable,
+    /// Loads, stores and memory size operators.
+    Memory,
+    /// Constants, comparisons and arithmetic.
+    Numeric,
+    /// Conversions between value types, including reinterpretations.
+    Conversion,
+}
+
+/// The kind of an immediate operand, in the order they are encoded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImmediateKind {
+    /// An `InlineSignatureType`.
+    BlockType,
+    /// A relative branch depth.
+    LabelIndex,
+    /// A `BrTable`: a vector of branch depths and a default.
+    LabelTable,
+    FunctionIndex,
+    TypeIndex,
+    LocalIndex,
+    GlobalIndex,
+    /// A `MemoryImmediate`: alignment flags and an offset.
+    Memory,
+    /// A reserved varuint that has to be zero.
+    Reserved,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StackEffect {
+    /// Pops `pops`, with the top of the stack last, and pushes `pushes`.
+    Static {
+        pops: &'static [ValueType],
+        pushes: &'static [ValueType],
+    },
+    /// The effect depends on the module or on the surrounding code: the
+    /// signature of a call or block, the type of a local or global, the
+    /// operands of `drop` and `select`, or the unreachable code after a
+    /// branch.
+    Dynamic,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OpInfo {
+    pub mnemonic: &'static str,
+    pub category: OpCategory,
+    pub immediates: &'static [ImmediateKind],
+    pub stack: StackEffect,
+}
+
+macro_rules! stack_effect {
+    (dynamic) => {
+        StackEffect::Dynamic
+    };
+    (([$($pop:ident),*] -> [$($push:ident),*])) => {
+        StackEffect::Static {
+            pops: &[$(ValueType::$pop),*],
+            pushes: &[$(ValueType::$push),*],
+        }
+    };
+}
+
+macro_rules! opinfo {
+    ($($(#[$attr:meta])* $name:ident $(($($arg:ident: $kind:ident),+))* = $code:literal
+       => $visit:ident, $mnemonic:literal, $category:ident, $effect:tt;)+) => {
+        impl OpCode {
+            pub fn info(self) -> &'static OpInfo {
+                match self {$(
+                    OpCode::$name => &OpInfo {
+                        mnemonic: $mnemonic,
+                        category: OpCategory::$category,
+                        immediates: &[$($(ImmediateKind::$kind),+)*],
+                        stack: stack_effect!($effect),
+                    }
+                ),+}
+            }
+        }
+    }
+}
+
+for_each_operator!(opinfo);
+
+impl OpCode {
+    /// The name of the operator in the text format, such as `i32.add`.
+    pub fn mnemonic(self) -> &'static str {
+        self.info().mnemonic
+    }
+}
+
+impl<'a> Op<'a> {
+    pub fn info(&self) -> &'static OpInfo {
+        self.code().info()
+    }
+
+    /// The natural alignment of a load or store, as the exponent used by the
+    /// alignment flags, together with its immediate.
+    pub fn natural_alignment(&self) -> Option<(u32, &MemoryImmediate)> {
+        Some(match *self {
+            Op::I32Load8S(ref memarg) | Op::I32Load8U(ref memarg) |
+            Op::I64Load8S(ref memarg) | Op::I64Load8U(ref memarg) |
+            Op::I32Store8(ref memarg) | Op::I64Store8(ref memarg) => (0, memarg),
+            Op::I32Load16S(ref memarg) | Op::I32Load16U(ref memarg) |
+            Op::I64Load16S(ref memarg) | Op::I64Load16U(ref memarg) |
+            Op::I32Store16(ref memarg) | Op::I64Store16(ref memarg) => (1, memarg),
+            Op::I32Load(ref memarg) | Op::F32Load(ref memarg) |
+            Op::I64Load32S(ref memarg) | Op::I64Load32U(ref memarg) |
+            Op::I32Store(ref memarg) | Op::F32Store(ref memarg) |
+            Op::I64Store32(ref memarg) => (2, memarg),
+            Op::I64Load(ref memarg) | Op::F64Load(ref memarg) |
+            Op::I64Store(ref memarg) | Op::F64Store(ref memarg) => (3, memarg),
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,34 @@
+//! Processing function bodies on a thread pool.
+//!
+//! The drivers here split a code section with `CodeSection::bodies` and
+//! run a closure over the bodies on rayon's thread pool. Results come back
+//! in function order. To use a pool other than the global one, call them
+//! inside `ThreadPool::install`.
+
+use super::*;
+use rayon::prelude::*;
+
+impl<'a> CodeSection<'a> {
+    /// Runs `f` on every function body in parallel and collects the
+    /// results in order. `f` is given the index of the body within the
+    /// section.
+    pub fn par_map<T, F>(&self, f: F) -> Result<Vec<T>, Error>
+        where T: Send,
+              F: Fn(usize, FunctionBody<'a>) -> T + Sync + Send
+    {
+        let bodies = self.bodies()?;
+        Ok(bodies.into_par_iter().enumerate().map(|(index, body)| f(index, body)).collect())
+    }
+
+    /// Like `par_map`, for closures that can fail. Returns the error of the
+    /// first failing body in section order, so the result does not depend
+    /// on how the work was scheduled.
+    pub fn par_try_map<T, E, F>(&self, f: F) -> Result<Vec<T>, E>
+        where T: Send,
+              E: Send + From<Error>,
+              F: Fn(usize, FunctionBody<'a>) -> Result<T, E> + Sync + Send
+    {
+        let results = self.par_map(f)?;
+        results.into_iter().collect()
+    }
+}
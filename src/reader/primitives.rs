@@ -0,0 +1,440 @@
+//! Slice-based readers for the integer and float encodings.
+//!
+//! Each function reads from the front of `iter` and advances it past what
+//! was read. Running out of input is reported as `Error::UnexpectedEof`.
+
+use super::Error;
+
+#[inline]
+pub fn read_u8(iter: &mut &[u8]) -> Result<u8, Error> {
+    match iter.split_first() {
+        Some((&byte, rest)) => {
+            *iter = rest;
+            Ok(byte)
+        },
+        None => Err(Error::UnexpectedEof),
+    }
+}
+
+/// Reads `len` bytes, borrowing them from the input.
+pub fn read_bytes<'a>(iter: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if len > iter.len() {
+        return Err(Error::UnexpectedEof)
+    }
+    let (bytes, rest) = iter.split_at(len);
+    *iter = rest;
+    Ok(bytes)
+}
+
+pub fn read_u32(iter: &mut &[u8]) -> Result<u32, Error> {
+    let bytes = read_bytes(iter, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn read_u64(iter: &mut &[u8]) -> Result<u64, Error> {
+    let bytes = read_bytes(iter, 8)?;
+    let mut value = [0; 8];
+    value.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(value))
+}
+
+pub fn read_f32(iter: &mut &[u8]) -> Result<f32, Error> {
+    read_u32(iter).map(f32::from_bits)
+}
+
+pub fn read_f64(iter: &mut &[u8]) -> Result<f64, Error> {
+    read_u64(iter).map(f64::from_bits)
+}
+
+/// Reads an unsigned LEB128 integer of up to 64 bits.
+///
+/// This is lenient about the encoding, and the value has to be narrowed by
+/// the caller. Fields of the binary format are read with the checked
+/// readers below.
+pub fn read_varuint(iter: &mut &[u8]) -> Result<u64, Error> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = read_u8(iter)?;
+        if shift == 63 && byte > 1 {
+            return Err(Error::Leb128("integer is too large"))
+        }
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result)
+        }
+        shift += 7;
+    }
+}
+
+/// Reads a signed LEB128 integer of up to 64 bits. Lenient, like
+/// `read_varuint`.
+pub fn read_varint(iter: &mut &[u8]) -> Result<i64, Error> {
+    let mut result = 0i64;
+    let mut shift = 0;
+    loop {
+        let byte = read_u8(iter)?;
+        if shift == 63 && byte != 0 && byte != 0x7f {
+            return Err(Error::Leb128("integer is too large"))
+        }
+        result |= ((byte & 0x7f) as i64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if shift < 64 && byte & 0x40 != 0 {
+                result |= !0 << shift;
+            }
+            return Ok(result)
+        }
+    }
+}
+
+/// The integer types of the binary format that are encoded as LEB128.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Leb128Kind {
+    U32,
+    S32,
+    /// Signed 33 bits, used for block types.
+    S33,
+    S64,
+}
+
+impl Leb128Kind {
+    fn bits(self) -> u32 {
+        match self {
+            Leb128Kind::U32 | Leb128Kind::S32 => 32,
+            Leb128Kind::S33 => 33,
+            Leb128Kind::S64 => 64,
+        }
+    }
+
+    fn signed(self) -> bool {
+        self != Leb128Kind::U32
+    }
+
+    /// The longest encoding the spec allows.
+    pub fn max_len(self) -> usize {
+        (self.bits() as usize).div_ceil(7)
+    }
+}
+
+/// A LEB128 integer read by `read_leb128`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Leb128 {
+    /// The value, sign extended for the signed kinds.
+    pub value: i64,
+    /// The number of bytes the integer took up.
+    pub len: usize,
+    /// Whether the encoding is the shortest one for the value. Longer
+    /// encodings are valid as long as they stay within `Leb128Kind::max_len`,
+    /// and linkers leave them behind when they patch in values.
+    pub minimal: bool,
+}
+
+/// Reads a LEB128 integer of the given kind, following the rules of the
+/// spec: the encoding may be at most `kind.max_len()` bytes long, and the
+/// bits of the last byte beyond the width of the type have to be zero, or,
+/// for signed kinds, copies of the sign bit.
+#[inline]
+pub fn read_leb128(iter: &mut &[u8], kind: Leb128Kind) -> Result<Leb128, Error> {
+    let bits = kind.bits();
+    let mut result = 0i64;
+    let mut shift = 0;
+    let mut len = 0;
+    let mut prev = 0u8;
+    loop {
+        let byte = read_u8(iter)?;
+        len += 1;
+        if len == kind.max_len() {
+            if byte & 0x80 != 0 {
+                return Err(Error::Leb128("integer representation is too long"))
+            }
+            let used = bits - shift;
+            let unused = if kind.signed() {
+                // The sign bit and everything above it.
+                (0x7f << (used - 1)) & 0x7f
+            } else {
+                (0x7f << used) & 0x7f
+            };
+            let high = byte & unused;
+            if high != 0 && !(kind.signed() && high == unused) {
+                return Err(Error::Leb128("integer is too large"))
+            }
+        }
+        result |= ((byte & 0x7f) as i64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if kind.signed() && shift < 64 && byte & 0x40 != 0 {
+                result |= !0 << shift;
+            }
+            let minimal = len == 1 || if kind.signed() {
+                !(byte == 0 && prev & 0x40 == 0 || byte == 0x7f && prev & 0x40 != 0)
+            } else {
+                byte != 0
+            };
+            return Ok(Leb128 {
+                value: result,
+                len,
+                minimal,
+            })
+        }
+        prev = byte;
+    }
+}
+
+/// Takes the first byte of `iter` if it is a complete LEB128 integer by
+/// itself. Most indices and immediates are, so the readers below try this
+/// before falling back to `read_leb128`.
+#[inline]
+fn read_single_byte(iter: &mut &[u8]) -> Option<u8> {
+    match iter.split_first() {
+        Some((&byte, rest)) if byte & 0x80 == 0 => {
+            *iter = rest;
+            Some(byte)
+        },
+        _ => None,
+    }
+}
+
+/// Sign extends a single byte LEB128 integer.
+#[inline]
+fn sign_extend(byte: u8) -> i64 {
+    ((byte << 1) as i8 >> 1) as i64
+}
+
+/// Reads an unsigned 32-bit LEB128 integer, as used for indices, counts
+/// and sizes.
+#[inline]
+pub fn read_var_u32(iter: &mut &[u8]) -> Result<u32, Error> {
+    match read_single_byte(iter) {
+        Some(byte) => Ok(byte as u32),
+        None => read_var_u32_slow(iter),
+    }
+}
+
+/// `read_leb128` for `U32`, without the bookkeeping for the encoding.
+#[inline]
+fn read_var_u32_slow(iter: &mut &[u8]) -> Result<u32, Error> {
+    let mut result = 0;
+    for (i, &byte) in iter.iter().take(5).enumerate() {
+        if i == 4 && byte & 0xf0 != 0 {
+            return Err(Error::Leb128(if byte & 0x80 != 0 {
+                "integer representation is too long"
+            } else {
+                "integer is too large"
+            }))
+        }
+        result |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            *iter = &iter[i + 1..];
+            return Ok(result)
+        }
+    }
+    Err(Error::UnexpectedEof)
+}
+
+macro_rules! read_signed_slow {
+    ($name:ident, $ty:ty, $bits:expr, $max_len:expr) => {
+        /// `read_leb128` for a signed kind, without the bookkeeping for the
+        /// encoding.
+        #[inline]
+        fn $name(iter: &mut &[u8]) -> Result<$ty, Error> {
+            let max_len = $max_len;
+            let mut result: $ty = 0;
+            for (i, &byte) in iter.iter().take(max_len).enumerate() {
+                if i == max_len - 1 {
+                    // The bits beyond the width have to be copies of the
+                    // sign bit.
+                    let unused = (0x7f << ($bits - 7 * i - 1)) & 0x7f;
+                    let high = byte & unused;
+                    if byte & 0x80 != 0 || high != 0 && high != unused {
+                        return Err(Error::Leb128(if byte & 0x80 != 0 {
+                            "integer representation is too long"
+                        } else {
+                            "integer is too large"
+                        }))
+                    }
+                }
+                result |= ((byte & 0x7f) as $ty) << (7 * i);
+                if byte & 0x80 == 0 {
+                    *iter = &iter[i + 1..];
+                    let shift = 7 * (i + 1);
+                    if shift < $bits {
+                        result = result << ($bits - shift) >> ($bits - shift);
+                    }
+                    return Ok(result)
+                }
+            }
+            Err(Error::UnexpectedEof)
+        }
+    }
+}
+
+read_signed_slow!(read_var_s32_slow, i32, 32, 5);
+read_signed_slow!(read_var_s64_slow, i64, 64, 10);
+
+#[inline]
+pub fn read_var_s32(iter: &mut &[u8]) -> Result<i32, Error> {
+    match read_single_byte(iter) {
+        Some(byte) => Ok(sign_extend(byte) as i32),
+        None => read_var_s32_slow(iter),
+    }
+}
+
+#[inline]
+pub fn read_var_s33(iter: &mut &[u8]) -> Result<i64, Error> {
+    match read_single_byte(iter) {
+        Some(byte) => Ok(sign_extend(byte)),
+        None => read_leb128(iter, Leb128Kind::S33).map(|leb| leb.value),
+    }
+}
+
+#[inline]
+pub fn read_var_s64(iter: &mut &[u8]) -> Result<i64, Error> {
+    match read_single_byte(iter) {
+        Some(byte) => Ok(sign_extend(byte)),
+        None => read_var_s64_slow(iter),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leb(bytes: &[u8], kind: Leb128Kind) -> Result<Leb128, Error> {
+        let mut iter = bytes;
+        let leb = read_leb128(&mut iter, kind)?;
+        assert_eq!(leb.len, bytes.len() - iter.len());
+        Ok(leb)
+    }
+
+    fn value(bytes: &[u8], kind: Leb128Kind) -> i64 {
+        leb(bytes, kind).unwrap().value
+    }
+
+    fn too_long(bytes: &[u8], kind: Leb128Kind) -> bool {
+        matches!(leb(bytes, kind), Err(Error::Leb128("integer representation is too long")))
+    }
+
+    fn too_large(bytes: &[u8], kind: Leb128Kind) -> bool {
+        matches!(leb(bytes, kind), Err(Error::Leb128("integer is too large")))
+    }
+
+    #[test]
+    fn u32_lengths() {
+        assert_eq!(value(&[0xff, 0xff, 0xff, 0xff, 0x0f], Leb128Kind::U32), u32::MAX as i64);
+        assert_eq!(value(&[0x80, 0x80, 0x80, 0x80, 0x00], Leb128Kind::U32), 0);
+        assert!(too_long(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], Leb128Kind::U32));
+        match leb(&[0x80, 0x80], Leb128Kind::U32) {
+            Err(Error::UnexpectedEof) => (),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn u32_unused_bits() {
+        assert!(too_large(&[0xff, 0xff, 0xff, 0xff, 0x1f], Leb128Kind::U32));
+        assert!(too_large(&[0x80, 0x80, 0x80, 0x80, 0x70], Leb128Kind::U32));
+    }
+
+    #[test]
+    fn s32_lengths() {
+        assert_eq!(value(&[0xff, 0xff, 0xff, 0xff, 0x07], Leb128Kind::S32), i32::MAX as i64);
+        assert_eq!(value(&[0x80, 0x80, 0x80, 0x80, 0x78], Leb128Kind::S32), i32::MIN as i64);
+        assert_eq!(value(&[0xff, 0xff, 0xff, 0xff, 0x7f], Leb128Kind::S32), -1);
+        assert!(too_long(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x7f], Leb128Kind::S32));
+    }
+
+    #[test]
+    fn s32_unused_bits() {
+        // The sign bit is set, but the bits above it are not.
+        assert!(too_large(&[0xff, 0xff, 0xff, 0xff, 0x0f], Leb128Kind::S32));
+        // The sign bit is clear, but a bit above it is set.
+        assert!(too_large(&[0x80, 0x80, 0x80, 0x80, 0x10], Leb128Kind::S32));
+        assert!(too_large(&[0xff, 0xff, 0xff, 0xff, 0x4f], Leb128Kind::S32));
+    }
+
+    #[test]
+    fn s33_lengths_and_unused_bits() {
+        assert_eq!(value(&[0xff, 0xff, 0xff, 0xff, 0x0f], Leb128Kind::S33), u32::MAX as i64);
+        assert_eq!(value(&[0x80, 0x80, 0x80, 0x80, 0x70], Leb128Kind::S33), -(1 << 32));
+        assert_eq!(value(&[0xc0, 0xff, 0xff, 0xff, 0x7f], Leb128Kind::S33), -64);
+        assert!(too_long(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], Leb128Kind::S33));
+        assert!(too_large(&[0x80, 0x80, 0x80, 0x80, 0x10], Leb128Kind::S33));
+        assert!(too_large(&[0x80, 0x80, 0x80, 0x80, 0x60], Leb128Kind::S33));
+    }
+
+    #[test]
+    fn s64_lengths_and_unused_bits() {
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+        let min = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f];
+        assert_eq!(value(&max, Leb128Kind::S64), i64::MAX);
+        assert_eq!(value(&min, Leb128Kind::S64), i64::MIN);
+        assert!(too_long(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00], Leb128Kind::S64));
+        assert!(too_large(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], Leb128Kind::S64));
+        assert!(too_large(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7e], Leb128Kind::S64));
+    }
+
+    #[test]
+    fn minimal() {
+        let minimal = |bytes: &[u8], kind| leb(bytes, kind).unwrap().minimal;
+        assert!(minimal(&[0x00], Leb128Kind::U32));
+        assert!(!minimal(&[0x80, 0x00], Leb128Kind::U32));
+        assert!(!minimal(&[0x82, 0x80, 0x80, 0x80, 0x00], Leb128Kind::U32));
+        assert!(minimal(&[0x80, 0x01], Leb128Kind::U32));
+        assert!(minimal(&[0x7f], Leb128Kind::S32));
+        assert!(!minimal(&[0xff, 0x7f], Leb128Kind::S32));
+        // 64 and -128 need the second byte for the sign.
+        assert!(minimal(&[0xc0, 0x00], Leb128Kind::S32));
+        assert!(minimal(&[0x80, 0x7f], Leb128Kind::S64));
+        assert!(!minimal(&[0xbf, 0x00], Leb128Kind::S33));
+    }
+
+    #[test]
+    fn single_byte_sign_extension() {
+        for byte in 0..0x80u8 {
+            let expected = value(&[byte], Leb128Kind::S64);
+            assert_eq!(read_var_s32(&mut &[byte][..]).unwrap() as i64, expected);
+            assert_eq!(read_var_s33(&mut &[byte][..]).unwrap(), expected);
+            assert_eq!(read_var_s64(&mut &[byte][..]).unwrap(), expected);
+            assert_eq!(read_var_u32(&mut &[byte][..]).unwrap(), byte as u32);
+        }
+        assert_eq!(read_var_s32(&mut &[0x3f][..]).unwrap(), 63);
+        assert_eq!(read_var_s32(&mut &[0x40][..]).unwrap(), -64);
+        assert_eq!(read_var_s64(&mut &[0x7f][..]).unwrap(), -1);
+    }
+
+    #[test]
+    fn fast_paths_match_read_leb128() {
+        let cases: &[&[u8]] = &[
+            &[0x80, 0x01],
+            &[0xe5, 0x8e, 0x26],
+            &[0xc0, 0xbb, 0x78],
+            &[0xff, 0xff, 0xff, 0xff, 0x0f],
+            &[0x80, 0x80, 0x80, 0x80, 0x00],
+            &[0x80, 0x80, 0x80, 0x80, 0x10],
+            &[0x80, 0x80, 0x80, 0x80, 0x78],
+            &[0xff, 0xff, 0xff, 0xff, 0x07],
+            &[0xff, 0xff, 0xff, 0xff, 0x7f],
+            &[0xff, 0xff, 0xff, 0xff, 0x4f],
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            &[0x80, 0x80],
+        ];
+        // The errors have to match as well as the values.
+        let same = |a: Result<i64, Error>, b: Result<i64, Error>| format!("{:?}", a) == format!("{:?}", b);
+        for &bytes in cases {
+            let mut iter = bytes;
+            let fast = read_var_u32(&mut iter).map(|value| value as i64);
+            assert!(same(fast, leb(bytes, Leb128Kind::U32).map(|leb| leb.value as u32 as i64)), "{:?}", bytes);
+            let mut iter = bytes;
+            let fast = read_var_s32(&mut iter).map(|value| value as i64);
+            assert!(same(fast, leb(bytes, Leb128Kind::S32).map(|leb| leb.value as i32 as i64)), "{:?}", bytes);
+            let mut iter = bytes;
+            let fast = read_var_s64(&mut iter);
+            assert!(same(fast, leb(bytes, Leb128Kind::S64).map(|leb| leb.value)), "{:?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::*;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A module read from a seekable stream, such as a file, instead of from a
+/// slice held in memory.
+///
+/// Construction only walks the section headers and records where each
+/// section lives. Payloads are read on demand into a caller-supplied
+/// buffer, and then decoded with the same `Section` and iterator types as
+/// `Module` uses.
+pub struct SeekModule<R> {
+    reader: R,
+    pub version: u32,
+    pub sections: Vec<SectionInfo>,
+    pub config: ParserConfig,
+}
+
+/// Where a section is located in the stream.
+#[derive(Clone, Debug)]
+pub struct SectionInfo {
+    pub id: SectionType,
+    pub name: String,
+    /// Offset of the section header, from the start of the stream.
+    pub offset: u64,
+    /// Size of the section, including its header.
+    pub size: u64,
+    /// Offset of the section payload, after the header and name.
+    pub payload_offset: u64,
+    pub payload_size: u64,
+}
+
+/// Reads the code section one function body at a time.
+pub struct CodeReader<'r, R: 'r> {
+    reader: &'r mut R,
+    remaining: u32,
+    /// The number of bytes of the section payload that have not been read.
+    left: u64,
+    config: ParserConfig,
+}
+
+/// Reads an unsigned LEB128 integer from `reader`, appending its raw bytes
+/// to `buf`.
+fn read_var_u32_into<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> Result<u32, Error> {
+    let start = buf.len();
+    loop {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        let byte = byte[0];
+        buf.push(byte);
+        if byte & 0x80 == 0 || buf.len() - start >= Leb128Kind::U32.max_len() {
+            break
+        }
+    }
+    let mut iter = &buf[start..];
+    read_var_u32(&mut iter)
+}
+
+impl<R: Read + Seek> SeekModule<R> {
+    pub fn new(reader: R) -> Result<SeekModule<R>, Error> {
+        SeekModule::with_config(reader, ParserConfig::default())
+    }
+
+    pub fn with_config(mut reader: R, config: ParserConfig) -> Result<SeekModule<R>, Error> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).map_err(|_| Error::NotWasm)?;
+        let version = Module::new(&header)?.version;
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut sections = vec![];
+        let mut offset = start;
+        let mut header = vec![];
+        while offset < end {
+            ParserConfig::check("number of sections", sections.len() + 1, config.max_sections)?;
+            header.clear();
+            let id = read_var_u32_into(&mut reader, &mut header)?;
+            let plen = read_var_u32_into(&mut reader, &mut header)?;
+            let header_len = header.len() as u64;
+            let name = if id == 0 {
+                let nlen = read_var_u32_into(&mut reader, &mut header)?;
+                ParserConfig::check("section name length", nlen as usize, config.max_name_len)?;
+                let mut name = vec![0; nlen as usize];
+                reader.read_exact(&mut name)?;
+                String::from_utf8(name).map_err(|e| e.utf8_error())?
+            } else {
+                String::new()
+            };
+            let payload_offset = reader.stream_position()?;
+            let size = header_len + plen as u64;
+            if offset + size > end || payload_offset > offset + size {
+                return Err(Error::UnexpectedEof)
+            }
+            let id = SectionType::from_int(id as u8).ok_or(Error::UnknownVariant("section type"))?;
+            config.features.check_section(id).map_err(|err| err.offset_by(offset as usize))?;
+            sections.push(SectionInfo {
+                id,
+                name,
+                offset,
+                size,
+                payload_offset,
+                payload_size: offset + size - payload_offset,
+            });
+            offset += size;
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+
+        Ok(SeekModule {
+            reader,
+            version,
+            sections,
+            config,
+        })
+    }
+
+    /// Returns the index of the first section with the given id.
+    pub fn find_section(&self, id: SectionType) -> Option<usize> {
+        self.sections.iter().position(|section| section.id == id)
+    }
+
+    /// Reads the section at `index` into `buf` and decodes its header.
+    pub fn load_section<'b>(&mut self, index: usize, buf: &'b mut Vec<u8>) -> Result<Section<'b>, Error> {
+        let info = &self.sections[index];
+        buf.clear();
+        buf.resize(info.size as usize, 0);
+        self.reader.seek(SeekFrom::Start(info.offset))?;
+        self.reader.read_exact(buf)?;
+        match SectionsIterator::new(buf, self.config).next() {
+            Some(section) => section,
+            None => Err(Error::UnexpectedEof),
+        }
+    }
+
+    /// Starts reading the code section at `index` without loading it,
+    /// so that function bodies can be read one at a time.
+    pub fn code_reader(&mut self, index: usize) -> Result<CodeReader<'_, R>, Error> {
+        let info = &self.sections[index];
+        if info.id != SectionType::Code {
+            return Err(Error::UnknownVariant("code section"))
+        }
+        self.reader.seek(SeekFrom::Start(info.payload_offset))?;
+        let mut header = vec![];
+        let count = read_var_u32_into(&mut self.reader, &mut header)?;
+        ParserConfig::check("number of functions", count as usize, self.config.max_functions)?;
+        if header.len() as u64 > info.payload_size {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(CodeReader {
+            reader: &mut self.reader,
+            remaining: count,
+            left: info.payload_size - header.len() as u64,
+            config: self.config,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<'r, R: Read> CodeReader<'r, R> {
+    /// The number of function bodies that have not been read yet.
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+
+    /// Reads the next function body into `buf`. A body that does not fit
+    /// in the rest of the section is `Error::UnexpectedEof`, and nothing
+    /// after the section is read.
+    pub fn next_body<'b>(&mut self, buf: &'b mut Vec<u8>) -> Option<Result<FunctionBody<'b>, Error>> {
+        if self.remaining == 0 {
+            return None
+        }
+        self.remaining -= 1;
+        buf.clear();
+        let body_size = try_opt!(read_var_u32_into(self.reader, buf)) as usize;
+        try_opt!(ParserConfig::check("function body size", body_size, self.config.max_body_size));
+        let start = buf.len();
+        let size = (start + body_size) as u64;
+        if size > self.left {
+            self.remaining = 0;
+            return Some(Err(Error::UnexpectedEof))
+        }
+        self.left -= size;
+        buf.resize(start + body_size, 0);
+        try_opt!(self.reader.read_exact(&mut buf[start..]));
+        let code = CodeSection {
+            count: 1,
+            entries_raw: buf,
+            config: self.config,
+        };
+        code.entries().next()
+    }
+}
@@ -0,0 +1,221 @@
+use super::*;
+use alloc::vec::Vec;
+
+/// A push-based parser for modules that arrive in pieces, such as from a
+/// socket.
+///
+/// Bytes are handed over with `push` as they come in, and `next` yields
+/// each section as soon as all of it has arrived. The code section is the
+/// exception: it is announced by `Payload::CodeSectionStart` as soon as its
+/// header is in, and each function body is then yielded on its own, so that
+/// compilation can start before the rest of the module has been received.
+pub struct StreamParser {
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    state: State,
+    sections: usize,
+    config: ParserConfig,
+}
+
+enum State {
+    Header,
+    Sections,
+    Code {
+        remaining: u32,
+        size: usize,
+    },
+    Done,
+}
+
+pub enum Payload<'a> {
+    /// At least this many more bytes have to be pushed before the next
+    /// payload can be decoded.
+    NeedMoreData(usize),
+    /// The module header, with its version.
+    Version(u32),
+    /// Any complete section other than the code section.
+    Section(Section<'a>),
+    /// The header of the code section. `count` function bodies follow,
+    /// spanning `size` bytes.
+    CodeSectionStart {
+        count: u32,
+        size: usize,
+    },
+    FunctionBody(FunctionBody<'a>),
+    /// The whole module has been parsed.
+    End,
+}
+
+/// Decodes an unsigned 32-bit LEB128 integer from the start of `data`, returning
+/// the value and its encoded length, or `None` if `data` ends before the
+/// integer does.
+fn peek_varuint(data: &[u8]) -> Option<Result<(u64, usize), Error>> {
+    let max_len = Leb128Kind::U32.max_len();
+    match data.iter().take(max_len).position(|b| b & 0x80 == 0) {
+        None if data.len() < max_len => None,
+        _ => {
+            let mut iter = data;
+            Some(match read_var_u32(&mut iter) {
+                Ok(value) => Ok((value as u64, data.len() - iter.len())),
+                Err(e) => Err(e),
+            })
+        }
+    }
+}
+
+macro_rules! try_peek {
+    ($parser:expr, $data:expr) => {
+        match peek_varuint($data) {
+            Some(Ok(x)) => x,
+            Some(Err(e)) => return Err(e),
+            None => return $parser.need(1),
+        }
+    }
+}
+
+impl Default for StreamParser {
+    fn default() -> StreamParser {
+        StreamParser::new()
+    }
+}
+
+impl StreamParser {
+    pub fn new() -> StreamParser {
+        StreamParser::with_config(ParserConfig::default())
+    }
+
+    pub fn with_config(config: ParserConfig) -> StreamParser {
+        StreamParser {
+            buf: vec![],
+            pos: 0,
+            eof: false,
+            state: State::Header,
+            sections: 0,
+            config,
+        }
+    }
+
+    /// Appends the next chunk of the module.
+    pub fn push(&mut self, data: &[u8]) {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Signals that no more data will be pushed, so that a truncated module
+    /// is reported as an error rather than as `NeedMoreData`.
+    pub fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    fn need(&self, count: usize) -> Result<Payload<'static>, Error> {
+        if self.eof {
+            Err(Error::UnexpectedEof)
+        } else {
+            Ok(Payload::NeedMoreData(count))
+        }
+    }
+
+    /// Decodes the next payload from the data pushed so far.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Payload<'_>, Error> {
+        let avail = self.buf.len() - self.pos;
+        match self.state {
+            State::Header => {
+                if avail < 8 {
+                    return self.need(8 - avail)
+                }
+                let version = Module::new(&self.buf[self.pos..self.pos + 8])?.version;
+                self.pos += 8;
+                self.state = State::Sections;
+                Ok(Payload::Version(version))
+            },
+            State::Sections => {
+                if avail == 0 {
+                    if self.eof {
+                        self.state = State::Done;
+                        return Ok(Payload::End)
+                    }
+                    return self.need(1)
+                }
+                let (id, id_len) = try_peek!(self, &self.buf[self.pos..]);
+                let (plen, plen_len) = try_peek!(self, &self.buf[self.pos + id_len..]);
+                let header_len = id_len + plen_len;
+                ParserConfig::check(
+                    "number of sections", self.sections + 1, self.config.max_sections
+                )?;
+                if id == SectionType::Code as u64 {
+                    let (count, count_len) = try_peek!(self, &self.buf[self.pos + header_len..]);
+                    if count_len as u64 > plen {
+                        return Err(Error::Malformed("code section is too short for its entry count"))
+                    }
+                    ParserConfig::check(
+                        "number of functions", count as usize, self.config.max_functions
+                    )?;
+                    self.sections += 1;
+                    self.pos += header_len + count_len;
+                    let size = plen as usize - count_len;
+                    self.state = State::Code {
+                        remaining: count as u32,
+                        size,
+                    };
+                    return Ok(Payload::CodeSectionStart {
+                        count: count as u32,
+                        size,
+                    })
+                }
+                let total = header_len as u64 + plen;
+                if total > avail as u64 {
+                    return self.need((total - avail as u64) as usize)
+                }
+                let start = self.pos;
+                self.sections += 1;
+                self.pos += total as usize;
+                let data = &self.buf[start..self.pos];
+                match SectionsIterator::new(data, self.config).next() {
+                    Some(section) => section.map(Payload::Section),
+                    None => Err(Error::Malformed("section header is larger than the section")),
+                }
+            },
+            State::Code { remaining, size } => {
+                if remaining == 0 {
+                    if size != 0 {
+                        return Err(Error::Malformed("code section is larger than its function bodies"))
+                    }
+                    self.state = State::Sections;
+                    return self.next()
+                }
+                let (body_size, len) = try_peek!(self, &self.buf[self.pos..]);
+                ParserConfig::check(
+                    "function body size", body_size as usize, self.config.max_body_size
+                )?;
+                let total = len + body_size as usize;
+                if total > size {
+                    return Err(Error::Malformed("function body is larger than the code section"))
+                }
+                if total > avail {
+                    return self.need(total - avail)
+                }
+                let start = self.pos;
+                self.pos += total;
+                self.state = State::Code {
+                    remaining: remaining - 1,
+                    size: size - total,
+                };
+                let code = CodeSection {
+                    count: 1,
+                    entries_raw: &self.buf[start..self.pos],
+                    config: self.config,
+                };
+                match code.entries().next() {
+                    Some(body) => body.map(Payload::FunctionBody),
+                    None => unreachable!(),
+                }
+            },
+            State::Done => Ok(Payload::End),
+        }
+    }
+}
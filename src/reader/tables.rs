@@ -1,14 +1,17 @@
 use super::*;
-use std::io::Read;
 
 pub struct TableSection<'a> {
     pub count: u32,
     pub entries_raw: &'a [u8],
+    pub config: ParserConfig,
 }
 
 pub struct TableEntryIterator<'a> {
     count: u32,
-    iter: &'a [u8]
+    iter: &'a [u8],
+    /// The length of all entries, for the offsets in errors.
+    len: usize,
+    config: ParserConfig,
 }
 
 pub struct TableEntry {
@@ -20,7 +23,9 @@ impl<'a> TableSection<'a> {
     pub fn entries(&self) -> TableEntryIterator<'a> {
         TableEntryIterator {
             count: self.count,
-            iter: self.entries_raw
+            iter: self.entries_raw,
+            len: self.entries_raw.len(),
+            config: self.config,
         }
     }
 }
@@ -33,12 +38,12 @@ impl<'a> Iterator for TableEntryIterator<'a> {
             return None
         }
         self.count -= 1;
-        let mut ty = [0; 1];
-        try_opt!((&mut self.iter).read_exact(&mut ty));
-        let limits = try_opt!(ResizableLimits::parse(&mut self.iter));
+        let start = self.len - self.iter.len();
+        let ty = try_opt!(read_u8(&mut self.iter));
+        let limits = try_opt!(ResizableLimits::parse(&mut self.iter, &self.config.features).map_err(|err| err.offset_by(start)));
         Some(Ok(TableEntry {
-            ty: ty[0],
-            limits: limits,
+            ty,
+            limits,
         }))
     }
 }
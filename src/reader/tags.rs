@@ -0,0 +1,29 @@
+use super::*;
+
+/// The tag section of the exception handling proposal. Each tag is the
+/// index of its type, which gives the values the exceptions it tags carry.
+pub struct TagSection<'a>(pub &'a [u8], pub usize);
+
+pub struct TagEntryIterator<'a>(&'a [u8], usize);
+
+impl<'a> TagSection<'a> {
+    pub fn types(&self) -> TagEntryIterator<'a> {
+        TagEntryIterator(self.0, self.1)
+    }
+}
+
+impl<'a> Iterator for TagEntryIterator<'a> {
+    type Item = Result<u32, Error>;
+
+    fn next(&mut self) -> Option<Result<u32, Error>> {
+        if self.1 == 0 {
+            return None
+        }
+        self.1 -= 1;
+        // The attribute, which is 0 for an exception, the only kind of tag.
+        if try_opt!(read_u8(&mut self.0)) != 0 {
+            return Some(Err(Error::UnknownVariant("tag attribute")))
+        }
+        Some(read_var_u32(&mut self.0))
+    }
+}
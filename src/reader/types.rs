@@ -1,9 +1,10 @@
 use super::*;
-use std::io;
 
-pub struct TypeSection<'a>(pub &'a [u8], pub usize);
+pub struct TypeSection<'a>(pub &'a [u8], pub usize, pub ParserConfig);
 
-pub struct TypeEntryIterator<'a>(&'a [u8], usize);
+/// The entries left, their number, the configuration, and the length of
+/// all entries, for the offsets in errors.
+pub struct TypeEntryIterator<'a>(&'a [u8], usize, ParserConfig, usize);
 
 pub enum TypeEntry<'a> {
     Function(FunctionType<'a>),
@@ -20,7 +21,7 @@ pub struct ParamsIterator<'a>(&'a [u8], usize);
 
 impl<'a> TypeSection<'a> {
     pub fn entries(&self) -> TypeEntryIterator<'a> {
-        TypeEntryIterator(self.0, self.1)
+        TypeEntryIterator(self.0, self.1, self.2, self.0.len())
     }
 }
 
@@ -28,41 +29,46 @@ impl<'a> Iterator for TypeEntryIterator<'a> {
     type Item = Result<TypeEntry<'a>, Error>;
 
     fn next(&mut self) -> Option<Result<TypeEntry<'a>, Error>> {
+        let start = self.3 - self.0.len();
+        self.read().map(|entry| entry.map_err(|err| err.offset_by(start)))
+    }
+}
+
+impl<'a> TypeEntryIterator<'a> {
+    fn read(&mut self) -> Option<Result<TypeEntry<'a>, Error>> {
         if self.1 == 0 {
             return None
         }
         self.1 -= 1;
-        let form = try_opt!(read_varuint(&mut self.0));
+        let form = try_opt!(read_var_u32(&mut self.0));
         let form = try_opt!(LanguageType::from_int(form as u8).ok_or(
             Error::UnknownVariant("type entry form")
         ));
-        let param_count = try_opt!(read_varuint(&mut self.0));
-        let params = if param_count > self.0.len() as u64 {
-            return Some(Err(Error::Io(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "param_count is larger than remaining space"
-            ))))
+        let param_count = try_opt!(read_var_u32(&mut self.0));
+        let params = if param_count as usize > self.0.len() {
+            return Some(Err(Error::UnexpectedEof))
         } else {
             let res = &self.0[..param_count as usize];
             self.0 = &self.0[param_count as usize..];
             res
         };
-        let return_count = try_opt!(read_varuint(&mut self.0));
+        // The parameters are decoded lazily, but a proposal they use has
+        // to be reported here.
+        if let Some(&ty) = params.iter().find(|&&ty| ValueType::from_int(ty).is_none()) {
+            return Some(Err(self.2.features.value_type_error(ty, "value type")))
+        }
+        let return_count = try_opt!(read_var_u32(&mut self.0));
+        if return_count > 1 {
+            try_opt!(WasmFeatures::check("multi-value", self.2.features.multi_value));
+            return Some(Err(Error::UnknownVariant("number of results")))
+        }
         let return_ty = if return_count > 0 {
-            if self.0.len() < 1 {
-                return Some(Err(Error::Io(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "return_count is larger than remaining space"
-                ))))
-            }
-            let res = self.0[0];
-            self.0 = &self.0[1..];
-            Some(try_opt!(ValueType::from_int(res).ok_or(Error::UnknownVariant("value type"))))
+            Some(try_opt!(read_value_type(&mut self.0, &self.2.features)))
         } else {
             None
         };
         Some(Ok(TypeEntry::Function(FunctionType {
-            form: form,
+            form,
             params_count: param_count as usize,
             params_raw: params,
             return_type: return_ty
@@ -79,15 +85,14 @@ impl<'a> FunctionType<'a> {
 impl<'a> Iterator for ParamsIterator<'a> {
     type Item = Result<ValueType, Error>;
 
+    #[allow(clippy::len_zero)]
     fn next(&mut self) -> Option<Result<ValueType, Error>> {
         if self.1 == 0 {
             return None
         }
         self.1 -= 1;
         if self.0.len() < 1 {
-            return Some(Err(Error::Io(io::Error::new(
-                io::ErrorKind::UnexpectedEof, "number of params is larger than available space"
-            ))))
+            return Some(Err(Error::UnexpectedEof))
         }
         let res = self.0[0];
         self.0 = &self.0[1..];
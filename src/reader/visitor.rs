@@ -0,0 +1,105 @@
+//! Decoding operators straight into method calls.
+//!
+//! Implement `OperatorVisitor` and override the methods for the operators
+//! you care about; everything else ends up in `visit_operator`. The
+//! drivers decode immediates into their typed form and pass them to the
+//! visitor without building an `Op` for each operator.
+
+use super::*;
+use super::bytecode::{OpCode, BrTable, MemoryImmediate};
+
+macro_rules! visitor {
+    ($($(#[$attr:meta])* $name:ident $(($($arg:ident: $kind:ident),+))* = $code:literal
+       => $visit:ident, $mnemonic:literal, $category:ident, $effect:tt;)+) => {
+        /// Receives the operators of a function body or expression, one
+        /// method call per operator.
+        ///
+        /// `offset` is the position of the opcode relative to the start of
+        /// the data the driver was given. Every method returns
+        /// `visit_operator(offset, code)` unless it is overridden, which
+        /// drops the immediates.
+        pub trait OperatorVisitor<'a> {
+            type Error: From<Error>;
+
+            /// Called for every operator whose method is not overridden.
+            fn visit_operator(&mut self, offset: usize, code: OpCode) -> Result<(), Self::Error>;
+
+            $(
+                #[allow(unused_variables)]
+                fn $visit(&mut self, offset: usize $($(, $arg: imm_type!('a, $kind))+)*) -> Result<(), Self::Error> {
+                    self.visit_operator(offset, OpCode::$name)
+                }
+            )+
+        }
+
+        fn dispatch<'a, V: OperatorVisitor<'a> + ?Sized>(
+            visitor: &mut V,
+            offset: usize,
+            code: OpCode,
+            iter: &mut &'a [u8],
+            config: &ParserConfig,
+        ) -> Result<(), V::Error> {
+            match code {$(
+                OpCode::$name => {
+                    $($(let $arg = imm_read!($kind, iter, &config.features).map_err(|err| err.offset_by(offset))?;)+)*
+                    visitor.$visit(offset $($(, $arg)+)*)
+                }
+            ),+}
+        }
+    }
+}
+
+for_each_operator!(visitor);
+
+/// Decodes operators from `data` up to and including the `End` that
+/// closes the body or expression, and returns the number of bytes read.
+pub fn visit_operators<'a, V>(
+    data: &'a [u8],
+    config: &ParserConfig,
+    visitor: &mut V,
+) -> Result<usize, V::Error>
+    where V: OperatorVisitor<'a> + ?Sized
+{
+    drive(data, 0, config, visitor)
+}
+
+/// Like `visit_operators`, but reports offsets starting at `base`.
+fn drive<'a, V>(
+    data: &'a [u8],
+    base: usize,
+    config: &ParserConfig,
+    visitor: &mut V,
+) -> Result<usize, V::Error>
+    where V: OperatorVisitor<'a> + ?Sized
+{
+    let mut iter = data;
+    let mut nesting = 1usize;
+    while nesting > 0 {
+        let offset = base + data.len() - iter.len();
+        let code = read_u8(&mut iter)?;
+        let code = OpCode::from_int(code).ok_or_else(|| config.features.opcode_error(code, iter).offset_by(offset))?;
+        match code {
+            OpCode::Block | OpCode::Loop | OpCode::If => {
+                nesting += 1;
+                ParserConfig::check("control nesting depth", nesting, config.max_nesting)?;
+            },
+            OpCode::End => nesting -= 1,
+            _ => (),
+        }
+        dispatch(visitor, offset, code, &mut iter, config)?;
+    }
+    Ok(data.len() - iter.len())
+}
+
+impl<'a> FunctionBody<'a> {
+    /// Skips the local declarations and feeds the operators of the body to
+    /// `visitor`. Offsets are relative to `self.body`.
+    pub fn visit_operators<V>(&self, visitor: &mut V) -> Result<(), V::Error>
+        where V: OperatorVisitor<'a> + ?Sized
+    {
+        let iter = self.skip_locals()?;
+        let start = self.body.len() - iter.len();
+        drive(iter, start, &self.config, visitor)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+//! Type checking of function bodies.
+//!
+//! This follows the validation algorithm in the appendix of the spec: an
+//! operand stack of value types, where an unknown type stands for any
+//! value produced by unreachable code, and a stack of control frames that
+//! records the operand stack height and result type of each block.
+//!
+//! `Context::typed_ops` walks a body and yields every operator together with
+//! the types it pops and pushes, for tools that need the operand stack at
+//! each instruction.
+
+use reader::{
+    ValueType, InlineSignatureType, Locals, FrameKind, OpCategory, StackEffect, FunctionBody
+};
+use reader::bytecode::{Op, OpIterator};
+use alloc::vec::Vec;
+use super::{Context, ValidationError, locate};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Frame {
+    kind: FrameKind,
+    result: Option<ValueType>,
+    /// The height of the operand stack when the frame was entered.
+    height: usize,
+    /// Set after an unconditional branch, until the end of the frame.
+    unreachable: bool,
+}
+
+/// Checks the operators of one function body, one at a time.
+pub struct FunctionValidator<'c, 'a: 'c> {
+    context: &'c Context<'a>,
+    locals: Locals,
+    result: Option<ValueType>,
+    /// `None` is a value of unknown type, popped from the polymorphic stack
+    /// of unreachable code.
+    operands: Vec<Option<ValueType>>,
+    frames: Vec<Frame>,
+    /// What the last operator popped, top of the stack first.
+    popped: Vec<Option<ValueType>>,
+    /// What the last operator pushed.
+    pushed: Vec<Option<ValueType>>,
+}
+
+impl<'c, 'a> FunctionValidator<'c, 'a> {
+    pub fn new(context: &'c Context<'a>, locals: Locals, result: Option<ValueType>) -> FunctionValidator<'c, 'a> {
+        FunctionValidator {
+            context,
+            locals,
+            result,
+            operands: vec![],
+            frames: vec![Frame {
+                kind: FrameKind::Function,
+                result,
+                height: 0,
+                unreachable: false,
+            }],
+            popped: vec![],
+            pushed: vec![],
+        }
+    }
+
+    /// Whether the `End` of the function body has been checked.
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The height of the operand stack.
+    pub fn height(&self) -> usize {
+        self.operands.len()
+    }
+
+    /// Whether the next operator is unreachable, because it follows an
+    /// unconditional branch in the same block.
+    pub fn is_unreachable(&self) -> bool {
+        self.frames.last().is_some_and(|frame| frame.unreachable)
+    }
+
+    fn push(&mut self, ty: Option<ValueType>) {
+        self.pushed.push(ty);
+        self.operands.push(ty)
+    }
+
+    fn pop(&mut self) -> Result<Option<ValueType>, &'static str> {
+        let frame = match self.frames.last() {
+            Some(frame) => frame,
+            None => return Err("operators after the end of the function"),
+        };
+        let ty = if self.operands.len() == frame.height {
+            if !frame.unreachable {
+                return Err("type mismatch")
+            }
+            None
+        } else {
+            self.operands.pop().unwrap()
+        };
+        self.popped.push(ty);
+        Ok(ty)
+    }
+
+    fn pop_expect(&mut self, expected: ValueType) -> Result<Option<ValueType>, &'static str> {
+        match self.pop()? {
+            Some(actual) if actual != expected => Err("type mismatch"),
+            _ => {
+                // An unknown operand has the type the operator expects.
+                *self.popped.last_mut().unwrap() = Some(expected);
+                Ok(Some(expected))
+            },
+        }
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, result: Option<ValueType>) {
+        self.frames.push(Frame {
+            kind,
+            result,
+            height: self.operands.len(),
+            unreachable: false,
+        })
+    }
+
+    fn pop_frame(&mut self) -> Result<Frame, &'static str> {
+        let frame = match self.frames.last() {
+            Some(&frame) => frame,
+            None => return Err("operators after the end of the function"),
+        };
+        if let Some(ty) = frame.result {
+            self.pop_expect(ty)?;
+        }
+        if self.operands.len() != frame.height {
+            return Err("type mismatch")
+        }
+        self.frames.pop();
+        Ok(frame)
+    }
+
+    /// The type a branch to the given label has to provide.
+    fn label(&self, depth: u32) -> Result<Option<ValueType>, &'static str> {
+        if depth as usize >= self.frames.len() {
+            return Err("unknown label")
+        }
+        let frame = &self.frames[self.frames.len() - 1 - depth as usize];
+        if frame.kind == FrameKind::Loop {
+            Ok(None)
+        } else {
+            Ok(frame.result)
+        }
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn call(&mut self, type_index: u32) -> Result<(), &'static str> {
+        let signature = match self.context.signatures.get(type_index as usize) {
+            Some(signature) => signature,
+            None => return Err("unknown type"),
+        };
+        for &ty in signature.params.iter().rev() {
+            self.pop_expect(ty)?;
+        }
+        if let Some(ty) = signature.result {
+            self.push(Some(ty));
+        }
+        Ok(())
+    }
+
+    fn local(&self, index: u32) -> Result<ValueType, &'static str> {
+        self.locals.get(index).ok_or("unknown local")
+    }
+
+    /// Checks the next operator of the body.
+    pub fn op(&mut self, op: &Op) -> Result<(), &'static str> {
+        self.popped.clear();
+        self.pushed.clear();
+        let info = op.info();
+        if info.category == OpCategory::Memory {
+            if self.context.memories == 0 {
+                return Err("unknown memory")
+            }
+            if let Some((natural, memarg)) = op.natural_alignment() {
+                if memarg.flags > natural {
+                    return Err("alignment must not be larger than natural")
+                }
+            }
+        }
+        match *op {
+            Op::Unreachable => self.set_unreachable(),
+            Op::Nop => (),
+            Op::Block(ty) => self.push_frame(FrameKind::Block, block_result(ty)),
+            Op::Loop(ty) => self.push_frame(FrameKind::Loop, block_result(ty)),
+            Op::If(ty) => {
+                self.pop_expect(ValueType::I32)?;
+                self.push_frame(FrameKind::If, block_result(ty));
+            },
+            Op::Else => {
+                if self.frames.last().map(|frame| frame.kind) != Some(FrameKind::If) {
+                    return Err("else without a matching if")
+                }
+                let frame = self.pop_frame()?;
+                self.push_frame(FrameKind::Else, frame.result);
+            },
+            Op::End => {
+                let frame = self.pop_frame()?;
+                if frame.kind == FrameKind::If && frame.result.is_some() {
+                    // The missing else arm would have to produce the value.
+                    return Err("type mismatch")
+                }
+                if let Some(ty) = frame.result {
+                    self.push(Some(ty));
+                }
+            },
+            Op::Br(depth) => {
+                if let Some(ty) = self.label(depth)? {
+                    self.pop_expect(ty)?;
+                }
+                self.set_unreachable();
+            },
+            Op::BrIf(depth) => {
+                self.pop_expect(ValueType::I32)?;
+                if let Some(ty) = self.label(depth)? {
+                    self.pop_expect(ty)?;
+                    self.push(Some(ty));
+                }
+            },
+            Op::BrTable(ref table) => {
+                self.pop_expect(ValueType::I32)?;
+                let ty = self.label(table.default)?;
+                for arm in table.arms() {
+                    let arm = arm.map_err(|_| "malformed br_table")?;
+                    if self.label(arm)? != ty {
+                        return Err("type mismatch")
+                    }
+                }
+                if let Some(ty) = ty {
+                    self.pop_expect(ty)?;
+                }
+                self.set_unreachable();
+            },
+            Op::Return => {
+                if let Some(ty) = self.result {
+                    self.pop_expect(ty)?;
+                }
+                self.set_unreachable();
+            },
+            Op::Call(index) => {
+                let type_index = match self.context.functions.get(index as usize) {
+                    Some(&type_index) => type_index,
+                    None => return Err("unknown function"),
+                };
+                self.call(type_index)?;
+            },
+            Op::CallIndirect(type_index, reserved) => {
+                if reserved {
+                    return Err("zero byte expected")
+                }
+                if self.context.tables == 0 {
+                    return Err("unknown table")
+                }
+                if type_index as usize >= self.context.signatures.len() {
+                    return Err("unknown type")
+                }
+                self.pop_expect(ValueType::I32)?;
+                self.call(type_index)?;
+            },
+            Op::Drop => {
+                self.pop()?;
+            },
+            Op::Select => {
+                self.pop_expect(ValueType::I32)?;
+                let first = self.pop()?;
+                let second = self.pop()?;
+                match (first, second) {
+                    (Some(first), Some(second)) if first != second => return Err("type mismatch"),
+                    _ => self.push(first.or(second)),
+                }
+            },
+            Op::GetLocal(index) => {
+                let ty = self.local(index)?;
+                self.push(Some(ty));
+            },
+            Op::SetLocal(index) => {
+                let ty = self.local(index)?;
+                self.pop_expect(ty)?;
+            },
+            Op::TeeLocal(index) => {
+                let ty = self.local(index)?;
+                self.pop_expect(ty)?;
+                self.push(Some(ty));
+            },
+            Op::GetGlobal(index) => {
+                let global = self.context.globals.get(index as usize).ok_or("unknown global")?;
+                self.push(Some(global.ty));
+            },
+            Op::SetGlobal(index) => {
+                let global = *self.context.globals.get(index as usize).ok_or("unknown global")?;
+                if !global.mutable {
+                    return Err("global is immutable")
+                }
+                self.pop_expect(global.ty)?;
+            },
+            Op::CurrentMemory(true) | Op::GrowMemory(true) => return Err("zero byte expected"),
+            _ => match info.stack {
+                StackEffect::Static { pops, pushes } => {
+                    for &ty in pops.iter().rev() {
+                        self.pop_expect(ty)?;
+                    }
+                    for &ty in pushes {
+                        self.push(Some(ty));
+                    }
+                },
+                StackEffect::Dynamic => unreachable!(),
+            },
+        }
+        Ok(())
+    }
+}
+
+fn block_result(ty: InlineSignatureType) -> Option<ValueType> {
+    match ty {
+        InlineSignatureType::I32 => Some(ValueType::I32),
+        InlineSignatureType::I64 => Some(ValueType::I64),
+        InlineSignatureType::F32 => Some(ValueType::F32),
+        InlineSignatureType::F64 => Some(ValueType::F64),
+        InlineSignatureType::Empty => None,
+    }
+}
+
+/// An operator together with its effect on the operand stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedOp<'a> {
+    /// The offset of the operator from the start of the module.
+    pub offset: usize,
+    pub op: Op<'a>,
+    /// The values the operator pops, bottom of the stack first. An operand
+    /// the operator accepts whatever its type, like the one of `drop` in
+    /// unreachable code, is `None`.
+    pub pops: Vec<Option<ValueType>>,
+    /// The values the operator pushes, bottom of the stack first. `None` is
+    /// the unknown result of `select` in unreachable code.
+    pub pushes: Vec<Option<ValueType>>,
+    /// The height of the operand stack before the operator.
+    pub height_before: usize,
+    /// The height of the operand stack after the operator. Unconditional
+    /// branches also discard the rest of the operands of their block, and
+    /// pops from the polymorphic stack of unreachable code take nothing
+    /// away, so this is not always `height_before - pops + pushes`.
+    pub height_after: usize,
+    /// Whether the operator follows an unconditional branch in its block.
+    pub unreachable: bool,
+}
+
+/// The operators of a function body with their types, as returned by
+/// `Context::typed_ops`. The body is validated on the way, and iteration
+/// stops after the first error.
+pub struct TypedOps<'c, 'a: 'c> {
+    validator: FunctionValidator<'c, 'a>,
+    ops: OpIterator<'a>,
+    function: u32,
+    /// The offsets of the operators and of the end of the body from the
+    /// start of the module.
+    start: usize,
+    end: usize,
+    done: bool,
+}
+
+impl<'c, 'a> TypedOps<'c, 'a> {
+    /// Checks the remaining operators without collecting their types.
+    pub fn finish(mut self) -> Result<(), ValidationError> {
+        while let Some(result) = self.step() {
+            result?;
+        }
+        Ok(())
+    }
+
+    fn error(&mut self, offset: usize, message: &'static str) -> ValidationError {
+        self.done = true;
+        ValidationError::Function {
+            function: self.function,
+            offset,
+            message,
+        }
+    }
+
+    /// Decodes and checks the next operator.
+    fn step(&mut self) -> Option<Result<(Op<'a>, usize), ValidationError>> {
+        if self.done {
+            return None
+        }
+        let offset = self.end - self.ops.iter.len();
+        match self.ops.next() {
+            None => {
+                self.done = true;
+                if !self.ops.iter.is_empty() || !self.validator.is_finished() {
+                    return Some(Err(self.error(offset, "operators after the end of the function")))
+                }
+                None
+            },
+            Some(Err(err)) => {
+                self.done = true;
+                Some(Err(locate(err.into(), self.start)))
+            },
+            Some(Ok(op)) => match self.validator.op(&op) {
+                Ok(()) => Some(Ok((op, offset))),
+                Err(message) => Some(Err(self.error(offset, message))),
+            },
+        }
+    }
+}
+
+impl<'c, 'a> Iterator for TypedOps<'c, 'a> {
+    type Item = Result<TypedOp<'a>, ValidationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let height_before = self.validator.height();
+        let unreachable = self.validator.is_unreachable();
+        let (op, offset) = match self.step() {
+            Some(Ok(next)) => next,
+            Some(Err(err)) => return Some(Err(err)),
+            None => return None,
+        };
+        Some(Ok(TypedOp {
+            offset,
+            op,
+            pops: self.validator.popped.iter().rev().cloned().collect(),
+            pushes: self.validator.pushed.clone(),
+            height_before,
+            height_after: self.validator.height(),
+            unreachable,
+        }))
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Walks the body of a function defined in the module, yielding each
+    /// operator with the types it pops and pushes. `function` is the index
+    /// of the function, counting the imported ones, and `body` has to come
+    /// from the code section of the module the context was built from.
+    pub fn typed_ops<'c>(&'c self, function: u32, body: &FunctionBody<'a>) -> Result<TypedOps<'c, 'a>, ValidationError> {
+        let invalid = |offset, message| ValidationError::Function {
+            function,
+            offset,
+            message,
+        };
+        let base = self.payload.as_ptr() as usize;
+        let start = body.body.as_ptr() as usize;
+        if start < base || start + body.body.len() > base + self.payload.len() {
+            return Err(invalid(0, "function body is not part of the module"))
+        }
+        // The payload starts after the magic number and version.
+        let start = start - base + 8;
+        if (function as usize) < self.imported_functions {
+            return Err(invalid(start, "function is imported"))
+        }
+        let type_index = match self.functions.get(function as usize) {
+            Some(&type_index) => type_index as usize,
+            None => return Err(invalid(start, "unknown function")),
+        };
+        let (locals, ops) = body.locals(&self.types[type_index]).map_err(|err| locate(err.into(), start))?;
+        let result = self.signatures[type_index].result;
+        let end = start + body.body.len();
+        let ops_start = end - ops.iter.len();
+        Ok(TypedOps {
+            validator: FunctionValidator::new(self, locals, result),
+            ops,
+            function,
+            start: ops_start,
+            end,
+            done: false,
+        })
+    }
+}
@@ -0,0 +1,453 @@
+//! Validation of modules against the rules of the spec.
+//!
+//! The reader only checks that a module can be decoded. `validate` checks
+//! the rest: the section layout, that every index refers to something that
+//! exists, the limits of tables and memories, constant expressions, export
+//! names, the start function, and the types of the operators in every
+//! function body. It stops at the first problem.
+
+use reader::{
+    Error, Module, SectionsIterator, SectionType, SectionContent, ValueType, ExternalKind,
+    ResizableLimits, TypeEntry, ImportEntryContents, GlobalEntryEither, ElementEntry, DataEntry,
+    FunctionType, WasmFeatures, read_var_u32
+};
+use reader::bytecode::Op;
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error;
+
+pub mod func;
+pub use self::func::{FunctionValidator, TypedOp, TypedOps};
+
+/// The most pages a memory can have, for 4 GiB of address space.
+const MAX_PAGES: u32 = 65536;
+
+/// The element type of tables, `anyfunc`.
+const ANYFUNC: u8 = 0x70;
+
+/// Errors produced by `validate`.
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The module could not be decoded.
+    Decode(Error),
+    /// A rule outside of function bodies is broken in the given section.
+    Module {
+        section: SectionType,
+        message: &'static str,
+    },
+    /// A function body does not type check. `function` is the index of the
+    /// function in the function index space, which counts imports first,
+    /// and `offset` is the position of the failing operator from the start
+    /// of the module.
+    Function {
+        function: u32,
+        offset: usize,
+        message: &'static str,
+    },
+    /// The module uses a proposal that is not enabled in
+    /// `ParserConfig::features`. `offset` is the position from the start
+    /// of the module where the reader found the use, or of the entries of
+    /// the section for uses found in an entry after decoding it.
+    Disabled {
+        feature: &'static str,
+        offset: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::Decode(ref err) => write!(f, "{}", err),
+            ValidationError::Module { section, message } => write!(f, "Invalid module: {} (in the {:?} section)", message, section),
+            ValidationError::Function { function, offset, message } => write!(f, "Invalid function {}: {} (at offset {:#x})", function, message, offset),
+            ValidationError::Disabled { feature, offset } => write!(f, "The {} proposal is not enabled (at offset {:#x})", feature, offset),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ValidationError::Decode(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for ValidationError {
+    fn from(err: Error) -> ValidationError {
+        ValidationError::Decode(err)
+    }
+}
+
+/// The parameters and result of a function type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<ValueType>,
+    pub result: Option<ValueType>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GlobalType {
+    pub ty: ValueType,
+    pub mutable: bool,
+}
+
+/// What function bodies can refer to, collected from the sections before
+/// the code section. Build one with `Context::new`.
+#[derive(Default)]
+pub struct Context<'a> {
+    /// The module payload, to compute offsets.
+    payload: &'a [u8],
+    types: Vec<FunctionType<'a>>,
+    signatures: Vec<Signature>,
+    /// The type index of each function, imports first.
+    functions: Vec<u32>,
+    imported_functions: usize,
+    tables: u32,
+    memories: u32,
+    globals: Vec<GlobalType>,
+    imported_globals: usize,
+}
+
+impl<'a> Context<'a> {
+    pub fn signature(&self, type_index: u32) -> Option<&Signature> {
+        self.signatures.get(type_index as usize)
+    }
+
+    /// The type index of a function in the function index space.
+    pub fn function_type(&self, index: u32) -> Option<u32> {
+        self.functions.get(index as usize).cloned()
+    }
+
+    pub fn global(&self, index: u32) -> Option<GlobalType> {
+        self.globals.get(index as usize).cloned()
+    }
+}
+
+fn invalid(section: SectionType, message: &'static str) -> ValidationError {
+    ValidationError::Module {
+        section,
+        message,
+    }
+}
+
+/// Gives errors about disabled proposals the offset they were found at.
+/// `base` is the offset of the input of the reader that found them.
+fn locate(err: ValidationError, base: usize) -> ValidationError {
+    match err {
+        ValidationError::Decode(Error::Disabled(feature, offset)) => ValidationError::Disabled {
+            feature,
+            offset: base + offset,
+        },
+        err => err,
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Checks everything outside of the function bodies and collects the
+    /// context the bodies are checked in.
+    pub fn new(module: &Module<'a>) -> Result<Context<'a>, ValidationError> {
+        let mut offset = 0;
+        Context::read(module, &mut offset).map_err(|err| locate(err, offset))
+    }
+
+    /// Does the work of `new`, keeping the offset that the errors of the
+    /// reader in use are relative to in `offset`.
+    fn read(module: &Module<'a>, offset: &mut usize) -> Result<Context<'a>, ValidationError> {
+        let features = module.config.features;
+        let mut context = Context {
+            payload: module.payload,
+            .. Context::default()
+        };
+        let mut exports = BTreeSet::new();
+        let mut data_count = None;
+        let mut data_segments = 0;
+        let mut sections = SectionsIterator::new(module.payload, module.config).strict();
+        loop {
+            *offset = 0;
+            let section = match sections.next() {
+                Some(section) => section?,
+                None => break,
+            };
+            // The readers of the contents count from the entries after the
+            // count, which sections without entries have no use for.
+            let mut entries = section.payload;
+            let _ = read_var_u32(&mut entries);
+            *offset = entries.as_ptr() as usize - module.payload.as_ptr() as usize + 8;
+            let id = section.id;
+            match id {
+                // Custom sections, including the name section, have no rules.
+                SectionType::Named => continue,
+                SectionType::Tag => return Err(invalid(id, "tags are not supported")),
+                _ => (),
+            }
+            match section.content()? {
+                SectionContent::Type(types) => {
+                    for entry in types.entries() {
+                        let TypeEntry::Function(ty) = entry?;
+                        let mut params = vec![];
+                        for param in ty.params() {
+                            params.push(param?);
+                        }
+                        context.signatures.push(Signature {
+                            params,
+                            result: ty.return_type,
+                        });
+                        context.types.push(ty);
+                    }
+                },
+                SectionContent::Import(imports) => {
+                    for entry in imports.entries() {
+                        match entry?.contents {
+                            ImportEntryContents::Function(type_index) => {
+                                if type_index as usize >= context.types.len() {
+                                    return Err(invalid(id, "unknown type"))
+                                }
+                                context.functions.push(type_index);
+                                context.imported_functions += 1;
+                            },
+                            ImportEntryContents::Table { element_type, limits } => {
+                                check_table(&mut context, element_type, &limits).map_err(|message| invalid(id, message))?;
+                            },
+                            ImportEntryContents::Memory(limits) => {
+                                check_memory(&mut context, &limits).map_err(|message| invalid(id, message))?;
+                            },
+                            ImportEntryContents::Global { ty, mutable } => {
+                                if mutable {
+                                    WasmFeatures::check("mutable-global", features.mutable_global)?;
+                                }
+                                context.globals.push(GlobalType {
+                                    ty,
+                                    mutable,
+                                });
+                                context.imported_globals += 1;
+                            },
+                        }
+                    }
+                },
+                SectionContent::Function(functions) => {
+                    for type_index in functions.types() {
+                        let type_index = type_index?;
+                        if type_index as usize >= context.types.len() {
+                            return Err(invalid(id, "unknown type"))
+                        }
+                        context.functions.push(type_index);
+                    }
+                },
+                SectionContent::Table(tables) => {
+                    for entry in tables.entries() {
+                        let entry = entry?;
+                        check_table(&mut context, entry.ty, &entry.limits).map_err(|message| invalid(id, message))?;
+                    }
+                },
+                SectionContent::Memory(memories) => {
+                    for entry in memories.entries() {
+                        check_memory(&mut context, &entry?.limits).map_err(|message| invalid(id, message))?;
+                    }
+                },
+                SectionContent::Global(globals) => {
+                    let mut ty = None;
+                    let mut init = vec![];
+                    for entry in globals.entries() {
+                        match entry? {
+                            GlobalEntryEither::Entry(entry) => ty = Some(GlobalType {
+                                ty: entry.ty,
+                                mutable: entry.mutable,
+                            }),
+                            GlobalEntryEither::Op(op) => {
+                                if !push_const_op(&mut init, op) {
+                                    continue
+                                }
+                                let ty = ty.take().expect("op before global entry");
+                                check_const_expr(&context, &init, ty.ty).map_err(|message| invalid(id, message))?;
+                                context.globals.push(ty);
+                                init.clear();
+                            },
+                        }
+                    }
+                },
+                SectionContent::Export(entries) => {
+                    for entry in entries.entries() {
+                        let entry = entry?;
+                        if !exports.insert(entry.field) {
+                            return Err(invalid(id, "duplicate export name"))
+                        }
+                        let (count, message) = match entry.kind {
+                            ExternalKind::Function => (context.functions.len() as u32, "unknown function"),
+                            ExternalKind::Table => (context.tables, "unknown table"),
+                            ExternalKind::Memory => (context.memories, "unknown memory"),
+                            ExternalKind::Global => (context.globals.len() as u32, "unknown global"),
+                        };
+                        if entry.index >= count {
+                            return Err(invalid(id, message))
+                        }
+                        if entry.kind == ExternalKind::Global && context.globals[entry.index as usize].mutable {
+                            WasmFeatures::check("mutable-global", features.mutable_global)?;
+                        }
+                    }
+                },
+                SectionContent::Start(index) => {
+                    let type_index = context.function_type(index).ok_or(invalid(id, "unknown function"))?;
+                    let signature = &context.signatures[type_index as usize];
+                    if !signature.params.is_empty() || signature.result.is_some() {
+                        return Err(invalid(id, "start function must have type [] -> []"))
+                    }
+                },
+                SectionContent::Elements(elements) => {
+                    let mut offset = vec![];
+                    for entry in elements.entries() {
+                        match entry? {
+                            ElementEntry::Index(index) => {
+                                if index >= context.tables {
+                                    return Err(invalid(id, "unknown table"))
+                                }
+                            },
+                            ElementEntry::Op(op) => {
+                                if push_const_op(&mut offset, op) {
+                                    check_const_expr(&context, &offset, ValueType::I32).map_err(|message| invalid(id, message))?;
+                                    offset.clear();
+                                }
+                            },
+                            ElementEntry::Elem(index) => {
+                                if index as usize >= context.functions.len() {
+                                    return Err(invalid(id, "unknown function"))
+                                }
+                            },
+                        }
+                    }
+                },
+                SectionContent::DataCount(count) => data_count = Some(count),
+                SectionContent::Code(code) => {
+                    let defined = context.functions.len() - context.imported_functions;
+                    if code.count as usize != defined {
+                        return Err(invalid(id, "function and code section have different lengths"))
+                    }
+                },
+                SectionContent::Data(data) => {
+                    let mut offset = vec![];
+                    for entry in data.entries() {
+                        match entry? {
+                            DataEntry::Index(index) => {
+                                if index >= context.memories {
+                                    return Err(invalid(id, "unknown memory"))
+                                }
+                                data_segments += 1;
+                            },
+                            DataEntry::Op(op) => {
+                                if push_const_op(&mut offset, op) {
+                                    check_const_expr(&context, &offset, ValueType::I32).map_err(|message| invalid(id, message))?;
+                                    offset.clear();
+                                }
+                            },
+                            DataEntry::Data(_) => (),
+                        }
+                    }
+                },
+                SectionContent::Name(_) | SectionContent::Tag(_) => (),
+            }
+        }
+        if let Some(count) = data_count {
+            if count != data_segments {
+                return Err(invalid(SectionType::DataCount, "data count and data section have inconsistent lengths"))
+            }
+        }
+        Ok(context)
+    }
+}
+
+/// Validates a module. See the module documentation for what is checked.
+pub fn validate(module: &Module) -> Result<(), ValidationError> {
+    let context = Context::new(module)?;
+    for section in SectionsIterator::new(module.payload, module.config) {
+        let section = section?;
+        if section.id != SectionType::Code {
+            continue
+        }
+        if let SectionContent::Code(code) = section.content()? {
+            for (index, body) in code.entries().enumerate() {
+                let function = (context.imported_functions + index) as u32;
+                context.typed_ops(function, &body?)?.finish()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks a limit against the largest allowed size.
+fn check_limits(limits: &ResizableLimits, max: u32, too_large: &'static str) -> Result<(), &'static str> {
+    if limits.initial > max || limits.maximum.is_some_and(|maximum| maximum > max) {
+        return Err(too_large)
+    }
+    match limits.maximum {
+        Some(maximum) if limits.initial > maximum => Err("size minimum must not be greater than maximum"),
+        _ => Ok(()),
+    }
+}
+
+fn check_table(context: &mut Context, element_type: u8, limits: &ResizableLimits) -> Result<(), &'static str> {
+    if element_type != ANYFUNC {
+        return Err("unknown table element type")
+    }
+    check_limits(limits, u32::MAX, "table size must be at most 2^32-1")?;
+    if limits.shared {
+        return Err("tables cannot be shared")
+    }
+    context.tables += 1;
+    if context.tables > 1 {
+        return Err("multiple tables")
+    }
+    Ok(())
+}
+
+fn check_memory(context: &mut Context, limits: &ResizableLimits) -> Result<(), &'static str> {
+    check_limits(limits, MAX_PAGES, "memory size must be at most 65536 pages (4GiB)")?;
+    if limits.shared && limits.maximum.is_none() {
+        return Err("shared memory must have maximum")
+    }
+    context.memories += 1;
+    if context.memories > 1 {
+        return Err("multiple memories")
+    }
+    Ok(())
+}
+
+/// Adds an operator of a constant expression, and returns whether it
+/// ended the expression.
+fn push_const_op<'a>(expr: &mut Vec<Op<'a>>, op: Op<'a>) -> bool {
+    let end = op == Op::End;
+    expr.push(op);
+    end
+}
+
+/// Checks an initializer: a single constant, or a read of an imported
+/// immutable global, followed by `End`.
+fn check_const_expr(context: &Context, ops: &[Op], expected: ValueType) -> Result<(), &'static str> {
+    let ty = match ops {
+        [ref op, Op::End] => match *op {
+            Op::I32Const(_) => ValueType::I32,
+            Op::I64Const(_) => ValueType::I64,
+            Op::F32Const(_) => ValueType::F32,
+            Op::F64Const(_) => ValueType::F64,
+            Op::GetGlobal(index) => {
+                if index as usize >= context.imported_globals {
+                    return Err("unknown global")
+                }
+                let global = context.globals[index as usize];
+                if global.mutable {
+                    return Err("constant expression required")
+                }
+                global.ty
+            },
+            _ => return Err("constant expression required"),
+        },
+        _ => return Err("constant expression required"),
+    };
+    if ty != expected {
+        return Err("type mismatch")
+    }
+    Ok(())
+}
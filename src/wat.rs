@@ -0,0 +1,754 @@
+//! Printing modules in the WebAssembly text format.
+//!
+//! `print` writes an `ast::Module` as a `(module ...)` S-expression that
+//! other tools can read back. Function bodies are either a flat list of
+//! instructions or folded, with the operands of each instruction nested
+//! inside it.
+//!
+//! Functions and locals are named after the name section when the module
+//! has one. Names are reduced to the characters an identifier may contain,
+//! and a name that is already taken gets the index appended. Everything
+//! else is referred to by index, with the index of each definition in a
+//! comment.
+//!
+//! Folding only moves instructions inside the one that consumes their
+//! results, so the folded form has the same meaning as the flat one even
+//! when a body does not validate.
+
+use ast::{Module, FunctionType, ImportKind, Function, Table, Memory, GlobalType};
+use reader::{
+    ParserConfig, ValueType, InlineSignatureType, ExternalKind, ResizableLimits, NameSection, StackEffect
+};
+use reader::bytecode::Op;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::mem;
+use core::str;
+
+/// How function bodies are laid out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Style {
+    /// One instruction per line, indented by block.
+    Flat,
+    /// S-expressions, with operands nested inside the instruction that
+    /// consumes them.
+    Folded,
+}
+
+/// Prints a module in the text format.
+pub fn print(module: &Module, style: Style) -> String {
+    let mut out = String::new();
+    write(&mut out, module, style).expect("writing to a String failed");
+    out
+}
+
+/// Like `print`, but writes to `out`.
+pub fn write<W: Write>(out: &mut W, module: &Module, style: Style) -> fmt::Result {
+    let mut functions = vec![];
+    for import in &module.imports {
+        if let ImportKind::Function(ty) = import.kind {
+            functions.push(ty);
+        }
+    }
+    functions.extend(module.functions.iter().map(|function| function.ty));
+    let mut printer = Printer {
+        out,
+        module,
+        names: Names::new(module),
+        functions,
+        style,
+    };
+    printer.module()
+}
+
+/// The identifiers taken from the name section.
+#[derive(Default)]
+struct Names {
+    /// By function index, imports first.
+    functions: BTreeMap<u32, String>,
+    /// By function index, then local index, parameters first.
+    locals: BTreeMap<(u32, u32), String>,
+    /// The identifiers taken, among functions and among the locals of each
+    /// function.
+    taken: BTreeSet<String>,
+    taken_locals: BTreeMap<u32, BTreeSet<String>>,
+}
+
+impl Names {
+    /// Reads the name section of `module`, in the format of the spec or,
+    /// failing that, in the older one without subsections. Module names
+    /// and the subsections of later proposals are not used. A malformed
+    /// subsection is ignored from the first error on.
+    fn new(module: &Module) -> Names {
+        let mut names = Names::default();
+        let section = match module.custom_section("name") {
+            Some(section) => NameSection {
+                payload: &section.payload,
+                config: ParserConfig::default(),
+            },
+            None => return names,
+        };
+        for entry in section.function_names() {
+            match entry {
+                Ok((index, name)) => names.function(index, name),
+                Err(_) => break,
+            }
+        }
+        for entry in section.local_names() {
+            match entry {
+                Ok((function, index, name)) => names.local(function, index, name),
+                Err(_) => break,
+            }
+        }
+        names
+    }
+
+    fn function(&mut self, index: u32, name: &str) {
+        if let Some(id) = identifier(name, index as usize, &mut self.taken) {
+            self.functions.insert(index, id);
+        }
+    }
+
+    fn local(&mut self, function: u32, index: u32, name: &str) {
+        let taken = self.taken_locals.entry(function).or_default();
+        if let Some(id) = identifier(name, index as usize, taken) {
+            self.locals.insert((function, index), id);
+        }
+    }
+
+    fn function_name(&self, index: u32) -> Option<&str> {
+        self.functions.get(&index).map(|name| &name[..])
+    }
+
+    fn local_name(&self, function: usize, index: u32) -> Option<&str> {
+        self.locals.get(&(function as u32, index)).map(|name| &name[..])
+    }
+}
+
+/// Turns a name into an identifier, without the `$`, that is not in
+/// `taken`. Characters an identifier cannot contain become `_`, and an
+/// empty name gives no identifier.
+fn identifier(name: &str, index: usize, taken: &mut BTreeSet<String>) -> Option<String> {
+    if name.is_empty() {
+        return None
+    }
+    let mut id: String = name.chars().map(|c| match c {
+        '!' | '#'..='\'' | '*'..='+' | '-'..=':' | '<'..='Z' | '\\' | '^'..='z' | '|' | '~' => c,
+        _ => '_',
+    }).collect();
+    while taken.contains(&id) {
+        id = format!("{}.{}", id, index);
+    }
+    taken.insert(id.clone());
+    Some(id)
+}
+
+fn value_type(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+    }
+}
+
+fn block_result(ty: InlineSignatureType) -> Option<&'static str> {
+    match ty {
+        InlineSignatureType::I32 => Some("i32"),
+        InlineSignatureType::I64 => Some("i64"),
+        InlineSignatureType::F32 => Some("f32"),
+        InlineSignatureType::F64 => Some("f64"),
+        InlineSignatureType::Empty => None,
+    }
+}
+
+/// Writes a string literal. Printable ASCII other than quotes and
+/// backslashes is written as is, and every other byte as an escape.
+fn string<W: Write>(out: &mut W, bytes: &[u8]) -> fmt::Result {
+    out.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{}", byte as char)?,
+            0x20..=0x7e => out.write_char(byte as char)?,
+            _ => write!(out, "\\{:02x}", byte)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Writes an infinite or NaN float constant. `payload` is the fraction
+/// of a NaN, and `canonical` the fraction of the canonical NaN.
+fn special_float<W: Write>(out: &mut W, negative: bool, nan: bool, payload: u64, canonical: u64) -> fmt::Result {
+    if negative {
+        out.write_char('-')?;
+    }
+    if !nan {
+        out.write_str("inf")
+    } else if payload == canonical {
+        out.write_str("nan")
+    } else {
+        write!(out, "nan:{:#x}", payload)
+    }
+}
+
+/// An instruction of a folded body, with the instructions it contains.
+struct Node<'m> {
+    op: &'m Op<'static>,
+    /// The instructions that produce the operands. For an `if`, this is
+    /// the condition.
+    operands: Vec<Node<'m>>,
+    /// The body of a block or loop, or the then arm of an if.
+    body: Vec<Node<'m>>,
+    /// The else arm of an if, if there is one.
+    alternative: Option<Vec<Node<'m>>>,
+    /// How many values the instruction leaves on the stack.
+    results: usize,
+}
+
+/// A block, loop or if whose `end` has not been reached yet.
+struct Frame<'m> {
+    /// `None` for the function body.
+    node: Option<Node<'m>>,
+    /// How many values a branch to the frame takes.
+    arity: usize,
+    /// The then arm, once `else` has been seen.
+    then: Option<Vec<Node<'m>>>,
+    nodes: Vec<Node<'m>>,
+}
+
+struct Printer<'w, 'm, W: 'w> {
+    out: &'w mut W,
+    module: &'m Module,
+    names: Names,
+    /// The type of every function, imports first.
+    functions: Vec<u32>,
+    style: Style,
+}
+
+impl<'w, 'm, W: Write> Printer<'w, 'm, W> {
+    /// Starts a new line indented by `indent` levels.
+    fn line(&mut self, indent: usize) -> fmt::Result {
+        self.out.write_char('\n')?;
+        for _ in 0..indent {
+            self.out.write_str("  ")?;
+        }
+        Ok(())
+    }
+
+    fn module(&mut self) -> fmt::Result {
+        let module = self.module;
+        self.out.write_str("(module")?;
+        for (index, ty) in module.types.iter().enumerate() {
+            self.line(1)?;
+            write!(self.out, "(type (;{};) (func", index)?;
+            self.signature(ty, None)?;
+            self.out.write_str("))")?;
+        }
+        let (mut functions, mut tables, mut memories, mut globals) = (0, 0, 0, 0);
+        for import in &module.imports {
+            self.line(1)?;
+            self.out.write_str("(import ")?;
+            string(self.out, import.module.as_bytes())?;
+            self.out.write_char(' ')?;
+            string(self.out, import.field.as_bytes())?;
+            match import.kind {
+                ImportKind::Function(ty) => {
+                    self.out.write_str(" (func")?;
+                    self.function_id(functions)?;
+                    write!(self.out, " (type {}))", ty)?;
+                    functions += 1;
+                },
+                ImportKind::Table(ref table) => {
+                    self.out.write_char(' ')?;
+                    self.table(tables, table)?;
+                    tables += 1;
+                },
+                ImportKind::Memory(ref memory) => {
+                    self.out.write_char(' ')?;
+                    self.memory(memories, memory)?;
+                    memories += 1;
+                },
+                ImportKind::Global(ty) => {
+                    write!(self.out, " (global (;{};) ", globals)?;
+                    self.global_type(ty)?;
+                    self.out.write_char(')')?;
+                    globals += 1;
+                },
+            }
+            self.out.write_char(')')?;
+        }
+        for function in &module.functions {
+            self.function(functions, function)?;
+            functions += 1;
+        }
+        for table in &module.tables {
+            self.line(1)?;
+            self.table(tables, table)?;
+            tables += 1;
+        }
+        for memory in &module.memories {
+            self.line(1)?;
+            self.memory(memories, memory)?;
+            memories += 1;
+        }
+        for (index, ty) in module.tags.iter().enumerate() {
+            self.line(1)?;
+            write!(self.out, "(tag (;{};) (type {}))", index, ty)?;
+        }
+        for global in &module.globals {
+            self.line(1)?;
+            write!(self.out, "(global (;{};) ", globals)?;
+            self.global_type(global.ty)?;
+            self.expr(&global.init, false)?;
+            self.out.write_char(')')?;
+            globals += 1;
+        }
+        for export in &module.exports {
+            self.line(1)?;
+            self.out.write_str("(export ")?;
+            string(self.out, export.field.as_bytes())?;
+            let kind = match export.kind {
+                ExternalKind::Function => "func",
+                ExternalKind::Table => "table",
+                ExternalKind::Memory => "memory",
+                ExternalKind::Global => "global",
+            };
+            write!(self.out, " ({} ", kind)?;
+            if export.kind == ExternalKind::Function {
+                self.function_ref(export.index)?;
+            } else {
+                write!(self.out, "{}", export.index)?;
+            }
+            self.out.write_str("))")?;
+        }
+        if let Some(start) = module.start {
+            self.line(1)?;
+            self.out.write_str("(start ")?;
+            self.function_ref(start)?;
+            self.out.write_char(')')?;
+        }
+        for (index, element) in module.elements.iter().enumerate() {
+            self.line(1)?;
+            write!(self.out, "(elem (;{};)", index)?;
+            if element.index != 0 {
+                write!(self.out, " (table {})", element.index)?;
+            }
+            self.expr(&element.offset, true)?;
+            self.out.write_str(" func")?;
+            for &function in &element.elems {
+                self.out.write_char(' ')?;
+                self.function_ref(function)?;
+            }
+            self.out.write_char(')')?;
+        }
+        for (index, data) in module.data.iter().enumerate() {
+            self.line(1)?;
+            write!(self.out, "(data (;{};)", index)?;
+            if data.index != 0 {
+                write!(self.out, " (memory {})", data.index)?;
+            }
+            self.expr(&data.offset, true)?;
+            self.out.write_char(' ')?;
+            string(self.out, &data.data)?;
+            self.out.write_char(')')?;
+        }
+        self.out.write_str(")\n")
+    }
+
+    /// Writes the parameters and result of a function type, naming the
+    /// parameters after the locals of `function` if it is given.
+    fn signature(&mut self, ty: &FunctionType, function: Option<usize>) -> fmt::Result {
+        if !ty.params.is_empty() {
+            let types: Vec<_> = ty.params.iter().map(|&ty| (1, ty)).collect();
+            self.out.write_char(' ')?;
+            self.declarations("param", &types, function)?;
+        }
+        if let Some(result) = ty.return_type {
+            write!(self.out, " (result {})", value_type(result))?;
+        }
+        Ok(())
+    }
+
+    /// Writes `(param ...)` or `(local ...)` declarations, separated by
+    /// spaces, for `count` values of each type. Locals are numbered after
+    /// the parameters of `function`. Values without names share a
+    /// declaration.
+    fn declarations(&mut self, keyword: &str, types: &[(u32, ValueType)], function: Option<usize>) -> fmt::Result {
+        let mut index = match (keyword, function) {
+            ("local", Some(function)) => self.function_type(function as u32).map_or(0, |ty| ty.params.len() as u32),
+            _ => 0,
+        };
+        let mut open = false;
+        let mut separator = "";
+        for &(count, ty) in types {
+            for _ in 0..count {
+                let names = &self.names;
+                let name = function.and_then(|function| names.local_name(function, index));
+                match name {
+                    Some(name) => {
+                        if open {
+                            self.out.write_char(')')?;
+                            open = false;
+                        }
+                        write!(self.out, "{}({} ${} {})", separator, keyword, name, value_type(ty))?;
+                    },
+                    None => {
+                        if !open {
+                            write!(self.out, "{}({}", separator, keyword)?;
+                            open = true;
+                        }
+                        write!(self.out, " {}", value_type(ty))?;
+                    },
+                }
+                separator = " ";
+                index += 1;
+            }
+        }
+        if open {
+            self.out.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn function_type(&self, function: u32) -> Option<&'m FunctionType> {
+        let module = self.module;
+        self.functions.get(function as usize).and_then(|&ty| module.types.get(ty as usize))
+    }
+
+    /// Writes the identifier of a function being defined, or its index in a
+    /// comment.
+    fn function_id(&mut self, index: usize) -> fmt::Result {
+        match self.names.function_name(index as u32) {
+            Some(name) => write!(self.out, " ${}", name),
+            None => write!(self.out, " (;{};)", index),
+        }
+    }
+
+    fn function_ref(&mut self, index: u32) -> fmt::Result {
+        match self.names.function_name(index) {
+            Some(name) => write!(self.out, "${}", name),
+            None => write!(self.out, "{}", index),
+        }
+    }
+
+    fn limits(&mut self, limits: &ResizableLimits) -> fmt::Result {
+        write!(self.out, " {}", limits.initial)?;
+        if let Some(maximum) = limits.maximum {
+            write!(self.out, " {}", maximum)?;
+        }
+        Ok(())
+    }
+
+    fn table(&mut self, index: usize, table: &Table) -> fmt::Result {
+        write!(self.out, "(table (;{};)", index)?;
+        self.limits(&table.limits)?;
+        match table.element_type {
+            0x70 => self.out.write_str(" funcref)"),
+            0x6f => self.out.write_str(" externref)"),
+            other => write!(self.out, " (;unknown element type {:#x};))", other),
+        }
+    }
+
+    fn memory(&mut self, index: usize, memory: &Memory) -> fmt::Result {
+        write!(self.out, "(memory (;{};)", index)?;
+        self.limits(&memory.limits)?;
+        if memory.limits.shared {
+            self.out.write_str(" shared")?;
+        }
+        self.out.write_char(')')
+    }
+
+    fn global_type(&mut self, ty: GlobalType) -> fmt::Result {
+        if ty.mutable {
+            write!(self.out, "(mut {})", value_type(ty.ty))
+        } else {
+            self.out.write_str(value_type(ty.ty))
+        }
+    }
+
+    /// Writes a constant expression on the current line, one folded
+    /// instruction after another. `offset` wraps more than one instruction
+    /// in `(offset ...)`, as segment offsets need.
+    fn expr(&mut self, ops: &[Op<'static>], offset: bool) -> fmt::Result {
+        let ops = match ops.split_last() {
+            Some((&Op::End, rest)) => rest,
+            _ => ops,
+        };
+        let wrap = offset && ops.len() != 1;
+        if wrap {
+            self.out.write_str(" (offset")?;
+        }
+        for op in ops {
+            self.out.write_str(" (")?;
+            self.instruction(op, None)?;
+            self.out.write_char(')')?;
+        }
+        if wrap {
+            self.out.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, index: usize, function: &'m Function) -> fmt::Result {
+        self.line(1)?;
+        self.out.write_str("(func")?;
+        self.function_id(index)?;
+        write!(self.out, " (type {})", function.ty)?;
+        if let Some(ty) = self.module.types.get(function.ty as usize) {
+            self.signature(ty, Some(index))?;
+        }
+        let locals: Vec<_> = function.locals.iter().map(|local| (local.count, local.ty)).collect();
+        if locals.iter().any(|&(count, _)| count > 0) {
+            self.line(2)?;
+            self.declarations("local", &locals, Some(index))?;
+        }
+        let body = match function.body.split_last() {
+            Some((&Op::End, rest)) => rest,
+            _ => &function.body[..],
+        };
+        match self.style {
+            Style::Flat => self.flat(body, index)?,
+            Style::Folded => {
+                let nodes = self.fold(body, index);
+                for node in &nodes {
+                    self.node(node, 2, index)?;
+                }
+            },
+        }
+        self.out.write_char(')')
+    }
+
+    fn flat(&mut self, body: &[Op<'static>], function: usize) -> fmt::Result {
+        let mut depth = 2;
+        for op in body {
+            match *op {
+                Op::Else | Op::End if depth > 2 => depth -= 1,
+                _ => (),
+            }
+            self.line(depth)?;
+            self.instruction(op, Some(function))?;
+            match *op {
+                Op::Block(_) | Op::Loop(_) | Op::If(_) | Op::Else => depth += 1,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes an instruction and its immediates. Locals are named after
+    /// those of `function`.
+    fn instruction(&mut self, op: &Op, function: Option<usize>) -> fmt::Result {
+        self.out.write_str(op.info().mnemonic)?;
+        if let Some((natural, memarg)) = op.natural_alignment() {
+            if memarg.offset != 0 {
+                write!(self.out, " offset={}", memarg.offset)?;
+            }
+            if memarg.flags != natural {
+                write!(self.out, " align={}", 1u64 << memarg.flags.min(63))?;
+            }
+            return Ok(())
+        }
+        match *op {
+            Op::Block(ty) | Op::Loop(ty) | Op::If(ty) => {
+                if let Some(result) = block_result(ty) {
+                    write!(self.out, " (result {})", result)?;
+                }
+            },
+            Op::Br(depth) | Op::BrIf(depth) => write!(self.out, " {}", depth)?,
+            Op::BrTable(ref table) => {
+                for arm in table.arms() {
+                    match arm {
+                        Ok(depth) => write!(self.out, " {}", depth)?,
+                        Err(_) => break,
+                    }
+                }
+                write!(self.out, " {}", table.default)?;
+            },
+            Op::Call(index) => {
+                self.out.write_char(' ')?;
+                self.function_ref(index)?;
+            },
+            Op::CallIndirect(ty, _) => write!(self.out, " (type {})", ty)?,
+            Op::GetLocal(index) | Op::SetLocal(index) | Op::TeeLocal(index) => {
+                let names = &self.names;
+                match function.and_then(|function| names.local_name(function, index)) {
+                    Some(name) => write!(self.out, " ${}", name)?,
+                    None => write!(self.out, " {}", index)?,
+                }
+            },
+            Op::GetGlobal(index) | Op::SetGlobal(index) => write!(self.out, " {}", index)?,
+            Op::I32Const(value) => write!(self.out, " {}", value)?,
+            Op::I64Const(value) => write!(self.out, " {}", value)?,
+            Op::F32Const(value) if value.is_finite() => write!(self.out, " {:?}", value)?,
+            Op::F32Const(value) => {
+                self.out.write_char(' ')?;
+                let payload = value.to_bits() as u64 & 0x7f_ffff;
+                special_float(self.out, value.is_sign_negative(), value.is_nan(), payload, 1 << 22)?;
+            },
+            Op::F64Const(value) if value.is_finite() => write!(self.out, " {:?}", value)?,
+            Op::F64Const(value) => {
+                self.out.write_char(' ')?;
+                let payload = value.to_bits() & 0xf_ffff_ffff_ffff;
+                special_float(self.out, value.is_sign_negative(), value.is_nan(), payload, 1 << 51)?;
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// How many values an instruction pops and pushes, given the branch
+    /// arity of each enclosing frame, innermost last. Instructions that
+    /// open or close a frame are handled by `fold`.
+    fn effect(&self, op: &Op, frames: &[Frame]) -> (usize, usize) {
+        let label = |depth: u32| {
+            frames.len().checked_sub(depth as usize + 1).map_or(0, |index| frames[index].arity)
+        };
+        let call = |ty: Option<&FunctionType>| {
+            ty.map_or((0, 0), |ty| (ty.params.len(), ty.return_type.is_some() as usize))
+        };
+        match *op {
+            Op::Br(depth) => (label(depth), 0),
+            Op::BrIf(depth) => (label(depth) + 1, label(depth)),
+            Op::BrTable(ref table) => (label(table.default) + 1, 0),
+            Op::Return => (frames[0].arity, 0),
+            Op::Call(index) => call(self.function_type(index)),
+            Op::CallIndirect(ty, _) => {
+                let (pops, pushes) = call(self.module.types.get(ty as usize));
+                (pops + 1, pushes)
+            },
+            Op::Drop => (1, 0),
+            Op::Select => (3, 1),
+            Op::GetLocal(_) | Op::GetGlobal(_) => (0, 1),
+            Op::SetLocal(_) | Op::SetGlobal(_) => (1, 0),
+            Op::TeeLocal(_) => (1, 1),
+            _ => match op.info().stack {
+                StackEffect::Static { pops, pushes } => (pops.len(), pushes.len()),
+                StackEffect::Dynamic => (0, 0),
+            },
+        }
+    }
+
+    /// Nests the instructions of a body inside the blocks that contain
+    /// them and the instructions that consume their results.
+    fn fold(&self, body: &'m [Op<'static>], function: usize) -> Vec<Node<'m>> {
+        let results = self.function_type(function as u32).map_or(0, |ty| ty.return_type.is_some() as usize);
+        let mut frames = vec![Frame {
+            node: None,
+            arity: results,
+            then: None,
+            nodes: vec![],
+        }];
+        for op in body {
+            match *op {
+                Op::Block(ty) | Op::Loop(ty) | Op::If(ty) => {
+                    let results = block_result(ty).is_some() as usize;
+                    let operands = match *op {
+                        Op::If(_) => take_operands(&mut frames.last_mut().unwrap().nodes, 1),
+                        _ => vec![],
+                    };
+                    frames.push(Frame {
+                        node: Some(Node {
+                            op,
+                            operands,
+                            body: vec![],
+                            alternative: None,
+                            results,
+                        }),
+                        arity: if let Op::Loop(_) = *op { 0 } else { results },
+                        then: None,
+                        nodes: vec![],
+                    });
+                },
+                Op::Else if frames.len() > 1 && frames.last().unwrap().then.is_none() => {
+                    let frame = frames.last_mut().unwrap();
+                    frame.then = Some(mem::take(&mut frame.nodes));
+                },
+                Op::End if frames.len() > 1 => {
+                    let node = close(frames.pop().unwrap());
+                    frames.last_mut().unwrap().nodes.push(node);
+                },
+                _ => {
+                    let (pops, pushes) = self.effect(op, &frames);
+                    let nodes = &mut frames.last_mut().unwrap().nodes;
+                    let operands = take_operands(nodes, pops);
+                    nodes.push(Node {
+                        op,
+                        operands,
+                        body: vec![],
+                        alternative: None,
+                        results: pushes,
+                    });
+                },
+            }
+        }
+        // Blocks left open by a truncated body.
+        while frames.len() > 1 {
+            let node = close(frames.pop().unwrap());
+            frames.last_mut().unwrap().nodes.push(node);
+        }
+        frames.pop().unwrap().nodes
+    }
+
+    fn node(&mut self, node: &Node, indent: usize, function: usize) -> fmt::Result {
+        self.line(indent)?;
+        self.out.write_char('(')?;
+        self.instruction(node.op, Some(function))?;
+        for operand in &node.operands {
+            self.node(operand, indent + 1, function)?;
+        }
+        match *node.op {
+            Op::If(_) => {
+                self.line(indent + 1)?;
+                self.out.write_str("(then")?;
+                for child in &node.body {
+                    self.node(child, indent + 2, function)?;
+                }
+                self.out.write_char(')')?;
+                if let Some(ref alternative) = node.alternative {
+                    self.line(indent + 1)?;
+                    self.out.write_str("(else")?;
+                    for child in alternative {
+                        self.node(child, indent + 2, function)?;
+                    }
+                    self.out.write_char(')')?;
+                }
+            },
+            _ => {
+                for child in &node.body {
+                    self.node(child, indent + 1, function)?;
+                }
+            },
+        }
+        self.out.write_char(')')
+    }
+}
+
+/// Takes up to `pops` instructions that each leave one value from the end
+/// of `nodes`, stopping at the first that does not.
+fn take_operands<'m>(nodes: &mut Vec<Node<'m>>, pops: usize) -> Vec<Node<'m>> {
+    let count = nodes.iter().rev().take(pops).take_while(|node| node.results == 1).count();
+    let len = nodes.len();
+    nodes.split_off(len - count)
+}
+
+/// Turns a block, loop or if that has reached its `end` into a node.
+fn close<'m>(frame: Frame<'m>) -> Node<'m> {
+    let mut node = frame.node.expect("closing the function body");
+    match frame.then {
+        Some(then) => {
+            node.body = then;
+            node.alternative = Some(frame.nodes);
+        },
+        None => node.body = frame.nodes,
+    }
+    node
+}
+
+impl Module {
+    /// Prints the module in the text format. See `wat::print`.
+    pub fn to_wat(&self, style: Style) -> String {
+        print(self, style)
+    }
+}
@@ -0,0 +1,231 @@
+//! Serializes modules back into the binary format.
+//!
+//! `encode` writes a whole `ast::Module`, and `write_section` one of its
+//! sections. The lower-level pieces they are built from are public as
+//! well, for generating a module directly without building a tree first:
+//! `SectionWriter` and `BodyWriter` take care of size prefixes,
+//! `OpEncoder` writes ops, and the other `write_*` functions write the
+//! entries the iterators in `reader` read.
+
+use ast;
+use reader::{SectionType, ImportEntryContents, GlobalEntry};
+use alloc::vec::Vec;
+
+pub mod primitives;
+pub use self::primitives::{
+    write_varuint, write_varint, write_u32, write_u64, write_name, write_vec,
+    write_limits, write_value_type, write_function_type, write_import_entry,
+    write_table_entry, write_global_entry, write_export_entry, write_local
+};
+
+pub mod ops;
+pub use self::ops::OpEncoder;
+
+pub mod sections;
+pub use self::sections::{
+    SectionWriter, BodyWriter
+};
+
+/// The order known sections have to appear in.
+const SECTION_ORDER: [SectionType; 13] = [
+    SectionType::Type,
+    SectionType::Import,
+    SectionType::Function,
+    SectionType::Table,
+    SectionType::Memory,
+    SectionType::Tag,
+    SectionType::Global,
+    SectionType::Export,
+    SectionType::Start,
+    SectionType::Element,
+    SectionType::DataCount,
+    SectionType::Code,
+    SectionType::Data,
+];
+
+/// Returns whether the module has anything to put in the given section.
+fn has_section(module: &ast::Module, id: SectionType) -> bool {
+    match id {
+        SectionType::Type => !module.types.is_empty(),
+        SectionType::Import => !module.imports.is_empty(),
+        SectionType::Function | SectionType::Code => !module.functions.is_empty(),
+        SectionType::Table => !module.tables.is_empty(),
+        SectionType::Memory => !module.memories.is_empty(),
+        SectionType::Tag => !module.tags.is_empty(),
+        SectionType::Global => !module.globals.is_empty(),
+        SectionType::Export => !module.exports.is_empty(),
+        SectionType::Start => module.start.is_some(),
+        SectionType::Element => !module.elements.is_empty(),
+        SectionType::DataCount => module.data_count.is_some(),
+        SectionType::Data => !module.data.is_empty(),
+        SectionType::Named => false,
+    }
+}
+
+/// Writes the known section `id` of `module` from its contents, without
+/// looking at `module.encoding`.
+///
+/// # Panics
+///
+/// If `id` is a custom section, or Start or DataCount and the module has
+/// nothing to put in it.
+pub fn write_section(out: &mut Vec<u8>, module: &ast::Module, id: SectionType) {
+    let mut section = SectionWriter::new(out, id);
+    match id {
+        SectionType::Type => {
+            write_vec(&mut section, &module.types, |out, ty| {
+                write_function_type(out, &ty.params, ty.return_type)
+            });
+        },
+        SectionType::Import => {
+            write_vec(&mut section, &module.imports, |out, import| {
+                let contents = match import.kind {
+                    ast::ImportKind::Function(ty) => ImportEntryContents::Function(ty),
+                    ast::ImportKind::Table(ref table) => ImportEntryContents::Table {
+                        element_type: table.element_type,
+                        limits: table.limits.clone(),
+                    },
+                    ast::ImportKind::Memory(ref memory) => ImportEntryContents::Memory(
+                        memory.limits.clone()
+                    ),
+                    ast::ImportKind::Global(ty) => ImportEntryContents::Global {
+                        ty: ty.ty,
+                        mutable: ty.mutable,
+                    },
+                };
+                write_import_entry(out, &import.module, &import.field, &contents)
+            });
+        },
+        SectionType::Function => {
+            write_vec(&mut section, &module.functions, |out, function| {
+                write_varuint(out, function.ty as u64)
+            });
+        },
+        SectionType::Table => {
+            write_vec(&mut section, &module.tables, |out, table| {
+                write_table_entry(out, table.element_type, &table.limits)
+            });
+        },
+        SectionType::Memory => {
+            write_vec(&mut section, &module.memories, |out, memory| {
+                write_limits(out, &memory.limits)
+            });
+        },
+        SectionType::Tag => {
+            write_vec(&mut section, &module.tags, |out, &ty| {
+                // The attribute of an exception.
+                out.push(0);
+                write_varuint(out, ty as u64)
+            });
+        },
+        SectionType::Global => {
+            write_vec(&mut section, &module.globals, |out, global| {
+                write_global_entry(out, &GlobalEntry {
+                    ty: global.ty.ty,
+                    mutable: global.ty.mutable,
+                });
+                OpEncoder::new(out).write_all(&global.init);
+            });
+        },
+        SectionType::Export => {
+            write_vec(&mut section, &module.exports, |out, export| {
+                write_export_entry(out, &export.field, export.kind, export.index)
+            });
+        },
+        SectionType::Start => {
+            let index = module.start.expect("module has no start function");
+            write_varuint(&mut section, index as u64);
+        },
+        SectionType::Element => {
+            write_vec(&mut section, &module.elements, |out, element| {
+                write_varuint(out, element.index as u64);
+                OpEncoder::new(out).write_all(&element.offset);
+                write_vec(out, &element.elems, |out, &elem| write_varuint(out, elem as u64));
+            });
+        },
+        SectionType::DataCount => {
+            let count = module.data_count.expect("module has no data count");
+            write_varuint(&mut section, count as u64);
+        },
+        SectionType::Code => {
+            write_vec(&mut section, &module.functions, |out, function| {
+                let mut body = BodyWriter::new(out, &function.locals);
+                body.ops().write_all(&function.body);
+            });
+        },
+        SectionType::Data => {
+            write_vec(&mut section, &module.data, |out, data| {
+                write_varuint(out, data.index as u64);
+                OpEncoder::new(out).write_all(&data.offset);
+                write_varuint(out, data.data.len() as u64);
+                out.extend_from_slice(&data.data);
+            });
+        },
+        SectionType::Named => unreachable!(),
+    }
+}
+
+pub fn write_custom_section(out: &mut Vec<u8>, custom: &ast::CustomSection) {
+    let mut section = SectionWriter::custom(out, &custom.name);
+    section.extend_from_slice(&custom.payload);
+}
+
+/// Writes a section with `write`, but replaces it with the section it was
+/// decoded from, as recorded in `recorded`, if `write` produced the same
+/// bytes the decoded contents did, which means it was not modified.
+fn write_recorded<F>(out: &mut Vec<u8>, recorded: Option<&ast::SectionEncoding>, write: F)
+    where F: FnOnce(&mut Vec<u8>)
+{
+    let start = out.len();
+    write(out);
+    if let Some(original) = recorded.and_then(|recorded| recorded.original_of(&out[start..])) {
+        out.truncate(start);
+        out.extend_from_slice(original);
+    }
+}
+
+/// Encodes `module` into the binary format, using the version it was read
+/// with.
+///
+/// Known sections are written in the order the format requires, if they
+/// have any contents or were in the binary the module was decoded from.
+/// Each custom section is written right after the section it originally
+/// followed. Integers are written in their shortest encoding, except in
+/// sections recorded in `module.encoding` that are unchanged since the
+/// module was decoded, which are written back as they were. Decoding a
+/// binary and encoding it again reproduces it byte for byte, as long as
+/// its known sections are in order and appear once each.
+pub fn encode(module: &ast::Module) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(b"\0asm");
+    write_u32(&mut out, module.version);
+    // The recorded custom sections, in order, and the first recorded
+    // section of each known id.
+    let mut customs = vec![];
+    let mut known = [None; 256];
+    for section in &module.encoding {
+        if section.id == SectionType::Named {
+            customs.push(section);
+        } else if known[section.id as usize].is_none() {
+            known[section.id as usize] = Some(section);
+        }
+    }
+    let write_customs = |out: &mut Vec<u8>, after: Option<SectionType>| {
+        for (nth, custom) in module.custom_sections.iter().enumerate() {
+            if custom.after == after {
+                write_recorded(out, customs.get(nth).cloned(), |out| write_custom_section(out, custom));
+            }
+        }
+    };
+    write_customs(&mut out, None);
+    for &id in SECTION_ORDER.iter() {
+        let recorded = known[id as usize];
+        // Start and DataCount cannot be empty.
+        let empty = recorded.is_some() && id != SectionType::Start && id != SectionType::DataCount;
+        if has_section(module, id) || empty {
+            write_recorded(&mut out, recorded, |out| write_section(out, module, id));
+        }
+        write_customs(&mut out, Some(id));
+    }
+    out
+}
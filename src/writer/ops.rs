@@ -0,0 +1,91 @@
+use super::*;
+use reader::bytecode::{Op, MemoryImmediate};
+use alloc::vec::Vec;
+
+/// Encodes ops, with their immediates, in the form `OpIterator` reads.
+pub struct OpEncoder<'w> {
+    out: &'w mut Vec<u8>,
+}
+
+/// Writes `$value`, a reference to an immediate of kind `$kind`.
+macro_rules! imm_write {
+    (BlockType, $out:ident, $value:expr) => {
+        $out.push(*$value as u8)
+    };
+    (LabelIndex, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (LabelTable, $out:ident, $value:expr) => {{
+        let table = $value;
+        write_varuint($out, table.count as u64);
+        for arm in table.arms() {
+            write_varuint($out, arm.expect("BrTable holds malformed arms") as u64);
+        }
+        write_varuint($out, table.default as u64);
+    }};
+    (FunctionIndex, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (TypeIndex, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (LocalIndex, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (GlobalIndex, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (Memory, $out:ident, $value:expr) => {{
+        let imm: &MemoryImmediate = $value;
+        write_varuint($out, imm.flags as u64);
+        write_varuint($out, imm.offset as u64);
+    }};
+    (Reserved, $out:ident, $value:expr) => {
+        write_varuint($out, *$value as u64)
+    };
+    (I32, $out:ident, $value:expr) => {
+        write_varint($out, *$value as i64)
+    };
+    (I64, $out:ident, $value:expr) => {
+        write_varint($out, *$value)
+    };
+    (F32, $out:ident, $value:expr) => {
+        write_u32($out, $value.to_bits())
+    };
+    (F64, $out:ident, $value:expr) => {
+        write_u64($out, $value.to_bits())
+    };
+}
+
+macro_rules! op_encoder {
+    ($($(#[$attr:meta])* $name:ident $(($($arg:ident: $kind:ident),+))* = $code:literal
+       => $visit:ident, $mnemonic:literal, $category:ident, $effect:tt;)+) => {
+        impl<'w> OpEncoder<'w> {
+            pub fn write(&mut self, op: &Op) {
+                let out = &mut *self.out;
+                out.push(op.code() as u8);
+                match *op {$(
+                    Op::$name $(($(ref $arg),+))* => {
+                        $($(imm_write!($kind, out, $arg);)+)*
+                    }
+                ),+}
+            }
+        }
+    }
+}
+
+for_each_operator!(op_encoder);
+
+impl<'w> OpEncoder<'w> {
+    pub fn new(out: &'w mut Vec<u8>) -> OpEncoder<'w> {
+        OpEncoder {
+            out,
+        }
+    }
+
+    pub fn write_all(&mut self, ops: &[Op]) {
+        for op in ops {
+            self.write(op);
+        }
+    }
+}
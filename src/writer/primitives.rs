@@ -0,0 +1,139 @@
+use reader::{ValueType, ExternalKind, ResizableLimits, ImportEntryContents, GlobalEntry, Local};
+use alloc::vec::Vec;
+
+/// Writes an unsigned LEB128 integer in its shortest form.
+pub fn write_varuint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Writes a signed LEB128 integer in its shortest form.
+pub fn write_varint(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub fn write_u32(out: &mut Vec<u8>, value: u32) {
+    for i in 0..4 {
+        out.push((value >> (i * 8)) as u8);
+    }
+}
+
+pub fn write_u64(out: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        out.push((value >> (i * 8)) as u8);
+    }
+}
+
+/// Writes a length-prefixed UTF-8 string.
+pub fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_varuint(out, name.len() as u64);
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Writes the number of items followed by each item.
+pub fn write_vec<T, F>(out: &mut Vec<u8>, items: &[T], mut write: F)
+    where F: FnMut(&mut Vec<u8>, &T)
+{
+    write_varuint(out, items.len() as u64);
+    for item in items {
+        write(out, item);
+    }
+}
+
+pub fn write_limits(out: &mut Vec<u8>, limits: &ResizableLimits) {
+    let shared = if limits.shared { 2 } else { 0 };
+    match limits.maximum {
+        Some(maximum) => {
+            write_varuint(out, shared | 1);
+            write_varuint(out, limits.initial as u64);
+            write_varuint(out, maximum as u64);
+        },
+        None => {
+            write_varuint(out, shared);
+            write_varuint(out, limits.initial as u64);
+        },
+    }
+}
+
+pub fn write_value_type(out: &mut Vec<u8>, ty: ValueType) {
+    out.push(ty as u8);
+}
+
+/// Writes an entry of the type section, as read by `TypeEntryIterator`.
+pub fn write_function_type(out: &mut Vec<u8>, params: &[ValueType], return_type: Option<ValueType>) {
+    out.push(0x60);
+    write_vec(out, params, |out, &param| write_value_type(out, param));
+    match return_type {
+        Some(ty) => {
+            write_varuint(out, 1);
+            write_value_type(out, ty);
+        },
+        None => write_varuint(out, 0),
+    }
+}
+
+/// Writes an entry of the import section, as read by `ImportEntryIterator`.
+pub fn write_import_entry(out: &mut Vec<u8>, module: &str, field: &str, contents: &ImportEntryContents) {
+    write_name(out, module);
+    write_name(out, field);
+    match *contents {
+        ImportEntryContents::Function(ty) => {
+            out.push(ExternalKind::Function as u8);
+            write_varuint(out, ty as u64);
+        },
+        ImportEntryContents::Table { element_type, ref limits } => {
+            out.push(ExternalKind::Table as u8);
+            write_table_entry(out, element_type, limits);
+        },
+        ImportEntryContents::Memory(ref limits) => {
+            out.push(ExternalKind::Memory as u8);
+            write_limits(out, limits);
+        },
+        ImportEntryContents::Global { ty, mutable } => {
+            out.push(ExternalKind::Global as u8);
+            write_value_type(out, ty);
+            write_varuint(out, mutable as u64);
+        },
+    }
+}
+
+/// Writes an entry of the table section, as read by `TableEntryIterator`.
+pub fn write_table_entry(out: &mut Vec<u8>, element_type: u8, limits: &ResizableLimits) {
+    out.push(element_type);
+    write_limits(out, limits);
+}
+
+/// Writes the type of a global. Its initializer expression has to follow.
+pub fn write_global_entry(out: &mut Vec<u8>, entry: &GlobalEntry) {
+    write_value_type(out, entry.ty);
+    write_varuint(out, entry.mutable as u64);
+}
+
+/// Writes an entry of the export section, as read by `ExportEntryIterator`.
+pub fn write_export_entry(out: &mut Vec<u8>, field: &str, kind: ExternalKind, index: u32) {
+    write_name(out, field);
+    out.push(kind as u8);
+    write_varuint(out, index as u64);
+}
+
+/// Writes a run of locals at the start of a function body.
+pub fn write_local(out: &mut Vec<u8>, local: &Local) {
+    write_varuint(out, local.count as u64);
+    write_value_type(out, local.ty);
+}
@@ -0,0 +1,111 @@
+use super::*;
+use reader::{SectionType, Local};
+use core::ops::{Deref, DerefMut};
+use alloc::vec::Vec;
+
+/// Writes a section whose size is not known up front.
+///
+/// The section's contents are written through the `Vec` this derefs to.
+/// When the writer is dropped, or `finish` is called, the size is
+/// inserted in front of the contents in its shortest encoding.
+pub struct SectionWriter<'w> {
+    out: &'w mut Vec<u8>,
+    start: usize,
+}
+
+/// Writes a function body in the code section, which is prefixed by its
+/// size just like a section is.
+pub struct BodyWriter<'w> {
+    out: &'w mut Vec<u8>,
+    start: usize,
+}
+
+/// Inserts the number of bytes written since `start` in front of them.
+fn patch_size(out: &mut Vec<u8>, start: usize) {
+    let mut size = vec![];
+    write_varuint(&mut size, (out.len() - start) as u64);
+    out.splice(start..start, size);
+}
+
+impl<'w> SectionWriter<'w> {
+    pub fn new(out: &'w mut Vec<u8>, id: SectionType) -> SectionWriter<'w> {
+        out.push(id as u8);
+        let start = out.len();
+        SectionWriter {
+            out,
+            start,
+        }
+    }
+
+    /// Starts a custom section with the given name.
+    pub fn custom(out: &'w mut Vec<u8>, name: &str) -> SectionWriter<'w> {
+        let mut section = SectionWriter::new(out, SectionType::Named);
+        write_name(&mut section, name);
+        section
+    }
+
+    pub fn ops(&mut self) -> OpEncoder<'_> {
+        OpEncoder::new(self.out)
+    }
+
+    pub fn finish(self) {}
+}
+
+impl<'w> Deref for SectionWriter<'w> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        self.out
+    }
+}
+
+impl<'w> DerefMut for SectionWriter<'w> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        self.out
+    }
+}
+
+impl<'w> Drop for SectionWriter<'w> {
+    fn drop(&mut self) {
+        patch_size(self.out, self.start);
+    }
+}
+
+impl<'w> BodyWriter<'w> {
+    /// Starts a function body with the given runs of locals. The ops
+    /// follow, ending with `End`.
+    pub fn new(out: &'w mut Vec<u8>, locals: &[Local]) -> BodyWriter<'w> {
+        let start = out.len();
+        write_vec(out, locals, write_local);
+        BodyWriter {
+            out,
+            start,
+        }
+    }
+
+    pub fn ops(&mut self) -> OpEncoder<'_> {
+        OpEncoder::new(self.out)
+    }
+
+    pub fn finish(self) {}
+}
+
+impl<'w> Deref for BodyWriter<'w> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        self.out
+    }
+}
+
+impl<'w> DerefMut for BodyWriter<'w> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        self.out
+    }
+}
+
+impl<'w> Drop for BodyWriter<'w> {
+    fn drop(&mut self) {
+        patch_size(self.out, self.start);
+    }
+}
@@ -0,0 +1,252 @@
+#![cfg(feature = "std")]
+
+extern crate wasm;
+
+use wasm::ast;
+use wasm::builder::{ModuleBuilder, FunctionBuilder, BuildError, ExternHandle, GlobalHandle, Instr};
+use wasm::reader::{ValueType, InlineSignatureType, Local};
+use wasm::reader::bytecode::{Op, BrTable};
+
+fn end() -> Vec<Instr> {
+    vec![Op::End.into()]
+}
+
+#[test]
+fn imported_function_cannot_be_defined() {
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    let imported = builder.import_function("env", "f", ty);
+    assert_eq!(builder.define_function(imported, vec![], end()), Err(BuildError::Imported(imported)));
+}
+
+#[test]
+fn handles_from_another_builder_are_errors() {
+    let mut other = ModuleBuilder::new();
+    let ty = other.func_type(&[], None);
+    other.declare_function(ty);
+    let foreign = other.declare_function(ty);
+
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    let unknown = || BuildError::UnknownHandle(ExternHandle::Function(foreign));
+    assert_eq!(builder.define_function(foreign, vec![], end()), Err(unknown()));
+    builder.function(ty, vec![], vec![Instr::Call(foreign), Op::End.into()]);
+    assert_eq!(builder.finish().err(), Some(unknown()));
+}
+
+/// Adds an immutable `i32` global initialized to zero.
+fn global(builder: &mut ModuleBuilder) -> GlobalHandle {
+    let ty = ast::GlobalType {
+        ty: ValueType::I32,
+        mutable: false,
+    };
+    builder.global(ty, vec![Op::I32Const(0).into(), Op::End.into()])
+}
+
+#[test]
+fn handles_in_range_from_another_builder_are_errors() {
+    let mut other = ModuleBuilder::new();
+    let ty = other.func_type(&[], None);
+    let foreign = other.declare_function(ty);
+    let foreign_global = global(&mut other);
+
+    // Each builder has a function 0 and a global 0.
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    let own = builder.declare_function(ty);
+    global(&mut builder);
+    assert_eq!(builder.define_function(foreign, vec![], end()), Err(BuildError::UnknownHandle(foreign.into())));
+    builder.define_function(own, vec![], end()).unwrap();
+    builder.export("f", foreign);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownHandle(foreign.into())));
+
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    builder.function(ty, vec![], end());
+    builder.start(foreign);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownHandle(foreign.into())));
+
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    global(&mut builder);
+    builder.function(ty, vec![], vec![Instr::GetGlobal(foreign_global), Op::Drop.into(), Op::End.into()]);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownHandle(foreign_global.into())));
+}
+
+#[test]
+fn type_handles_from_another_builder_are_errors() {
+    let mut other = ModuleBuilder::new();
+    let foreign = other.func_type(&[], None);
+
+    let mut builder = ModuleBuilder::new();
+    builder.func_type(&[], None);
+    builder.import_function("env", "f", foreign);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownType(foreign)));
+
+    let mut builder = ModuleBuilder::new();
+    builder.func_type(&[], None);
+    builder.function(foreign, vec![], end());
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownType(foreign)));
+
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    builder.function(ty, vec![], vec![Op::I32Const(0).into(), Instr::CallIndirect(foreign), Op::End.into()]);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnknownType(foreign)));
+}
+
+#[test]
+fn start_function_has_no_params_or_results() {
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[ValueType::I32], None);
+    let imported = builder.import_function("env", "f", ty);
+    builder.start(imported);
+    assert_eq!(builder.finish().err(), Some(BuildError::InvalidStart(imported)));
+
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    let start = builder.function(ty, vec![], end());
+    builder.start(start);
+    assert_eq!(builder.finish().unwrap().start, Some(0));
+}
+
+#[test]
+fn indices_count_imports_added_last() {
+    let mut builder = ModuleBuilder::new();
+    let ty = builder.func_type(&[], None);
+    let defined = builder.function(ty, vec![], end());
+    let imported = builder.import_function("env", "f", ty);
+    builder.export("f", defined);
+    let (module, indices) = builder.finish_with_indices().unwrap();
+    assert_eq!(indices.function(imported), Some(0));
+    assert_eq!(indices.function(defined), Some(1));
+    assert_eq!(module.exports[0].index, 1);
+}
+
+/// The ops of a finished function body.
+fn ops(body: Vec<Instr>) -> Vec<Op<'static>> {
+    body.into_iter().map(|instr| match instr {
+        Instr::Op(op) => op,
+        instr => panic!("unresolved {:?}", instr),
+    }).collect()
+}
+
+#[test]
+fn labels_resolve_to_relative_depths() {
+    let empty = InlineSignatureType::Empty;
+    let mut function = FunctionBuilder::new(&[]);
+    let outer = function.block(empty);
+    let looped = function.loop_(empty);
+    function.instr(Op::I32Const(0));
+    function.if_(empty);
+    function.br(outer);
+    function.else_();
+    function.br_if(looped);
+    function.end();
+    function.instr(Op::I32Const(0));
+    function.br_table(&[looped, outer], outer);
+    function.end();
+    function.end();
+    let (_, body) = function.finish().unwrap();
+    assert_eq!(ops(body), vec![
+        Op::Block(empty),
+        Op::Loop(empty),
+        Op::I32Const(0),
+        Op::If(empty),
+        Op::Br(2),
+        Op::Else,
+        Op::BrIf(1),
+        Op::End,
+        Op::I32Const(0),
+        Op::BrTable(BrTable::new(&[0, 1], 1)),
+        Op::End,
+        Op::End,
+        Op::End,
+    ]);
+}
+
+#[test]
+fn locals_are_grouped_by_type() {
+    let mut function = FunctionBuilder::new(&[ValueType::F32]);
+    let param = function.param(0).unwrap();
+    let a = function.local(ValueType::I32);
+    let b = function.local(ValueType::I64);
+    let c = function.local(ValueType::I32);
+    for &local in &[c, b, a, param] {
+        function.get_local(local);
+        function.instr(Op::Drop);
+    }
+    // Raw indices count the locals in the order they were allocated, so
+    // this is `c` as well.
+    function.instr(Op::I32Const(0));
+    function.instr(Op::SetLocal(3));
+    let (locals, body) = function.finish().unwrap();
+    assert_eq!(locals, vec![
+        Local {
+            count: 2,
+            ty: ValueType::I32,
+        },
+        Local {
+            count: 1,
+            ty: ValueType::I64,
+        },
+    ]);
+    assert_eq!(ops(body), vec![
+        Op::GetLocal(2), Op::Drop,
+        Op::GetLocal(3), Op::Drop,
+        Op::GetLocal(1), Op::Drop,
+        Op::GetLocal(0), Op::Drop,
+        Op::I32Const(0), Op::SetLocal(2),
+        Op::End,
+    ]);
+}
+
+#[test]
+fn scopes_have_to_be_closed() {
+    let mut function = FunctionBuilder::new(&[]);
+    function.block(InlineSignatureType::Empty);
+    function.loop_(InlineSignatureType::Empty);
+    function.end();
+    function.loop_(InlineSignatureType::Empty);
+    assert_eq!(function.finish().err(), Some(BuildError::UnclosedScope(2)));
+
+    let mut function = FunctionBuilder::new(&[]);
+    function.end();
+    assert_eq!(function.finish().err(), Some(BuildError::UnexpectedEnd));
+
+    let mut function = FunctionBuilder::new(&[]);
+    function.block(InlineSignatureType::Empty);
+    function.else_();
+    function.end();
+    assert_eq!(function.finish().err(), Some(BuildError::UnexpectedElse));
+}
+
+#[test]
+fn labels_and_locals_from_elsewhere_are_errors() {
+    let mut function = FunctionBuilder::new(&[ValueType::I32]);
+    assert_eq!(function.param(1).err(), Some(BuildError::UnknownParam(1)));
+    let label = function.block(InlineSignatureType::Empty);
+    function.end();
+    function.br(label);
+    assert_eq!(function.finish().err(), Some(BuildError::LabelOutOfScope(label)));
+
+    // The other function has a label and a local with the same numbers.
+    let mut other = FunctionBuilder::new(&[ValueType::I32]);
+    let foreign_label = other.block(InlineSignatureType::Empty);
+    let foreign_local = other.local(ValueType::I32);
+    let mut function = FunctionBuilder::new(&[ValueType::I32]);
+    function.block(InlineSignatureType::Empty);
+    function.br(foreign_label);
+    function.end();
+    assert_eq!(function.finish().err(), Some(BuildError::LabelOutOfScope(foreign_label)));
+
+    let mut function = FunctionBuilder::new(&[ValueType::I32]);
+    function.local(ValueType::I32);
+    function.get_local(foreign_local);
+    function.instr(Op::Drop);
+    assert_eq!(function.finish().err(), Some(BuildError::UnknownLocal(foreign_local)));
+
+    let mut function = FunctionBuilder::new(&[ValueType::I32]);
+    function.instr(Op::GetLocal(1));
+    function.instr(Op::Drop);
+    assert!(matches!(function.finish(), Err(BuildError::UnknownLocal(_))));
+}
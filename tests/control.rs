@@ -0,0 +1,160 @@
+extern crate wasm;
+
+use wasm::reader::{ScopedOps, ControlTree, Node, FrameKind, InlineSignatureType, Error};
+use wasm::reader::bytecode::{Op, BrTable};
+
+const EMPTY: InlineSignatureType = InlineSignatureType::Empty;
+
+fn scoped(ops: Vec<Op<'static>>) -> Vec<Result<(usize, FrameKind, Op<'static>), Error>> {
+    ScopedOps::new(ops.into_iter().map(Ok))
+        .map(|op| op.map(|op| (op.depth, op.frame, op.op)))
+        .collect()
+}
+
+fn tree(ops: Vec<Op<'static>>) -> Result<ControlTree<'static>, Error> {
+    ControlTree::build(ops.into_iter().map(Ok))
+}
+
+#[test]
+fn else_and_end_belong_to_the_frame_they_close() {
+    let ops = scoped(vec![
+        Op::Block(EMPTY),
+        Op::If(EMPTY),
+        Op::Nop,
+        Op::Else,
+        Op::Drop,
+        Op::End,
+        Op::End,
+        Op::End,
+    ]);
+    let ops = ops.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(ops, vec![
+        (0, FrameKind::Function, Op::Block(EMPTY)),
+        (1, FrameKind::Block, Op::If(EMPTY)),
+        (2, FrameKind::If, Op::Nop),
+        (2, FrameKind::If, Op::Else),
+        (2, FrameKind::Else, Op::Drop),
+        (2, FrameKind::Else, Op::End),
+        (1, FrameKind::Block, Op::End),
+        (0, FrameKind::Function, Op::End),
+    ]);
+}
+
+#[test]
+fn scoped_ops_reject_else_outside_an_if() {
+    let ops = scoped(vec![Op::Block(EMPTY), Op::Else]);
+    assert!(ops[0].is_ok());
+    match ops[1] {
+        Err(Error::Malformed(_)) => (),
+        ref op => panic!("{:?}", op),
+    }
+}
+
+#[test]
+fn nested_ifs_with_and_without_else() {
+    let tree = tree(vec![
+        Op::If(EMPTY),
+        Op::If(EMPTY),
+        Op::Nop,
+        Op::End,
+        Op::Else,
+        Op::If(InlineSignatureType::I32),
+        Op::I32Const(1),
+        Op::Else,
+        Op::I32Const(2),
+        Op::End,
+        Op::Drop,
+        Op::End,
+        Op::End,
+    ]).unwrap();
+    assert_eq!(tree.body, vec![Node::If {
+        id: 1,
+        ty: EMPTY,
+        then: vec![Node::If {
+            id: 2,
+            ty: EMPTY,
+            then: vec![Node::Op(Op::Nop)],
+            else_: None,
+        }],
+        else_: Some(vec![
+            Node::If {
+                id: 3,
+                ty: InlineSignatureType::I32,
+                then: vec![Node::Op(Op::I32Const(1))],
+                else_: Some(vec![Node::Op(Op::I32Const(2))]),
+            },
+            Node::Op(Op::Drop),
+        ]),
+    }]);
+    assert_eq!(tree.frames, vec![FrameKind::Function, FrameKind::If, FrameKind::If, FrameKind::If]);
+}
+
+#[test]
+fn branch_targets_resolve_to_block_ids() {
+    let table = Op::BrTable(BrTable::new(&[0, 1, 2], 1));
+    let tree = tree(vec![
+        Op::Block(EMPTY),
+        Op::Loop(EMPTY),
+        Op::I32Const(0),
+        table.clone(),
+        Op::End,
+        Op::I32Const(0),
+        Op::BrIf(0),
+        Op::End,
+        Op::Br(0),
+        Op::End,
+    ]).unwrap();
+    assert_eq!(tree.body, vec![
+        Node::Block {
+            id: 1,
+            ty: EMPTY,
+            body: vec![
+                Node::Loop {
+                    id: 2,
+                    ty: EMPTY,
+                    body: vec![
+                        Node::Op(Op::I32Const(0)),
+                        Node::Branch {
+                            op: table,
+                            targets: vec![2, 1, 0, 1],
+                        },
+                    ],
+                },
+                Node::Op(Op::I32Const(0)),
+                Node::Branch {
+                    op: Op::BrIf(0),
+                    targets: vec![1],
+                },
+            ],
+        },
+        Node::Branch {
+            op: Op::Br(0),
+            targets: vec![0],
+        },
+    ]);
+    assert_eq!(tree.frames, vec![FrameKind::Function, FrameKind::Block, FrameKind::Loop]);
+}
+
+#[test]
+fn control_tree_errors() {
+    let errors = vec![
+        // Else in a block and in the function body.
+        vec![Op::Block(EMPTY), Op::Else, Op::End, Op::End],
+        vec![Op::Else, Op::End],
+        // A second else.
+        vec![Op::If(EMPTY), Op::Else, Op::Else, Op::End, Op::End],
+        // Branches past the function body.
+        vec![Op::Block(EMPTY), Op::Br(2), Op::End, Op::End],
+        vec![Op::BrTable(BrTable::new(&[0], 1)), Op::End],
+    ];
+    for ops in errors {
+        match tree(ops.clone()) {
+            Err(Error::Malformed(_)) => (),
+            result => panic!("{:?}: {:?}", ops, result),
+        }
+    }
+    match tree(vec![Op::Block(EMPTY), Op::End]) {
+        Err(Error::UnexpectedEof) => (),
+        result => panic!("{:?}", result),
+    }
+}
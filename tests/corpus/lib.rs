@@ -0,0 +1,60 @@
+//! Decodes, validates, prints and re-encodes a module, to give the tests
+//! a module built by rustc.
+
+#![no_std]
+
+extern crate alloc;
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use wasm::reader::Module;
+use wasm::wat::Style;
+
+/// Hands out memory from the end of the heap and never frees it.
+struct Bump(UnsafeCell<usize>);
+
+unsafe impl Sync for Bump {}
+
+unsafe impl GlobalAlloc for Bump {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let next = &mut *self.0.get();
+        if *next == 0 {
+            *next = core::arch::wasm32::memory_size(0) * 65536;
+        }
+        let start = (*next + layout.align() - 1) & !(layout.align() - 1);
+        let end = start + layout.size();
+        let have = core::arch::wasm32::memory_size(0) * 65536;
+        if end > have && core::arch::wasm32::memory_grow(0, (end - have + 65535) / 65536) == usize::MAX {
+            return core::ptr::null_mut();
+        }
+        *next = end;
+        start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+#[global_allocator]
+static ALLOCATOR: Bump = Bump(UnsafeCell::new(0));
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[no_mangle]
+pub extern "C" fn process(ptr: *const u8, len: usize) -> usize {
+    let bytes = unsafe { core::slice::from_raw_parts(ptr, len) };
+    let module = match Module::new(bytes) {
+        Ok(module) => module,
+        Err(_) => return 0,
+    };
+    if module.validate().is_err() {
+        return 1;
+    }
+    let features = module.detect_features().map(|report| report.uses.len()).unwrap_or(0);
+    match module.decode() {
+        Ok(ast) => ast.to_wat(Style::Folded).len() + ast.encode().len() + features,
+        Err(_) => 2,
+    }
+}
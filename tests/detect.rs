@@ -0,0 +1,125 @@
+extern crate wasm;
+
+use wasm::detect::FeatureReport;
+use wasm::reader::{Module, ParserConfig, WasmFeatures};
+use wasm::writer::write_varuint;
+
+/// A module of the given sections, each an id and its contents.
+fn module(sections: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+    for &(id, contents) in sections {
+        bytes.push(id);
+        write_varuint(&mut bytes, contents.len() as u64);
+        bytes.extend_from_slice(contents);
+    }
+    bytes
+}
+
+fn detect(bytes: &[u8]) -> FeatureReport {
+    let config = ParserConfig {
+        features: WasmFeatures::all(),
+        ..ParserConfig::default()
+    };
+    Module::with_config(bytes, config).unwrap().detect_features().unwrap()
+}
+
+fn features(report: &FeatureReport) -> Vec<&'static str> {
+    report.uses.iter().map(|used| used.feature).collect()
+}
+
+/// A function of type `[] -> []` with the given body.
+fn function(body: &[u8]) -> Vec<u8> {
+    let mut code = vec![1];
+    write_varuint(&mut code, body.len() as u64 + 1);
+    code.push(0);
+    code.extend_from_slice(body);
+    module(&[(1, b"\x01\x60\x00\x00"), (3, b"\x01\x00"), (10, &code)])
+}
+
+#[test]
+fn entries_after_a_proposal_are_scanned() {
+    let bytes = module(&[
+        // A `v128` global, then a mutable `i32` one.
+        (2, b"\x02\x01m\x01a\x03\x7b\x00\x01m\x01b\x03\x7f\x01"),
+        // The export of the second.
+        (7, b"\x01\x01b\x03\x01"),
+    ]);
+    let report = detect(&bytes);
+    assert_eq!(features(&report), ["simd", "mutable-global"]);
+    // The import of the mutable global, which comes before its export.
+    assert_eq!(report.uses[1].offset, 22);
+
+    let bytes = module(&[
+        (2, b"\x01\x01m\x01a\x03\x7b\x00"),
+        (6, b"\x01\x7f\x01\x41\x00\x0b"),
+        // The export of the defined global, index 1.
+        (7, b"\x01\x01b\x03\x01"),
+    ]);
+    assert_eq!(features(&detect(&bytes)), ["simd", "mutable-global"]);
+}
+
+#[test]
+fn operators_after_simd_threads_and_exceptions_are_scanned() {
+    // `v128.const 0; drop`, `i32.const 0; i32.atomic.load; drop`,
+    // `try; catch_all; end`, then `i32.const 0; i32.extend8_s; drop`.
+    let mut body = vec![0xfd, 0x0c];
+    body.extend_from_slice(&[0; 16]);
+    body.extend_from_slice(&[0x1a, 0x41, 0x00, 0xfe, 0x10, 0x02, 0x00, 0x1a]);
+    body.extend_from_slice(&[0x06, 0x40, 0x19, 0x0b]);
+    body.extend_from_slice(&[0x41, 0x00, 0xc0, 0x1a, 0x0b]);
+    let report = detect(&function(&body));
+    assert_eq!(features(&report), ["simd", "threads", "exceptions", "sign-extension"]);
+}
+
+#[test]
+fn passive_segments_need_bulk_memory() {
+    let bytes = module(&[
+        (5, b"\x01\x00\x01"),
+        // One passive data segment.
+        (11, b"\x01\x01\x01\xaa"),
+    ]);
+    let report = detect(&bytes);
+    assert_eq!(features(&report), ["bulk-memory"]);
+    assert_eq!(report.uses[0].offset, 16);
+
+    let bytes = module(&[
+        (1, b"\x01\x60\x00\x00"),
+        (3, b"\x01\x00"),
+        // A passive and a declarative element segment of function 0.
+        (9, b"\x02\x01\x00\x01\x00\x03\x00\x01\x00"),
+        (10, b"\x01\x02\x00\x0b"),
+    ]);
+    assert_eq!(features(&detect(&bytes)), ["bulk-memory", "reference-types"]);
+}
+
+#[test]
+fn memories_beyond_the_first_need_multi_memory() {
+    let bytes = module(&[(5, b"\x02\x00\x01\x00\x01")]);
+    let report = detect(&bytes);
+    assert_eq!(features(&report), ["multi-memory"]);
+    // The second memory.
+    assert_eq!(report.uses[0].offset, 13);
+    assert_eq!(report.release(), None);
+
+    // An imported memory and a defined one.
+    let bytes = module(&[
+        (2, b"\x01\x01m\x01a\x02\x00\x01"),
+        (5, b"\x01\x00\x01"),
+    ]);
+    assert_eq!(features(&detect(&bytes)), ["multi-memory"]);
+
+    let bytes = module(&[(5, b"\x01\x00\x01")]);
+    assert_eq!(detect(&bytes).release(), Some("1.0"));
+}
+
+#[test]
+fn memory64_is_reported() {
+    // `(memory i64 1)`, then a shared 64-bit memory with a maximum.
+    let bytes = module(&[(5, b"\x01\x04\x01")]);
+    let report = detect(&bytes);
+    assert_eq!(features(&report), ["memory64"]);
+    assert_eq!(report.release(), None);
+
+    let bytes = module(&[(2, b"\x01\x01m\x01a\x02\x07\x01\x80\x80\x80\x80\x80\x01")]);
+    assert_eq!(features(&detect(&bytes)), ["memory64", "threads"]);
+}
@@ -0,0 +1,82 @@
+extern crate wasm;
+
+use wasm::ast;
+use wasm::reader::{Module, ParserConfig, WasmFeatures};
+use wasm::reader::bytecode::Op;
+
+/// A module as a linker that leaves room for relocations writes it: every
+/// size is padded to five bytes, the reserved immediates are padded too,
+/// and the global and export sections are there but empty.
+const PADDED: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // A custom section named "x".
+    0x00, 0x82, 0x80, 0x80, 0x80, 0x00, 0x01, 0x78,
+    // Type `[] -> []`.
+    0x01, 0x84, 0x80, 0x80, 0x80, 0x00, 0x01, 0x60, 0x00, 0x00,
+    0x03, 0x02, 0x01, 0x00,
+    0x04, 0x04, 0x01, 0x70, 0x00, 0x00,
+    0x05, 0x03, 0x01, 0x00, 0x01,
+    0x06, 0x01, 0x00,
+    0x07, 0x01, 0x00,
+    0x0a, 0x17, 0x01, 0x91, 0x80, 0x80, 0x80, 0x00,
+    0x00,
+    // `call_indirect 0` with the reserved byte padded.
+    0x41, 0x00, 0x11, 0x00, 0x80, 0x00,
+    // `grow_memory`, then `current_memory`.
+    0x41, 0x01, 0x40, 0x00, 0x1a,
+    0x3f, 0x80, 0x00, 0x1a,
+    0x0b,
+];
+
+fn decode(bytes: &[u8]) -> ast::Module {
+    Module::new(bytes).unwrap().decode().unwrap()
+}
+
+#[test]
+fn padded_module_round_trips() {
+    assert_eq!(decode(PADDED).encode(), PADDED);
+}
+
+#[test]
+fn shortest_module_round_trips() {
+    let mut module = decode(PADDED);
+    module.encoding.clear();
+    let bytes = module.encode();
+    assert!(bytes.len() < PADDED.len());
+    assert_eq!(decode(&bytes).encode(), bytes);
+}
+
+#[test]
+fn modified_section_is_written_again() {
+    let mut module = decode(PADDED);
+    module.functions[0].body.insert(0, Op::Nop);
+    let bytes = module.encode();
+    // Everything before the code section is kept as it was.
+    let code = PADDED.iter().position(|&byte| byte == 0x0a).unwrap();
+    assert_eq!(bytes[..code], PADDED[..code]);
+    assert_eq!(bytes[code..code + 3], [0x0a, 0x12, 0x01]);
+    let decoded = decode(&bytes);
+    assert_eq!(decoded.functions, module.functions);
+}
+
+#[test]
+fn tag_and_data_count_sections_round_trip() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        // One tag of type 0.
+        0x0d, 0x03, 0x01, 0x00, 0x00,
+        // No data segments.
+        0x0c, 0x01, 0x00,
+    ];
+    let config = ParserConfig {
+        features: WasmFeatures::all(),
+        ..ParserConfig::default()
+    };
+    let mut module = Module::with_config(&bytes, config).unwrap().decode().unwrap();
+    assert_eq!(module.tags, [0]);
+    assert_eq!(module.data_count, Some(0));
+    assert_eq!(module.encode(), bytes);
+    module.encoding.clear();
+    assert_eq!(module.encode(), bytes);
+}
@@ -0,0 +1,36 @@
+extern crate wasm;
+
+use wasm::reader::{ParserConfig, WasmFeatures, Error};
+use wasm::reader::bytecode::OpIterator;
+
+/// The error reading the first operator of `code` gives.
+fn error(code: &[u8], features: WasmFeatures) -> Error {
+    let config = ParserConfig {
+        features,
+        ..ParserConfig::default()
+    };
+    match OpIterator::with_config(code, &config).next() {
+        Some(Err(err)) => err,
+        _ => panic!("{:?} was read", code),
+    }
+}
+
+#[test]
+fn wasm2_leaves_out_what_the_reader_does_not_implement() {
+    let wasm2 = WasmFeatures::wasm2();
+    assert!(!wasm2.multi_value && !wasm2.simd);
+    assert_eq!(wasm2.release(), Some("2.0"));
+    // `v128.const`, and a block of type 0.
+    assert!(matches!(error(b"\xfd\x0c", wasm2), Error::Disabled("simd", 0)));
+    assert!(matches!(error(b"\x02\x00", wasm2), Error::Disabled("multi-value", _)));
+
+    // Enabling them only changes the error.
+    let all = WasmFeatures::all();
+    assert!(matches!(error(b"\xfd\x0c", all), Error::UnknownVariant(_)));
+    assert!(matches!(error(b"\x02\x00", all), Error::UnknownVariant(_)));
+    let simd = WasmFeatures {
+        simd: true,
+        ..wasm2
+    };
+    assert_eq!(simd.release(), Some("2.0"));
+}
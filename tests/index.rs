@@ -0,0 +1,288 @@
+extern crate wasm;
+
+use wasm::ast;
+use wasm::reader::{
+    Module, ModuleIndex, ValueType, Local, ResizableLimits, ExternalKind,
+    FunctionRef, GlobalRef, TableRef, MemoryRef, ExternRef
+};
+use wasm::reader::bytecode::Op;
+use wasm::writer::{write_varuint, write_name};
+
+/// This crate compiled to a module by rustc, with a name section in the
+/// format of the spec. See `corpus/Cargo.toml`.
+const CORPUS: &[u8] = include_bytes!("corpus/corpus.wasm");
+
+/// A module with an imported function and two functions of type
+/// `[] -> []`, with `names` as its name section.
+fn module(names: Vec<u8>) -> Vec<u8> {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.types.push(ast::FunctionType {
+        params: vec![],
+        return_type: None,
+    });
+    module.imports.push(ast::Import {
+        module: "env".to_owned(),
+        field: "f".to_owned(),
+        kind: ast::ImportKind::Function(0),
+    });
+    for _ in 0..2 {
+        module.functions.push(ast::Function {
+            ty: 0,
+            locals: vec![Local {
+                count: 1,
+                ty: ValueType::I32,
+            }],
+            body: vec![Op::End],
+        });
+    }
+    module.custom_sections.push(ast::CustomSection {
+        name: "name".to_owned(),
+        payload: names,
+        after: None,
+    });
+    module.encode()
+}
+
+fn limits(initial: u32) -> ResizableLimits {
+    ResizableLimits {
+        initial,
+        maximum: None,
+        shared: false,
+    }
+}
+
+/// A module that imports two functions, a global, a table and a memory,
+/// interleaved, and defines two functions, two globals, a table and a
+/// memory. Each definition is told apart from the imports by its type,
+/// limits or initializer.
+fn index_spaces() -> Vec<u8> {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    for &params in &[&[][..], &[ValueType::I32][..]] {
+        module.types.push(ast::FunctionType {
+            params: params.to_vec(),
+            return_type: None,
+        });
+    }
+    let imports = vec![
+        ("f", ast::ImportKind::Function(1)),
+        ("g", ast::ImportKind::Global(ast::GlobalType {
+            ty: ValueType::I64,
+            mutable: false,
+        })),
+        ("t", ast::ImportKind::Table(ast::Table {
+            element_type: 0x70,
+            limits: limits(1),
+        })),
+        ("h", ast::ImportKind::Function(0)),
+        ("m", ast::ImportKind::Memory(ast::Memory {
+            limits: limits(1),
+        })),
+    ];
+    for (field, kind) in imports {
+        module.imports.push(ast::Import {
+            module: "env".to_owned(),
+            field: field.to_owned(),
+            kind,
+        });
+    }
+    for ty in 0..2 {
+        module.functions.push(ast::Function {
+            ty,
+            locals: vec![],
+            body: vec![Op::End],
+        });
+    }
+    module.tables.push(ast::Table {
+        element_type: 0x70,
+        limits: limits(2),
+    });
+    module.memories.push(ast::Memory {
+        limits: limits(2),
+    });
+    for value in 1..3 {
+        module.globals.push(ast::Global {
+            ty: ast::GlobalType {
+                ty: ValueType::I32,
+                mutable: true,
+            },
+            init: vec![Op::I32Const(value), Op::End],
+        });
+    }
+    for &(field, kind, index) in &[("first", ExternalKind::Function, 2), ("last", ExternalKind::Global, 2)] {
+        module.exports.push(ast::Export {
+            field: field.to_owned(),
+            kind,
+            index,
+        });
+    }
+    module.encode()
+}
+
+/// A subsection of a name section in the format of the spec.
+fn subsection(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    write_varuint(out, contents.len() as u64);
+    out.extend_from_slice(contents);
+}
+
+#[test]
+fn function_names_by_index() {
+    // Only the imported function and the last one are named, and a local
+    // names subsection follows.
+    let mut functions = vec![];
+    write_varuint(&mut functions, 2);
+    for &(index, name) in &[(0, "imported"), (2, "last")] {
+        write_varuint(&mut functions, index);
+        write_name(&mut functions, name);
+    }
+    let mut locals = vec![];
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 0);
+    write_name(&mut locals, "x");
+    let mut names = vec![];
+    subsection(&mut names, 0, b"\x01m");
+    subsection(&mut names, 1, &functions);
+    subsection(&mut names, 2, &locals);
+    let bytes = module(names);
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    assert_eq!(index.function(0).unwrap().name, Some("imported"));
+    assert_eq!(index.function(1).unwrap().name, None);
+    assert_eq!(index.function(2).unwrap().name, Some("last"));
+}
+
+#[test]
+fn legacy_function_names_by_position() {
+    let mut names = vec![];
+    write_varuint(&mut names, 2);
+    write_name(&mut names, "imported");
+    write_varuint(&mut names, 0);
+    write_name(&mut names, "first");
+    write_varuint(&mut names, 1);
+    write_name(&mut names, "x");
+    let bytes = module(names);
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    assert_eq!(index.function(0).unwrap().name, Some("imported"));
+    assert_eq!(index.function(1).unwrap().name, Some("first"));
+    assert_eq!(index.function(2).unwrap().name, None);
+}
+
+#[test]
+fn corpus_function_names() {
+    let module = Module::new(CORPUS).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    let export = index.export("process").unwrap();
+    assert_eq!(index.function(export.index).unwrap().name, Some("process"));
+    for function in 0..index.function_count() {
+        assert!(index.function(function).unwrap().name.is_some(), "function {} has no name", function);
+    }
+}
+
+#[test]
+fn functions_count_imports_first() {
+    let bytes = index_spaces();
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    assert_eq!(index.function_count(), 4);
+    match index.resolve_function(0) {
+        Some(FunctionRef::Imported { module: "env", field: "f", type_index: 1, .. }) => (),
+        _ => panic!("function 0 is not the first import"),
+    }
+    match index.resolve_function(1) {
+        Some(FunctionRef::Imported { field: "h", type_index: 0, .. }) => (),
+        _ => panic!("function 1 is not the second import"),
+    }
+    for (function, local_index) in (2..4).zip(0..) {
+        match index.resolve_function(function) {
+            Some(FunctionRef::Defined { local_index: found, type_index, body, .. }) => {
+                assert_eq!((found, type_index), (local_index, local_index));
+                assert_eq!(body.body.body, &[0x0b]);
+            },
+            _ => panic!("function {} is not defined", function),
+        }
+    }
+    assert!(index.resolve_function(4).is_none());
+    assert!(index.function(1).unwrap().body.is_none());
+    let function = index.function(3).unwrap();
+    assert_eq!(function.type_index, 1);
+    assert_eq!(function.ty.params().map(Result::unwrap).collect::<Vec<_>>(), vec![ValueType::I32]);
+    assert!(function.body.is_some());
+    assert!(index.function(4).is_none());
+}
+
+#[test]
+fn globals_count_imports_first() {
+    let bytes = index_spaces();
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    assert_eq!(index.global_count(), 3);
+    match index.resolve_global(0) {
+        Some(GlobalRef::Imported { field: "g", ty: ValueType::I64, mutable: false, .. }) => (),
+        _ => panic!("global 0 is not the import"),
+    }
+    for (global, value) in (1..3).zip(1..) {
+        match index.resolve_global(global) {
+            Some(GlobalRef::Defined { local_index, ty: ValueType::I32, mutable: true, init }) => {
+                assert_eq!(local_index, global - 1);
+                assert_eq!(init, &[Op::I32Const(value), Op::End]);
+            },
+            _ => panic!("global {} is not defined", global),
+        }
+    }
+    assert!(index.resolve_global(3).is_none());
+    let imported = index.global(0).unwrap();
+    assert_eq!((imported.ty, imported.mutable, imported.init), (ValueType::I64, false, None));
+    let defined = index.global(2).unwrap();
+    assert_eq!(defined.init, Some(&[Op::I32Const(2), Op::End][..]));
+    assert!(index.global(3).is_none());
+}
+
+#[test]
+fn tables_and_memories_count_imports_first() {
+    let bytes = index_spaces();
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    assert_eq!((index.table_count(), index.memory_count()), (2, 2));
+    match index.resolve_table(0) {
+        Some(TableRef::Imported { field: "t", element_type: 0x70, limits, .. }) => assert_eq!(limits.initial, 1),
+        _ => panic!("table 0 is not the import"),
+    }
+    match index.resolve_table(1) {
+        Some(TableRef::Defined { local_index: 0, limits, .. }) => assert_eq!(limits.initial, 2),
+        _ => panic!("table 1 is not defined"),
+    }
+    assert!(index.resolve_table(2).is_none());
+    match index.resolve_memory(0) {
+        Some(MemoryRef::Imported { field: "m", limits, .. }) => assert_eq!(limits.initial, 1),
+        _ => panic!("memory 0 is not the import"),
+    }
+    match index.resolve_memory(1) {
+        Some(MemoryRef::Defined { local_index: 0, limits }) => assert_eq!(limits.initial, 2),
+        _ => panic!("memory 1 is not defined"),
+    }
+    assert!(index.resolve_memory(2).is_none());
+}
+
+#[test]
+fn exports_resolve_through_the_index_spaces() {
+    let bytes = index_spaces();
+    let module = Module::new(&bytes).unwrap();
+    let index = ModuleIndex::new(&module).unwrap();
+    match index.resolve_export(index.export("first").unwrap()) {
+        Some(ExternRef::Function(FunctionRef::Defined { local_index: 0, .. })) => (),
+        _ => panic!("export first is not the first defined function"),
+    }
+    match index.resolve_export(index.export("last").unwrap()) {
+        Some(ExternRef::Global(GlobalRef::Defined { local_index: 1, .. })) => (),
+        _ => panic!("export last is not the last global"),
+    }
+}
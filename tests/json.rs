@@ -0,0 +1,139 @@
+#![cfg(feature = "json")]
+
+extern crate wasm;
+extern crate serde_json;
+
+use wasm::ast;
+use wasm::reader::{
+    Module, ValueType, ExternalKind, SectionType, InlineSignatureType, ResizableLimits, Local
+};
+use wasm::reader::bytecode::{Op, BrTable, MemoryImmediate};
+
+/// This crate compiled to a module by rustc. See `corpus/Cargo.toml`.
+const CORPUS: &[u8] = include_bytes!("corpus/corpus.wasm");
+
+/// The JSON of `small()`, as `wasm-json` prints it.
+const SMALL_JSON: &str = include_str!("json/small.json");
+
+/// A module with one of each kind of section, written by `encode`, so every
+/// integer is already in its shortest form.
+fn small() -> Vec<u8> {
+    let module = ast::Module {
+        version: 1,
+        types: vec![
+            ast::FunctionType {
+                params: vec![ValueType::I32],
+                return_type: Some(ValueType::I32),
+            },
+            ast::FunctionType {
+                params: vec![],
+                return_type: None,
+            },
+        ],
+        imports: vec![ast::Import {
+            module: "env".to_owned(),
+            field: "log".to_owned(),
+            kind: ast::ImportKind::Function(1),
+        }],
+        functions: vec![ast::Function {
+            ty: 0,
+            locals: vec![Local {
+                count: 1,
+                ty: ValueType::F32,
+            }],
+            body: vec![
+                Op::Block(InlineSignatureType::Empty),
+                Op::GetLocal(0),
+                Op::BrTable(BrTable::new(&[0], 0)),
+                Op::End,
+                Op::F32Const(1.5),
+                Op::SetLocal(1),
+                Op::I32Const(0),
+                Op::CallIndirect(1, false),
+                Op::GetLocal(0),
+                Op::I32Load(MemoryImmediate {
+                    flags: 2,
+                    offset: 4,
+                }),
+                Op::End,
+            ],
+        }],
+        tables: vec![ast::Table {
+            element_type: 0x70,
+            limits: ResizableLimits {
+                initial: 1,
+                maximum: Some(1),
+                shared: false,
+            },
+        }],
+        memories: vec![ast::Memory {
+            limits: ResizableLimits {
+                initial: 1,
+                maximum: None,
+                shared: false,
+            },
+        }],
+        globals: vec![ast::Global {
+            ty: ast::GlobalType {
+                ty: ValueType::I32,
+                mutable: true,
+            },
+            init: vec![Op::I32Const(-7), Op::End],
+        }],
+        exports: vec![ast::Export {
+            field: "f".to_owned(),
+            kind: ExternalKind::Function,
+            index: 1,
+        }],
+        elements: vec![ast::Element {
+            index: 0,
+            offset: vec![Op::I32Const(0), Op::End],
+            elems: vec![1],
+        }],
+        data: vec![ast::Data {
+            index: 0,
+            offset: vec![Op::I32Const(8), Op::End],
+            data: b"hi".to_vec(),
+        }],
+        custom_sections: vec![ast::CustomSection {
+            name: "note".to_owned(),
+            payload: vec![1, 2],
+            after: Some(SectionType::Data),
+        }],
+        .. ast::Module::default()
+    };
+    module.encode()
+}
+
+fn to_json(bytes: &[u8]) -> String {
+    let module = Module::new(bytes).and_then(|module| module.decode()).unwrap();
+    serde_json::to_string_pretty(&module).unwrap() + "\n"
+}
+
+fn to_wasm(json: &str) -> Vec<u8> {
+    serde_json::from_str::<ast::Module>(json).unwrap().encode()
+}
+
+#[test]
+fn small_module_round_trips() {
+    let bytes = small();
+    assert_eq!(to_wasm(&to_json(&bytes)), bytes);
+}
+
+#[test]
+fn small_module_snapshot() {
+    assert_eq!(to_json(&small()), SMALL_JSON);
+    assert_eq!(to_wasm(SMALL_JSON), small());
+}
+
+#[test]
+fn corpus_round_trips() {
+    // The corpus is not in canonical form, and the JSON leaves out what
+    // `encode` needs to reproduce it, so it comes back canonical.
+    let mut module = Module::new(CORPUS).and_then(|module| module.decode()).unwrap();
+    module.encoding.clear();
+    let json = serde_json::to_string(&module).unwrap();
+    let decoded: ast::Module = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, module);
+    assert_eq!(decoded.encode(), module.encode());
+}
@@ -0,0 +1,104 @@
+extern crate wasm;
+
+use wasm::ast;
+use wasm::reader::{Module, SectionContent, TypeEntry, ValueType, Local, Locals};
+use wasm::reader::bytecode::Op;
+
+/// A module with one function of type `[params] -> []` that declares
+/// `locals`.
+fn module(params: Vec<ValueType>, locals: Vec<Local>) -> Vec<u8> {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.types.push(ast::FunctionType {
+        params,
+        return_type: None,
+    });
+    module.functions.push(ast::Function {
+        ty: 0,
+        locals,
+        body: vec![Op::Nop, Op::End],
+    });
+    module.encode()
+}
+
+/// The locals of the function in `bytes`, and the operators after them.
+fn locals(bytes: &[u8]) -> (Locals, Vec<Op<'static>>) {
+    let module = Module::new(bytes).unwrap();
+    let mut ty = None;
+    let mut body = None;
+    for section in module.sections() {
+        match section.unwrap().content().unwrap() {
+            SectionContent::Type(types) => match types.entries().next().unwrap().unwrap() {
+                TypeEntry::Function(function) => ty = Some(function),
+            },
+            SectionContent::Code(code) => body = code.entries().next().map(Result::unwrap),
+            _ => (),
+        }
+    }
+    let (locals, ops) = body.unwrap().locals(&ty.unwrap()).unwrap();
+    (locals, ops.map(|op| op.unwrap().into_owned()).collect())
+}
+
+fn local(count: u32, ty: ValueType) -> Local {
+    Local {
+        count,
+        ty,
+    }
+}
+
+#[test]
+fn params_then_runs_of_locals() {
+    let bytes = module(
+        vec![ValueType::I32, ValueType::I64],
+        vec![local(2, ValueType::F32), local(3, ValueType::I32), local(1, ValueType::F64)],
+    );
+    let (locals, ops) = locals(&bytes);
+    assert_eq!(ops, vec![Op::Nop, Op::End]);
+    assert_eq!(locals.param_count(), 2);
+    assert_eq!(locals.len(), 8);
+    assert!(!locals.is_empty());
+    let types = (0..9).map(|index| locals.get(index)).collect::<Vec<_>>();
+    assert_eq!(types, vec![
+        Some(ValueType::I32),
+        Some(ValueType::I64),
+        Some(ValueType::F32),
+        Some(ValueType::F32),
+        Some(ValueType::I32),
+        Some(ValueType::I32),
+        Some(ValueType::I32),
+        Some(ValueType::F64),
+        None,
+    ]);
+    assert_eq!(locals.get(u32::MAX), None);
+}
+
+#[test]
+fn runs_of_the_same_type_merge() {
+    // The last parameter and the runs after it are all `i32`, and the empty
+    // run in between is ignored.
+    let bytes = module(
+        vec![ValueType::F64, ValueType::I32],
+        vec![local(1, ValueType::I32), local(0, ValueType::F32), local(2, ValueType::I32)],
+    );
+    let (locals, _) = locals(&bytes);
+    assert_eq!(locals.param_count(), 2);
+    assert_eq!(locals.len(), 5);
+    assert_eq!(locals.get(0), Some(ValueType::F64));
+    for index in 1..5 {
+        assert_eq!(locals.get(index), Some(ValueType::I32), "local {}", index);
+    }
+    assert_eq!(locals.get(5), None);
+}
+
+#[test]
+fn no_params_or_locals() {
+    let bytes = module(vec![], vec![]);
+    let (locals, ops) = locals(&bytes);
+    assert_eq!(ops, vec![Op::Nop, Op::End]);
+    assert_eq!(locals.param_count(), 0);
+    assert_eq!(locals.len(), 0);
+    assert!(locals.is_empty());
+    assert_eq!(locals.get(0), None);
+}
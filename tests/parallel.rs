@@ -0,0 +1,93 @@
+#![cfg(feature = "parallel")]
+
+extern crate wasm;
+
+use std::thread::sleep;
+use std::time::Duration;
+use wasm::reader::{Module, SectionContent, CodeSection, Error};
+
+/// This crate compiled to a module by rustc. See `corpus/Cargo.toml`.
+const CORPUS: &[u8] = include_bytes!("corpus/corpus.wasm");
+
+fn code<'a>(module: &'a Module<'a>) -> CodeSection<'a> {
+    for section in module.sections() {
+        if let Ok(SectionContent::Code(code)) = section.unwrap().content() {
+            return code
+        }
+    }
+    panic!("no code section")
+}
+
+#[derive(Debug)]
+enum Failure {
+    Read(Error),
+    Body(usize),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Failure {
+        Failure::Read(err)
+    }
+}
+
+#[test]
+fn bodies_in_section_order() {
+    let module = Module::new(CORPUS).unwrap();
+    let code = code(&module);
+    let bodies = code.bodies().unwrap();
+    assert_eq!(bodies.len(), code.count as usize);
+    for (body, expected) in bodies.iter().zip(code.entries()) {
+        assert_eq!(body.body, expected.unwrap().body);
+    }
+}
+
+#[test]
+fn par_map_keeps_function_order() {
+    let module = Module::new(CORPUS).unwrap();
+    let code = code(&module);
+    let expected = code.entries().enumerate().map(|(index, body)| (index, body.unwrap().body)).collect::<Vec<_>>();
+    let results = code.par_map(|index, body| {
+        // Make the first bodies finish last.
+        if index < 4 {
+            sleep(Duration::from_millis(10));
+        }
+        (index, body.body)
+    }).unwrap();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn par_try_map_returns_the_first_error_in_section_order() {
+    let module = Module::new(CORPUS).unwrap();
+    let code = code(&module);
+    let result = code.par_try_map(|index, _| {
+        match index {
+            // The earlier failure takes longer, so the later one is seen
+            // first.
+            50 => {
+                sleep(Duration::from_millis(50));
+                Err(Failure::Body(index))
+            },
+            200 => Err(Failure::Body(index)),
+            _ => Ok(index),
+        }
+    });
+    match result {
+        Err(Failure::Body(50)) => (),
+        result => panic!("{:?}", result),
+    }
+    let all = code.par_try_map(|index, _| Ok::<_, Failure>(index)).unwrap();
+    assert_eq!(all, (0..code.count as usize).collect::<Vec<_>>());
+}
+
+#[test]
+fn par_try_map_reports_malformed_sections() {
+    let module = Module::new(CORPUS).unwrap();
+    let mut code = code(&module);
+    // Drop the last byte, which belongs to the last body.
+    code.entries_raw = &code.entries_raw[..code.entries_raw.len() - 1];
+    match code.par_try_map(|index, _| Ok::<_, Failure>(index)) {
+        Err(Failure::Read(Error::UnexpectedEof)) => (),
+        result => panic!("{:?}", result),
+    }
+}
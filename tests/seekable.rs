@@ -0,0 +1,31 @@
+#![cfg(feature = "std")]
+
+extern crate wasm;
+
+use std::io::Cursor;
+use wasm::reader::{SeekModule, SectionType, Error};
+
+#[test]
+fn body_larger_than_code_section() {
+    let bytes = vec![
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // One type, `[] -> []`, and one function of that type.
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x02, 0x01, 0x00,
+        // A body that claims five bytes, in a section with two left.
+        0x0a, 0x04, 0x01, 0x05, 0x00, 0x0b,
+        // A custom section the body would run into.
+        0x00, 0x04, 0x03, b'a', b'b', b'c',
+    ];
+    let mut module = SeekModule::new(Cursor::new(bytes)).unwrap();
+    let code = module.find_section(SectionType::Code).unwrap();
+    let mut reader = module.code_reader(code).unwrap();
+    let mut buf = vec![];
+    match reader.next_body(&mut buf) {
+        Some(Err(Error::UnexpectedEof)) => (),
+        Some(Err(err)) => panic!("{}", err),
+        Some(Ok(_)) => panic!("read past the code section"),
+        None => panic!("no body"),
+    }
+    assert!(reader.next_body(&mut buf).is_none());
+}
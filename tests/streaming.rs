@@ -0,0 +1,171 @@
+extern crate wasm;
+
+use wasm::reader::{Module, StreamParser, Payload, SectionType, SectionContent, Error};
+
+/// This crate compiled to a module by rustc. See `corpus/Cargo.toml`.
+const CORPUS: &[u8] = include_bytes!("corpus/corpus.wasm");
+
+/// A module with a type, two functions and a custom section after the code
+/// section.
+const SMALL: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+    0x03, 0x03, 0x02, 0x00, 0x00,
+    0x0a, 0x0e, 0x02,
+    // One `i32` local, `nop`, `nop`.
+    0x06, 0x01, 0x01, 0x7f, 0x01, 0x01, 0x0b,
+    // No locals, `i32.const 1`, `drop`.
+    0x05, 0x00, 0x41, 0x01, 0x1a, 0x0b,
+    0x00, 0x03, 0x01, b'x', 0x00,
+];
+
+/// What the parser yields, without the borrows.
+#[derive(Debug, PartialEq)]
+enum Event {
+    Version(u32),
+    Section(SectionType, Vec<u8>),
+    CodeSectionStart(u32),
+    FunctionBody(usize, Vec<u8>),
+}
+
+/// Pushes `bytes` in chunks of the sizes `chunks` gives, and returns what
+/// the parser yields and how often it asked for more data.
+fn stream<I: Iterator<Item = usize>>(bytes: &[u8], chunks: I) -> Result<(Vec<Event>, usize), Error> {
+    let mut parser = StreamParser::new();
+    let mut events = vec![];
+    let mut waits = 0;
+    let mut rest = bytes;
+    let mut chunks = chunks;
+    loop {
+        match parser.next()? {
+            Payload::NeedMoreData(_) => {
+                waits += 1;
+                if rest.is_empty() {
+                    parser.finish();
+                    continue
+                }
+                let size = chunks.next().unwrap().max(1).min(rest.len());
+                parser.push(&rest[..size]);
+                rest = &rest[size..];
+            },
+            Payload::Version(version) => events.push(Event::Version(version)),
+            Payload::Section(section) => events.push(Event::Section(section.id, section.payload.to_vec())),
+            Payload::CodeSectionStart { count, .. } => events.push(Event::CodeSectionStart(count)),
+            Payload::FunctionBody(body) => events.push(Event::FunctionBody(body.local_count, body.body.to_vec())),
+            Payload::End => return Ok((events, waits)),
+        }
+    }
+}
+
+/// What `Module::new` reads from `bytes`, as events.
+fn events(bytes: &[u8]) -> Vec<Event> {
+    let module = Module::new(bytes).unwrap();
+    let mut events = vec![Event::Version(module.version)];
+    for section in module.sections() {
+        let section = section.unwrap();
+        match section.content() {
+            Ok(SectionContent::Code(code)) => {
+                events.push(Event::CodeSectionStart(code.count));
+                for body in code.entries() {
+                    let body = body.unwrap();
+                    events.push(Event::FunctionBody(body.local_count, body.body.to_vec()));
+                }
+            },
+            _ => events.push(Event::Section(section.id, section.payload.to_vec())),
+        }
+    }
+    events
+}
+
+/// Chunk sizes from 1 to `max`, from a linear congruential generator.
+fn random_sizes(seed: u32, max: u32) -> impl Iterator<Item = usize> {
+    let mut state = seed;
+    std::iter::repeat_with(move || {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        ((state >> 16) % max + 1) as usize
+    })
+}
+
+#[test]
+fn one_byte_at_a_time() {
+    let (streamed, waits) = stream(SMALL, std::iter::repeat(1)).unwrap();
+    assert_eq!(streamed, events(SMALL));
+    assert!(waits >= SMALL.len());
+    let (streamed, _) = stream(CORPUS, std::iter::repeat(1)).unwrap();
+    assert_eq!(streamed, events(CORPUS));
+}
+
+#[test]
+fn random_chunk_sizes() {
+    let expected = events(CORPUS);
+    for seed in 0..8 {
+        let (streamed, _) = stream(CORPUS, random_sizes(seed, 4096)).unwrap();
+        assert_eq!(streamed, expected, "seed {}", seed);
+    }
+    for seed in 0..8 {
+        let (streamed, _) = stream(SMALL, random_sizes(seed, 8)).unwrap();
+        assert_eq!(streamed, events(SMALL), "seed {}", seed);
+    }
+}
+
+#[test]
+fn function_body_split_across_chunks() {
+    // The first body starts at 22, the second at 29.
+    for split in 22..35 {
+        let mut parser = StreamParser::new();
+        parser.push(&SMALL[..split]);
+        let mut bodies = 0;
+        loop {
+            match parser.next().unwrap() {
+                Payload::FunctionBody(_) => bodies += 1,
+                Payload::NeedMoreData(_) => break,
+                Payload::End => panic!("end before all data was pushed"),
+                _ => (),
+            }
+        }
+        assert_eq!(bodies, if split < 29 { 0 } else { 1 }, "split at {}", split);
+        parser.push(&SMALL[split..]);
+        loop {
+            match parser.next().unwrap() {
+                Payload::FunctionBody(body) => {
+                    bodies += 1;
+                    assert_eq!(body.local_count, 2 - bodies);
+                },
+                Payload::Section(section) => {
+                    assert_eq!(section.name, "x");
+                    break
+                },
+                _ => panic!("unexpected payload, split at {}", split),
+            }
+        }
+        assert_eq!(bodies, 2);
+    }
+}
+
+#[test]
+fn finish_on_a_truncated_module() {
+    // Cutting the module between sections leaves a valid module.
+    for len in (1..SMALL.len()).filter(|len| ![8, 14, 19, 35].contains(len)) {
+        let mut parser = StreamParser::new();
+        parser.push(&SMALL[..len]);
+        parser.finish();
+        let err = loop {
+            match parser.next() {
+                Ok(Payload::End) => panic!("truncated at {} but parsed to the end", len),
+                Ok(Payload::NeedMoreData(_)) => panic!("asked for more data after finish"),
+                Ok(_) => (),
+                Err(err) => break err,
+            }
+        };
+        match err {
+            Error::UnexpectedEof => (),
+            err => panic!("truncated at {}: {}", len, err),
+        }
+    }
+}
+
+#[test]
+fn matches_module_new() {
+    let (streamed, _) = stream(CORPUS, std::iter::repeat(CORPUS.len())).unwrap();
+    assert_eq!(streamed, events(CORPUS));
+}
@@ -0,0 +1,279 @@
+extern crate wasm;
+
+use wasm::ast;
+use wasm::reader::{Module, Error, ValueType, Local, ResizableLimits, SectionType, SectionContent, ParserConfig, WasmFeatures, InlineSignatureType};
+use wasm::reader::bytecode::{Op, BrTable};
+use wasm::validate::{ValidationError, Context};
+
+fn validate(bytes: &[u8]) -> Result<(), ValidationError> {
+    Module::new(bytes).expect("not a wasm module").validate()
+}
+
+/// A module with one function of type `[i32] -> [i32]` and the given body.
+fn function(body: Vec<Op<'static>>) -> Vec<u8> {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.types.push(ast::FunctionType {
+        params: vec![ValueType::I32],
+        return_type: Some(ValueType::I32),
+    });
+    module.functions.push(ast::Function {
+        ty: 0,
+        locals: vec![Local {
+            count: 1,
+            ty: ValueType::I64,
+        }],
+        body,
+    });
+    module.encode()
+}
+
+/// An operator as `Context::typed_ops` yields it, without the offset:
+/// pops, pushes, height before and after, and whether it is unreachable.
+type Typed = (Op<'static>, Vec<Option<ValueType>>, Vec<Option<ValueType>>, usize, usize, bool);
+
+/// The typed operators of the function `function` builds from `body`.
+fn typed_ops(body: Vec<Op<'static>>) -> Result<Vec<Typed>, ValidationError> {
+    let bytes = function(body);
+    let module = Module::new(&bytes).unwrap();
+    let context = Context::new(&module).unwrap();
+    let code = module.sections().map(|section| section.unwrap()).find(|section| section.id == SectionType::Code).unwrap();
+    let body = match code.content().unwrap() {
+        SectionContent::Code(code) => code.entries().next().unwrap().unwrap(),
+        _ => unreachable!(),
+    };
+    let mut ops = vec![];
+    for op in context.typed_ops(0, &body)? {
+        let op = op?;
+        ops.push((op.op.into_owned(), op.pops, op.pushes, op.height_before, op.height_after, op.unreachable));
+    }
+    Ok(ops)
+}
+
+const I32: Option<ValueType> = Some(ValueType::I32);
+const I64: Option<ValueType> = Some(ValueType::I64);
+
+#[test]
+fn valid_function() {
+    let bytes = function(vec![Op::GetLocal(0), Op::I32Const(1), Op::I32Add, Op::End]);
+    validate(&bytes).unwrap();
+}
+
+#[test]
+fn operand_type_mismatch() {
+    let bytes = function(vec![Op::GetLocal(0), Op::GetLocal(1), Op::I32Add, Op::End]);
+    match validate(&bytes) {
+        Err(ValidationError::Function { function: 0, .. }) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn missing_result() {
+    let bytes = function(vec![Op::Nop, Op::End]);
+    match validate(&bytes) {
+        Err(ValidationError::Function { function: 0, .. }) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn unreachable_code_is_polymorphic() {
+    let bytes = function(vec![Op::Unreachable, Op::I32Add, Op::End]);
+    validate(&bytes).unwrap();
+}
+
+#[test]
+fn unknown_call_target() {
+    let bytes = function(vec![Op::GetLocal(0), Op::Call(5), Op::End]);
+    match validate(&bytes) {
+        Err(ValidationError::Function { function: 0, .. }) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn data_without_memory() {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.data.push(ast::Data {
+        index: 0,
+        offset: vec![Op::I32Const(0), Op::End],
+        data: vec![1, 2, 3],
+    });
+    match validate(&module.encode()) {
+        Err(ValidationError::Module { section: SectionType::Data, .. }) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn shared_memory_needs_maximum() {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.memories.push(ast::Memory {
+        limits: ResizableLimits {
+            initial: 1,
+            maximum: None,
+            shared: true,
+        },
+    });
+    assert!(validate(&module.encode()).is_err());
+}
+
+#[test]
+fn data_segment_longer_than_section() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // A memory of one page.
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // One segment at `i32.const 0` that claims 100 bytes but has one.
+        0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x64, 0xaa,
+    ];
+    match validate(&bytes) {
+        Err(ValidationError::Decode(Error::UnexpectedEof)) => (),
+        other => panic!("{:?}", other),
+    }
+    let module = Module::new(&bytes).unwrap();
+    assert!(module.decode().is_err());
+    assert!(module.detect_features().is_err());
+}
+
+fn mvp() -> ParserConfig {
+    ParserConfig {
+        features: WasmFeatures::wasm1(),
+        ..ParserConfig::default()
+    }
+}
+
+#[test]
+fn disabled_operator_offset() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // One function of type `[i32] -> [i32]`.
+        0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+        0x03, 0x02, 0x01, 0x00,
+        // `local.get 0`, `i32.extend8_s` at offset 27.
+        0x0a, 0x07, 0x01, 0x05, 0x00, 0x20, 0x00, 0xc0, 0x0b,
+    ];
+    let module = Module::with_config(&bytes, mvp()).unwrap();
+    match module.validate() {
+        Err(ValidationError::Disabled { feature: "sign-extension", offset: 27 }) => (),
+        other => panic!("{:?}", other),
+    }
+    let report = module.detect_features().unwrap();
+    assert_eq!(report.uses[0].feature, "sign-extension");
+    assert_eq!(report.uses[0].offset, 27);
+}
+
+#[test]
+fn data_offset_uses_parser_config() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // One segment at `i32.const 0; i32.extend8_s`, with the operator
+        // at offset 19.
+        0x0b, 0x08, 0x01, 0x00, 0x41, 0x00, 0xc0, 0x0b, 0x01, 0xaa,
+    ];
+    let module = Module::with_config(&bytes, mvp()).unwrap();
+    let data = module.sections().map(|section| section.unwrap()).find(|section| section.id == SectionType::Data).unwrap();
+    let entries = match data.content().unwrap() {
+        SectionContent::Data(data) => data,
+        _ => unreachable!(),
+    };
+    let error = entries.entries().find_map(|entry| entry.err());
+    match error {
+        // Relative to the entries, which follow the count.
+        Some(Error::Disabled("sign-extension", 3)) => (),
+        other => panic!("{:?}", other),
+    }
+    match module.validate() {
+        Err(ValidationError::Disabled { feature: "sign-extension", offset: 19 }) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn typed_select() {
+    let ops = typed_ops(vec![
+        Op::GetLocal(1), Op::GetLocal(1), Op::GetLocal(0), Op::Select, Op::Drop,
+        Op::GetLocal(0), Op::End,
+    ]).unwrap();
+    assert_eq!(ops, vec![
+        (Op::GetLocal(1), vec![], vec![I64], 0, 1, false),
+        (Op::GetLocal(1), vec![], vec![I64], 1, 2, false),
+        (Op::GetLocal(0), vec![], vec![I32], 2, 3, false),
+        (Op::Select, vec![I64, I64, I32], vec![I64], 3, 1, false),
+        (Op::Drop, vec![I64], vec![], 1, 0, false),
+        (Op::GetLocal(0), vec![], vec![I32], 0, 1, false),
+        (Op::End, vec![I32], vec![I32], 1, 1, false),
+    ]);
+}
+
+#[test]
+fn typed_unreachable_code() {
+    // Operands taken from the polymorphic stack have the type the operator
+    // expects, or are unknown where any type would do.
+    let ops = typed_ops(vec![
+        Op::Unreachable, Op::Select, Op::Drop, Op::I32Add, Op::End,
+    ]).unwrap();
+    assert_eq!(ops, vec![
+        (Op::Unreachable, vec![], vec![], 0, 0, false),
+        (Op::Select, vec![None, None, I32], vec![None], 0, 1, true),
+        (Op::Drop, vec![None], vec![], 1, 0, true),
+        (Op::I32Add, vec![I32, I32], vec![I32], 0, 1, true),
+        (Op::End, vec![I32], vec![I32], 1, 1, true),
+    ]);
+    // One known operand decides the type `select` pushes.
+    let ops = typed_ops(vec![
+        Op::GetLocal(0), Op::Return, Op::GetLocal(1), Op::I32Const(0), Op::Select, Op::Drop, Op::End,
+    ]).unwrap();
+    assert_eq!(ops[1], (Op::Return, vec![I32], vec![], 1, 0, false));
+    assert_eq!(ops[4], (Op::Select, vec![None, I64, I32], vec![I64], 2, 1, true));
+    assert_eq!(ops[6], (Op::End, vec![I32], vec![I32], 0, 1, true));
+}
+
+#[test]
+fn typed_br_table() {
+    let table = Op::BrTable(BrTable::new(&[0, 1], 0));
+    let ops = typed_ops(vec![
+        Op::Block(InlineSignatureType::I32),
+        Op::GetLocal(0),
+        Op::GetLocal(0),
+        table.clone(),
+        Op::Drop,
+        Op::End,
+        Op::End,
+    ]).unwrap();
+    assert_eq!(ops, vec![
+        (Op::Block(InlineSignatureType::I32), vec![], vec![], 0, 0, false),
+        (Op::GetLocal(0), vec![], vec![I32], 0, 1, false),
+        (Op::GetLocal(0), vec![], vec![I32], 1, 2, false),
+        // The value for the label, then the index.
+        (table, vec![I32, I32], vec![], 2, 0, false),
+        (Op::Drop, vec![None], vec![], 0, 0, true),
+        (Op::End, vec![I32], vec![I32], 0, 1, true),
+        (Op::End, vec![I32], vec![I32], 1, 1, false),
+    ]);
+    // A loop label takes no value, the block around it one.
+    let result = typed_ops(vec![
+        Op::Block(InlineSignatureType::I32),
+        Op::Loop(InlineSignatureType::Empty),
+        Op::GetLocal(0),
+        Op::GetLocal(0),
+        Op::BrTable(BrTable::new(&[1], 0)),
+        Op::End,
+        Op::End,
+        Op::End,
+    ]);
+    match result {
+        Err(ValidationError::Function { function: 0, message: "type mismatch", .. }) => (),
+        other => panic!("{:?}", other),
+    }
+}
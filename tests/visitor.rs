@@ -0,0 +1,142 @@
+extern crate wasm;
+
+use wasm::reader::{
+    Module, SectionContent, ParserConfig, InlineSignatureType, OperatorVisitor, Error,
+    visit_operators
+};
+use wasm::reader::bytecode::{Op, OpCode, OpIterator, BrTable, MemoryImmediate};
+
+/// This crate compiled to a module by rustc. See `corpus/Cargo.toml`.
+const CORPUS: &[u8] = include_bytes!("corpus/corpus.wasm");
+
+/// Rebuilds the `Op` of every operator it is shown, with its offset.
+#[derive(Default)]
+struct Rebuild<'a> {
+    ops: Vec<(usize, Op<'a>)>,
+}
+
+/// Overrides the methods of the operators with immediates to rebuild them
+/// from the immediates the visitor was given.
+macro_rules! rebuild {
+    ($($visit:ident => $name:ident($($arg:ident: $ty:ty),+);)+) => {$(
+        fn $visit(&mut self, offset: usize, $($arg: $ty),+) -> Result<(), Error> {
+            self.ops.push((offset, Op::$name($($arg),+)));
+            Ok(())
+        }
+    )+}
+}
+
+impl<'a> OperatorVisitor<'a> for Rebuild<'a> {
+    type Error = Error;
+
+    fn visit_operator(&mut self, offset: usize, code: OpCode) -> Result<(), Error> {
+        let op = code.to_op().unwrap_or_else(|| panic!("{:?} has immediates but was not overridden", code));
+        self.ops.push((offset, op));
+        Ok(())
+    }
+
+    rebuild! {
+        visit_block => Block(ty: InlineSignatureType);
+        visit_loop => Loop(ty: InlineSignatureType);
+        visit_if => If(ty: InlineSignatureType);
+        visit_br => Br(relative_depth: u32);
+        visit_br_if => BrIf(relative_depth: u32);
+        visit_br_table => BrTable(table: BrTable<'a>);
+        visit_call => Call(function_index: u32);
+        visit_call_indirect => CallIndirect(type_index: u32, reserved: bool);
+        visit_get_local => GetLocal(local_index: u32);
+        visit_set_local => SetLocal(local_index: u32);
+        visit_tee_local => TeeLocal(local_index: u32);
+        visit_get_global => GetGlobal(global_index: u32);
+        visit_set_global => SetGlobal(global_index: u32);
+        visit_i32_load => I32Load(memarg: MemoryImmediate);
+        visit_i64_load => I64Load(memarg: MemoryImmediate);
+        visit_f32_load => F32Load(memarg: MemoryImmediate);
+        visit_f64_load => F64Load(memarg: MemoryImmediate);
+        visit_i32_load8_s => I32Load8S(memarg: MemoryImmediate);
+        visit_i32_load8_u => I32Load8U(memarg: MemoryImmediate);
+        visit_i32_load16_s => I32Load16S(memarg: MemoryImmediate);
+        visit_i32_load16_u => I32Load16U(memarg: MemoryImmediate);
+        visit_i64_load8_s => I64Load8S(memarg: MemoryImmediate);
+        visit_i64_load8_u => I64Load8U(memarg: MemoryImmediate);
+        visit_i64_load16_s => I64Load16S(memarg: MemoryImmediate);
+        visit_i64_load16_u => I64Load16U(memarg: MemoryImmediate);
+        visit_i64_load32_s => I64Load32S(memarg: MemoryImmediate);
+        visit_i64_load32_u => I64Load32U(memarg: MemoryImmediate);
+        visit_i32_store => I32Store(memarg: MemoryImmediate);
+        visit_i64_store => I64Store(memarg: MemoryImmediate);
+        visit_f32_store => F32Store(memarg: MemoryImmediate);
+        visit_f64_store => F64Store(memarg: MemoryImmediate);
+        visit_i32_store8 => I32Store8(memarg: MemoryImmediate);
+        visit_i32_store16 => I32Store16(memarg: MemoryImmediate);
+        visit_i64_store8 => I64Store8(memarg: MemoryImmediate);
+        visit_i64_store16 => I64Store16(memarg: MemoryImmediate);
+        visit_i64_store32 => I64Store32(memarg: MemoryImmediate);
+        visit_current_memory => CurrentMemory(reserved: bool);
+        visit_grow_memory => GrowMemory(reserved: bool);
+        visit_i32_const => I32Const(value: i32);
+        visit_i64_const => I64Const(value: i64);
+        visit_f32_const => F32Const(value: f32);
+        visit_f64_const => F64Const(value: f64);
+    }
+}
+
+/// The operators `OpIterator` reads from `data`, each with its offset.
+fn iterate<'a>(data: &'a [u8], config: &ParserConfig) -> Result<Vec<(usize, Op<'a>)>, Error> {
+    let mut iter = OpIterator::with_config(data, config);
+    let mut ops = vec![];
+    loop {
+        let offset = data.len() - iter.iter.len();
+        match iter.next() {
+            Some(op) => ops.push((offset, op?)),
+            None => return Ok(ops),
+        }
+    }
+}
+
+#[test]
+fn corpus_visits_match_op_iterator() {
+    let module = Module::new(CORPUS).unwrap();
+    let mut bodies = 0;
+    for section in module.sections() {
+        let code = match section.unwrap().content() {
+            Ok(SectionContent::Code(code)) => code,
+            _ => continue,
+        };
+        for body in code.entries() {
+            let body = body.unwrap();
+            let mut visitor = Rebuild::default();
+            body.visit_operators(&mut visitor).unwrap();
+            // The offsets of both are relative to the body.
+            let start = body.body.len() - body.skip_locals().unwrap().len();
+            let expected = iterate(&body.body[start..], &body.config).unwrap()
+                .into_iter()
+                .map(|(offset, op)| (start + offset, op))
+                .collect::<Vec<_>>();
+            assert_eq!(visitor.ops, expected, "body {}", bodies);
+            bodies += 1;
+        }
+    }
+    assert_eq!(bodies, 259);
+}
+
+#[test]
+fn max_nesting_is_enforced() {
+    // Three blocks inside the function body, which counts as a level too.
+    let data = [0x02, 0x40, 0x02, 0x40, 0x02, 0x40, 0x0b, 0x0b, 0x0b, 0x0b];
+    let config = |max_nesting| ParserConfig {
+        max_nesting,
+        .. ParserConfig::default()
+    };
+    let mut visitor = Rebuild::default();
+    assert_eq!(visit_operators(&data, &config(4), &mut visitor).unwrap(), data.len());
+    assert_eq!(visitor.ops, iterate(&data, &config(4)).unwrap());
+    match visit_operators(&data, &config(3), &mut Rebuild::default()) {
+        Err(Error::LimitExceeded("control nesting depth", 3)) => (),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    match iterate(&data, &config(3)) {
+        Err(Error::LimitExceeded("control nesting depth", 3)) => (),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,163 @@
+extern crate wasm;
+
+use wasm::ast;
+use wasm::reader::{ValueType, Local};
+use wasm::reader::bytecode::Op;
+use wasm::wat::Style;
+use wasm::writer::{write_varuint, write_name};
+
+/// A module with an imported function and a function of type
+/// `[i32] -> [i32]` that adds one to its parameter, named after `names`.
+fn module(names: Option<Vec<u8>>) -> ast::Module {
+    let mut module = ast::Module {
+        version: 1,
+        .. ast::Module::default()
+    };
+    module.types.push(ast::FunctionType {
+        params: vec![ValueType::I32],
+        return_type: Some(ValueType::I32),
+    });
+    module.imports.push(ast::Import {
+        module: "env".to_owned(),
+        field: "f".to_owned(),
+        kind: ast::ImportKind::Function(0),
+    });
+    module.functions.push(ast::Function {
+        ty: 0,
+        locals: vec![Local {
+            count: 1,
+            ty: ValueType::I64,
+        }],
+        body: vec![Op::GetLocal(0), Op::I32Const(1), Op::I32Add, Op::Call(0), Op::End],
+    });
+    if let Some(payload) = names {
+        module.custom_sections.push(ast::CustomSection {
+            name: "name".to_owned(),
+            payload,
+            after: None,
+        });
+    }
+    module
+}
+
+/// A subsection of a name section in the format of the spec.
+fn subsection(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    write_varuint(out, contents.len() as u64);
+    out.extend_from_slice(contents);
+}
+
+/// Names the imported function `imported`, the other `add one`, and its
+/// parameter and local `x` and `x`.
+fn standard_names() -> Vec<u8> {
+    let mut functions = vec![];
+    write_varuint(&mut functions, 2);
+    for &(index, name) in &[(0, "imported"), (1, "add one")] {
+        write_varuint(&mut functions, index);
+        write_name(&mut functions, name);
+    }
+    let mut locals = vec![];
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 2);
+    for &(index, name) in &[(0, "x"), (1, "x")] {
+        write_varuint(&mut locals, index);
+        write_name(&mut locals, name);
+    }
+    let mut payload = vec![];
+    // The module name, which is not used.
+    subsection(&mut payload, 0, b"\x01m");
+    subsection(&mut payload, 1, &functions);
+    subsection(&mut payload, 2, &locals);
+    payload
+}
+
+/// The same names in the older format, without subsections: each
+/// function in order, followed by its locals.
+fn legacy_names() -> Vec<u8> {
+    let mut payload = vec![];
+    write_varuint(&mut payload, 2);
+    write_name(&mut payload, "imported");
+    write_varuint(&mut payload, 0);
+    write_name(&mut payload, "add one");
+    write_varuint(&mut payload, 2);
+    write_name(&mut payload, "x");
+    write_name(&mut payload, "x");
+    payload
+}
+
+const NAMED: &str = "\
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (import \"env\" \"f\" (func $imported (type 0)))
+  (func $add_one (type 0) (param $x i32) (result i32)
+    (local $x.1 i64)
+    local.get $x
+    i32.const 1
+    i32.add
+    call $imported))
+";
+
+#[test]
+fn flat() {
+    assert_eq!(module(None).to_wat(Style::Flat), "\
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (import \"env\" \"f\" (func (;0;) (type 0)))
+  (func (;1;) (type 0) (param i32) (result i32)
+    (local i64)
+    local.get 0
+    i32.const 1
+    i32.add
+    call 0))
+");
+}
+
+#[test]
+fn folded() {
+    assert_eq!(module(None).to_wat(Style::Folded), "\
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (import \"env\" \"f\" (func (;0;) (type 0)))
+  (func (;1;) (type 0) (param i32) (result i32)
+    (local i64)
+    (call 0
+      (i32.add
+        (local.get 0)
+        (i32.const 1)))))
+");
+}
+
+#[test]
+fn standard_name_section() {
+    assert_eq!(module(Some(standard_names())).to_wat(Style::Flat), NAMED);
+}
+
+#[test]
+fn legacy_name_section() {
+    assert_eq!(module(Some(legacy_names())).to_wat(Style::Flat), NAMED);
+}
+
+#[test]
+fn sparse_local_names() {
+    // Only the local of the second function, by index.
+    let mut locals = vec![];
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_varuint(&mut locals, 1);
+    write_name(&mut locals, "tmp");
+    let mut payload = vec![];
+    subsection(&mut payload, 2, &locals);
+    let wat = module(Some(payload)).to_wat(Style::Folded);
+    assert!(wat.contains("(func (;1;) (type 0) (param i32) (result i32)\n    (local $tmp i64)\n"), "{}", wat);
+    assert!(wat.contains("(call 0\n"), "{}", wat);
+}
+
+#[test]
+fn tags() {
+    let mut module = module(None);
+    module.tags.push(0);
+    let wat = module.to_wat(Style::Flat);
+    assert!(wat.ends_with("\n  (tag (;0;) (type 0)))\n"), "{}", wat);
+}